@@ -0,0 +1,221 @@
+use super::types::{IoTActionHistory, IoTActionRecord, IoTActionTimedOut};
+use crate::communication::types::BasicResponse;
+use crate::communication::{self, data_capturer, data_fetcher};
+use crate::data_store::db_models::DBIoTAction;
+use crate::data_store::sql_execution_handler::ExecutionHandler;
+use crate::integration::providers::IntegrationProvider;
+use crate::logging;
+use crate::state::state::ServerState;
+use crate::state::types::PendingIoTAction;
+use crate::ws_fan::fan;
+use chrono::Utc;
+use futures::lock::Mutex;
+use std::sync::Arc;
+use uuid::Uuid;
+
+// Every action sent to an IoT server gets an id, the
+// integration server echoes it back in its action_response
+// so the response can be matched to who asked for it.
+// Actions are kept in the database for the board owner(as long
+// as the passive history, see board::history), ones that never
+// get a response are timed out by server::setup_iot_action_timeout_task.
+
+/// How long the integration server has to respond to an action
+pub const ACTION_RESPONSE_TIMEOUT_MS: i64 = 10000;
+
+/// Sends the action to the board's IoT server and starts
+/// tracking it, returns the id of the action.
+pub async fn send_action(
+    server_state: &mut ServerState,
+    execution_handler: &mut ExecutionHandler,
+    integration_publish_channel: &Arc<Mutex<lapin::Channel>>,
+    room_id: &i32,
+    external_id: &String,
+    requester_id: Option<i32>,
+    bot_name: String,
+    action: String,
+) -> String {
+    let action_id = Uuid::new_v4().to_string();
+    let now = Utc::now().timestamp_millis();
+    let capture_result = data_capturer::capture_iot_action(
+        execution_handler,
+        &DBIoTAction {
+            id: -1,
+            action_id: action_id.clone(),
+            room_id: room_id.clone(),
+            external_server_id: external_id.clone(),
+            requester_id: requester_id,
+            bot_name: bot_name.clone(),
+            action: action.clone(),
+            status: "pending".to_owned(),
+            response: None,
+            requested_at: now,
+            responded_at: None,
+        },
+    )
+    .await;
+    if capture_result.encountered_error {
+        logging::console::log_failure(&format!(
+            "{} for action({}) on board({})",
+            capture_result.desc, action_id, external_id
+        ));
+    }
+    server_state.pending_iot_actions.insert(
+        action_id.clone(),
+        PendingIoTAction {
+            room_id: room_id.clone(),
+            external_id: external_id.clone(),
+            requester_id: requester_id,
+            bot_name: bot_name.clone(),
+            action: action.clone(),
+            sent_at: now,
+        },
+    );
+    let provider = server_state
+        .rooms
+        .get(room_id)
+        .and_then(|room| room.iot_server_connections.get(external_id))
+        .map_or(IntegrationProvider::default(), |board| board.provider);
+    communication::handler::send_request_to_integration_server(
+        integration_publish_channel,
+        provider.format_action(&action_id, &bot_name, &action),
+        provider.action_category(),
+        external_id.clone(),
+    )
+    .await;
+    return action_id;
+}
+
+/// Stops tracking the action the response belongs to, responses
+/// without an action id can only be matched when the board
+/// has a single pending action.
+pub fn take_responded_action(
+    server_state: &mut ServerState,
+    external_id: &String,
+    action_id: Option<String>,
+) -> Option<(String, PendingIoTAction)> {
+    let action_id = match action_id {
+        Some(action_id) => action_id,
+        None => {
+            let board_action_ids: Vec<&String> = server_state
+                .pending_iot_actions
+                .iter()
+                .filter(|(_, pending)| &pending.external_id == external_id)
+                .map(|(action_id, _)| action_id)
+                .collect();
+            if board_action_ids.len() != 1 {
+                return None;
+            }
+            board_action_ids[0].clone()
+        }
+    };
+    match server_state.pending_iot_actions.get(&action_id) {
+        Some(pending) if &pending.external_id == external_id => {}
+        _ => return None,
+    }
+    let pending = server_state.pending_iot_actions.remove(&action_id)?;
+    return Some((action_id, pending));
+}
+
+pub async fn record_result(
+    execution_handler: &mut ExecutionHandler,
+    action_id: &String,
+    status: &str,
+    response: Option<String>,
+) {
+    let capture_result = data_capturer::capture_iot_action_result(
+        execution_handler,
+        action_id,
+        &status.to_owned(),
+        &response,
+        &Utc::now().timestamp_millis(),
+    )
+    .await;
+    if capture_result.encountered_error {
+        logging::console::log_failure(&format!(
+            "{} for action({})",
+            capture_result.desc, action_id
+        ));
+    }
+}
+
+/// Removes and returns the actions the integration
+/// server didn't respond to in time.
+pub fn take_timed_out_actions(
+    server_state: &mut ServerState,
+    now: i64,
+) -> Vec<(String, PendingIoTAction)> {
+    let timed_out_ids: Vec<String> = server_state
+        .pending_iot_actions
+        .iter()
+        .filter(|(_, pending)| now - pending.sent_at > ACTION_RESPONSE_TIMEOUT_MS)
+        .map(|(action_id, _)| action_id.clone())
+        .collect();
+    return timed_out_ids
+        .into_iter()
+        .filter_map(|action_id| {
+            let pending = server_state.pending_iot_actions.remove(&action_id)?;
+            Some((action_id, pending))
+        })
+        .collect();
+}
+
+/// Records the timed out actions and lets their rooms know
+pub async fn time_out_actions(
+    server_state: &mut ServerState,
+    execution_handler: &mut ExecutionHandler,
+) {
+    let timed_out = take_timed_out_actions(server_state, Utc::now().timestamp_millis());
+    for (action_id, pending) in timed_out {
+        record_result(execution_handler, &action_id, "timed_out", None).await;
+        let response = BasicResponse {
+            response_op_code: "action_timed_out_iot".to_owned(),
+            response_containing_data: serde_json::to_string(&IoTActionTimedOut {
+                action_id: action_id,
+                external_id: pending.external_id,
+                requester_id: pending.requester_id,
+                bot_name: pending.bot_name,
+                action: pending.action,
+            })
+            .unwrap(),
+        };
+        fan::broadcast_message_to_room(
+            serde_json::to_string(&response).unwrap(),
+            server_state,
+            pending.room_id,
+        )
+        .await;
+    }
+}
+
+/// None if the history couldn't be gathered
+pub async fn gather_history(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    external_id: String,
+    limit: i64,
+) -> Option<IoTActionHistory> {
+    let actions =
+        data_fetcher::get_iot_actions_for_board(execution_handler, room_id, &external_id, &limit)
+            .await;
+    if actions.0 {
+        return None;
+    }
+    return Some(IoTActionHistory {
+        external_id: external_id,
+        actions: actions
+            .1
+            .into_iter()
+            .map(|action| IoTActionRecord {
+                action_id: action.action_id,
+                requester_id: action.requester_id,
+                bot_name: action.bot_name,
+                action: action.action,
+                status: action.status,
+                response: action.response,
+                requested_at: action.requested_at,
+                responded_at: action.responded_at,
+            })
+            .collect(),
+    });
+}
@@ -0,0 +1,285 @@
+use super::types::{
+    BoardAutomations, BoardRule, BoardSchedule, CreateBoardRule, CreateBoardSchedule,
+    PendingBoardAction, RuleComparison,
+};
+use super::{actions, passive};
+use crate::communication::types::BasicResponse;
+use crate::communication::{data_capturer, data_fetcher};
+use crate::data_store::sql_execution_handler::ExecutionHandler;
+use crate::logging;
+use crate::state::state::ServerState;
+use crate::state::types::Board;
+use crate::ws_fan::fan;
+use chrono::{TimeZone, Utc};
+use cron::Schedule;
+use futures::lock::Mutex;
+use serde_json::Value;
+use std::str::FromStr;
+use std::sync::Arc;
+use uuid::Uuid;
+
+// Board owners can automate their boards:
+//  - schedules run an action at a point in time(or on a cron schedule)
+//  - rules run an action when incoming passive data meets a condition
+// Both are executed by the automation task(see server.rs) and
+// run through the integration server like any other action,
+// so their results come back as action_response_iot.
+// They're saved with the board(see board::handler) so they
+// come back with it after reconnects and restarts.
+
+pub const MAX_AUTOMATIONS_PER_BOARD: usize = 25;
+
+/// Returns false if the schedule is invalid,
+/// or the board already has too many automations.
+pub fn add_schedule(board: &mut Board, data: CreateBoardSchedule, now: i64) -> bool {
+    if at_automation_limit(board) {
+        return false;
+    }
+    let next_run = match (&data.cron, data.run_at) {
+        (Some(cron), None) => match next_cron_run(cron, now) {
+            Some(next_run) => next_run,
+            None => return false,
+        },
+        (None, Some(run_at)) if run_at > now => run_at,
+        _ => return false,
+    };
+    board.schedules.push(BoardSchedule {
+        schedule_id: Uuid::new_v4().to_string(),
+        bot_name: data.bot_name,
+        action: data.action,
+        cron: data.cron,
+        next_run: next_run,
+    });
+    return true;
+}
+
+/// Returns false if the pointer isn't a json pointer,
+/// or the board already has too many automations.
+pub fn add_rule(board: &mut Board, data: CreateBoardRule) -> bool {
+    if at_automation_limit(board) || !data.pointer.starts_with('/') {
+        return false;
+    }
+    board.rules.push(BoardRule {
+        rule_id: Uuid::new_v4().to_string(),
+        pointer: data.pointer,
+        comparison: data.comparison,
+        value: data.value,
+        bot_name: data.bot_name,
+        action: data.action,
+        condition_met: false,
+    });
+    return true;
+}
+
+/// Returns false if nothing was removed
+pub fn remove_automation(board: &mut Board, automation_id: &String) -> bool {
+    let num_automations = board.schedules.len() + board.rules.len();
+    board
+        .schedules
+        .retain(|schedule| &schedule.schedule_id != automation_id);
+    board.rules.retain(|rule| &rule.rule_id != automation_id);
+    return num_automations != board.schedules.len() + board.rules.len();
+}
+
+pub fn automations(board: &Board) -> BoardAutomations {
+    return BoardAutomations {
+        external_id: board.external_server_id.clone(),
+        schedules: board.schedules.clone(),
+        rules: board.rules.clone(),
+    };
+}
+
+/// Saves the board's current schedules and rules
+pub async fn persist_automations(execution_handler: &mut ExecutionHandler, board: &Board) {
+    let capture_result = data_capturer::capture_iot_board_automations(
+        execution_handler,
+        &board.room_id,
+        &board.external_server_id,
+        &serde_json::to_string(&automations(board)).unwrap(),
+    )
+    .await;
+    if capture_result.encountered_error {
+        logging::console::log_failure(&format!(
+            "{} for board({}) in room({})",
+            capture_result.desc, board.external_server_id, board.room_id
+        ));
+    }
+}
+
+/// Puts the schedules and rules saved for
+/// this board in this room back on it.
+pub async fn restore_automations(execution_handler: &mut ExecutionHandler, board: &mut Board) {
+    let saved = data_fetcher::get_iot_board_automations(
+        execution_handler,
+        &board.room_id,
+        &board.external_server_id,
+    )
+    .await
+    .1
+    .and_then(|saved| serde_json::from_str::<BoardAutomations>(&saved).ok());
+    if let Some(saved) = saved {
+        board.schedules = saved.schedules;
+        board.rules = saved.rules;
+    }
+}
+
+/// Checks the board's rules against a new passive data
+/// snapshot, rules only fire when their condition goes
+/// from unmet to met.
+pub fn evaluate_rules(board: &mut Board, passive_data: &String) -> Vec<PendingBoardAction> {
+    let mut triggered = Vec::new();
+    if board.rules.len() == 0 {
+        return triggered;
+    }
+    let passive_data = passive::parse_passive_data(passive_data);
+    for rule in board.rules.iter_mut() {
+        let condition_met = match passive_data.pointer(&rule.pointer) {
+            Some(actual) => compare(actual, rule.comparison, &rule.value),
+            None => false,
+        };
+        if condition_met && !rule.condition_met {
+            triggered.push(PendingBoardAction {
+                external_id: board.external_server_id.clone(),
+                bot_name: rule.bot_name.clone(),
+                action: rule.action.clone(),
+                automation_id: rule.rule_id.clone(),
+            });
+        }
+        rule.condition_met = condition_met;
+    }
+    return triggered;
+}
+
+/// Gathers the triggered rules and every schedule that is due,
+/// cron schedules move on to their next run while one shot
+/// schedules are removed.
+pub fn take_due_actions(server_state: &mut ServerState, now: i64) -> Vec<PendingBoardAction> {
+    let mut due: Vec<PendingBoardAction> = server_state.pending_board_actions.drain(..).collect();
+    for room in server_state.rooms.values_mut() {
+        for board in room.iot_server_connections.values_mut() {
+            for schedule in board.schedules.iter_mut() {
+                if schedule.next_run > now {
+                    continue;
+                }
+                due.push(PendingBoardAction {
+                    external_id: board.external_server_id.clone(),
+                    bot_name: schedule.bot_name.clone(),
+                    action: schedule.action.clone(),
+                    automation_id: schedule.schedule_id.clone(),
+                });
+                // -1 marks one shot schedules for removal
+                schedule.next_run = match &schedule.cron {
+                    Some(cron) => next_cron_run(cron, now).unwrap_or(-1),
+                    None => -1,
+                };
+            }
+            board.schedules.retain(|schedule| schedule.next_run != -1);
+        }
+    }
+    return due;
+}
+
+/// Sends every due action to the integration server
+pub async fn run_due_actions(
+    server_state: &mut ServerState,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    integration_publish_channel: &Arc<Mutex<lapin::Channel>>,
+) {
+    let due = take_due_actions(server_state, Utc::now().timestamp_millis());
+    if due.len() == 0 {
+        return;
+    }
+    let mut handler = execution_handler.lock().await;
+    // schedules that ran moved on(or were removed)
+    let mut ran_on: Vec<(i32, String)> = Vec::new();
+    for pending_action in due {
+        // the board could have disconnected since
+        let room_id = match server_state
+            .external_servers
+            .get(&pending_action.external_id)
+        {
+            Some(room_id) => room_id.clone(),
+            None => continue,
+        };
+        if !ran_on.contains(&(room_id, pending_action.external_id.clone())) {
+            ran_on.push((room_id.clone(), pending_action.external_id.clone()));
+        }
+        logging::console::log_event(&format!(
+            "Executing automated HOI Action:{:?}",
+            pending_action
+        ));
+        actions::send_action(
+            server_state,
+            &mut handler,
+            integration_publish_channel,
+            &room_id,
+            &pending_action.external_id,
+            None,
+            pending_action.bot_name,
+            pending_action.action,
+        )
+        .await;
+    }
+    for (room_id, external_id) in ran_on {
+        let board = server_state
+            .rooms
+            .get(&room_id)
+            .and_then(|room| room.iot_server_connections.get(&external_id));
+        if let Some(board) = board {
+            persist_automations(&mut handler, board).await;
+        }
+    }
+}
+
+/// Lets the room know the board's automations changed
+pub async fn broadcast_automations(
+    server_state: &mut ServerState,
+    room_id: i32,
+    external_id: &String,
+) {
+    let board = server_state
+        .rooms
+        .get(&room_id)
+        .and_then(|room| room.iot_server_connections.get(external_id));
+    if let Some(board) = board {
+        let response = BasicResponse {
+            response_op_code: "board_automations".to_owned(),
+            response_containing_data: serde_json::to_string(&automations(board)).unwrap(),
+        };
+        fan::broadcast_message_to_room(
+            serde_json::to_string(&response).unwrap(),
+            server_state,
+            room_id,
+        )
+        .await;
+    }
+}
+
+fn at_automation_limit(board: &Board) -> bool {
+    return board.schedules.len() + board.rules.len() >= MAX_AUTOMATIONS_PER_BOARD;
+}
+
+fn next_cron_run(cron: &String, now: i64) -> Option<i64> {
+    let schedule = Schedule::from_str(cron).ok()?;
+    let next = schedule.after(&Utc.timestamp_millis(now)).next()?;
+    return Some(next.timestamp_millis());
+}
+
+fn compare(actual: &Value, comparison: RuleComparison, expected: &Value) -> bool {
+    if let (Some(actual), Some(expected)) = (actual.as_f64(), expected.as_f64()) {
+        return match comparison {
+            RuleComparison::GreaterThan => actual > expected,
+            RuleComparison::GreaterThanOrEqual => actual >= expected,
+            RuleComparison::LessThan => actual < expected,
+            RuleComparison::LessThanOrEqual => actual <= expected,
+            RuleComparison::Equal => actual == expected,
+            RuleComparison::NotEqual => actual != expected,
+        };
+    }
+    // anything that isn't a number can only be checked for equality
+    return match comparison {
+        RuleComparison::Equal => actual == expected,
+        RuleComparison::NotEqual => actual != expected,
+        _ => false,
+    };
+}
@@ -0,0 +1,347 @@
+use crate::board::automation;
+use crate::board::types::BoardStatus;
+use crate::communication::types::{BasicResponse, BoardOwnerChanged, IoTControllerScope};
+use crate::communication::{data_capturer, data_fetcher};
+use crate::data_store::db_models::{DBIoTBoard, DBIoTBoardPermission};
+use crate::data_store::sql_execution_handler::ExecutionHandler;
+use crate::integration::credentials;
+use crate::integration::providers::IntegrationProvider;
+use crate::integration::types::StoredCredentials;
+use crate::logging;
+use crate::state::state::ServerState;
+use crate::state::types::{Board, BoardGrant, PassiveFanOut};
+use crate::ws_fan::fan;
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+
+// Boards live in the room's state while connected,
+// the database keeps them(and their controllers) around
+// so they survive their owner leaving and restarts.
+//  - A board reconnecting to the same room gets its controllers back.
+//  - Owners rejoining a room get their boards reconnected
+//    if the credentials were remembered.
+//  - Persistent rooms get their boards back on startup.
+//  - Automations(schedules/rules) are restored along with the board.
+//  - Explicitly disconnecting a board forgets it.
+
+/// Saves a newly connected board, restoring the
+/// controllers it had the last time it was
+/// connected to this room.
+pub async fn persist_and_restore_board(
+    execution_handler: &mut ExecutionHandler,
+    board: &mut Board,
+) {
+    let mut permissions =
+        data_fetcher::get_iot_board_permissions_for_room(execution_handler, &board.room_id).await;
+    if let Some(db_permissions) = permissions.1.remove(&board.external_server_id) {
+        board.users_with_permission = grants_from_db_permissions(db_permissions);
+    }
+    automation::restore_automations(execution_handler, board).await;
+    let capture_result =
+        data_capturer::capture_iot_board(execution_handler, &db_board(board)).await;
+    if capture_result.encountered_error {
+        logging::console::log_failure(&format!(
+            "{} for board({}) in room({})",
+            capture_result.desc, board.external_server_id, board.room_id
+        ));
+    }
+}
+
+/// Saves the user's current grant for the board,
+/// or removes it if they no longer have one.
+pub async fn persist_board_permission(
+    execution_handler: &mut ExecutionHandler,
+    board: &Board,
+    user_id: &i32,
+) {
+    let grant = board.users_with_permission.get(user_id);
+    let permission = DBIoTBoardPermission {
+        id: -1,
+        room_id: board.room_id.clone(),
+        external_server_id: board.external_server_id.clone(),
+        user_id: user_id.clone(),
+        bots: grant.and_then(|grant| scope_to_json(&grant.bots)),
+        actions: grant.and_then(|grant| scope_to_json(&grant.actions)),
+    };
+    let capture_result = if grant.is_some() {
+        data_capturer::capture_iot_board_permission(execution_handler, &permission).await
+    } else {
+        data_capturer::capture_iot_board_permission_removal(execution_handler, &permission).await
+    };
+    if capture_result.encountered_error {
+        logging::console::log_failure(&format!(
+            "{} for board({}) in room({})",
+            capture_result.desc, board.external_server_id, board.room_id
+        ));
+    }
+}
+
+/// Only used when the owner disconnects the
+/// board on purpose, leaving keeps it around.
+pub async fn forget_board(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    external_server_id: &String,
+) {
+    let capture_result =
+        data_capturer::capture_iot_board_removal(execution_handler, room_id, external_server_id)
+            .await;
+    if capture_result.encountered_error {
+        logging::console::log_failure(&format!(
+            "{} for board({}) in room({})",
+            capture_result.desc, external_server_id, room_id
+        ));
+    }
+}
+
+/// Saves credentials the owner asked us to remember,
+/// they're already encrypted with the storage key.
+pub async fn remember_credentials(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    external_server_id: &String,
+    credentials: String,
+) {
+    let capture_result = data_capturer::capture_iot_board_credentials(
+        execution_handler,
+        room_id,
+        external_server_id,
+        &Some(credentials),
+    )
+    .await;
+    if capture_result.encountered_error {
+        logging::console::log_failure(&format!(
+            "{} for board({}) in room({})",
+            capture_result.desc, external_server_id, room_id
+        ));
+    }
+}
+
+/// None if nothing was remembered, or it
+/// can't be decrypted with the current storage key.
+pub async fn remembered_credentials(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    external_server_id: &String,
+) -> Option<StoredCredentials> {
+    let encrypted =
+        data_fetcher::get_iot_board_credentials(execution_handler, room_id, external_server_id)
+            .await
+            .1?;
+    let decrypted = credentials::decrypt(&credentials::storage_key()?, &encrypted)?;
+    return serde_json::from_str(&decrypted).ok();
+}
+
+/// Puts the saved boards of a room back into state,
+/// used for persistent rooms rehydrated on startup since
+/// the integration server kept their connections alive.
+pub async fn restore_boards_for_room(
+    server_state: &mut ServerState,
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+) -> usize {
+    let boards = data_fetcher::get_iot_boards_for_room(execution_handler, room_id).await;
+    let mut permissions =
+        data_fetcher::get_iot_board_permissions_for_room(execution_handler, room_id).await;
+    if boards.0 || permissions.0 {
+        logging::console::log_failure(&format!("Issue restoring boards for room({})", room_id));
+        return 0;
+    }
+    let mut num_restored = 0;
+    let now = Utc::now().timestamp_millis();
+    if let Some(room) = server_state.rooms.get_mut(room_id) {
+        for db_board in boards.1 {
+            let users_with_permission = grants_from_db_permissions(
+                permissions
+                    .1
+                    .remove(&db_board.external_server_id)
+                    .unwrap_or_default(),
+            );
+            server_state
+                .external_servers
+                .insert(db_board.external_server_id.clone(), room_id.clone());
+            let mut board = Board {
+                room_id: room_id.clone(),
+                owner_user_id: db_board.owner_id,
+                users_with_permission: users_with_permission,
+                external_server_id: db_board.external_server_id,
+                passive_data_snapshot: None,
+                passive_fan_out: PassiveFanOut::default(),
+                outside_name: db_board.outside_name,
+                provider: IntegrationProvider::from_name(&db_board.provider).unwrap_or_default(),
+                // given a full stale threshold to come back
+                last_seen: now,
+                status: BoardStatus::Online,
+                integration_server_id: None,
+                schedules: Vec::new(),
+                rules: Vec::new(),
+            };
+            automation::restore_automations(execution_handler, &mut board).await;
+            room.iot_server_connections
+                .insert(board.external_server_id.clone(), board);
+            num_restored += 1;
+        }
+    }
+    return num_restored;
+}
+
+/// Hands a board over to someone else in its room,
+/// returns false if the board doesn't exist.
+pub async fn transfer_board(
+    server_state: &mut ServerState,
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    external_server_id: &String,
+    new_owner_id: &i32,
+) -> bool {
+    let board_option = server_state
+        .rooms
+        .get_mut(room_id)
+        .and_then(|room| room.iot_server_connections.get_mut(external_server_id));
+    let board = match board_option {
+        Some(board) => board,
+        None => return false,
+    };
+    let owner_changed = BoardOwnerChanged {
+        external_id: external_server_id.clone(),
+        old_owner_id: board.owner_user_id.clone(),
+        new_owner_id: new_owner_id.clone(),
+        outside_name: board.outside_name.clone(),
+    };
+    board.owner_user_id = new_owner_id.clone();
+    // owners always have permission
+    if board.users_with_permission.remove(new_owner_id).is_some() {
+        persist_board_permission(execution_handler, board, new_owner_id).await;
+    }
+    let capture_result =
+        data_capturer::capture_iot_board(execution_handler, &db_board(board)).await;
+    if capture_result.encountered_error {
+        logging::console::log_failure(&format!(
+            "{} for board({}) in room({})",
+            capture_result.desc, external_server_id, room_id
+        ));
+    }
+    let response = BasicResponse {
+        response_op_code: "board_owner_changed".to_owned(),
+        response_containing_data: serde_json::to_string(&owner_changed).unwrap(),
+    };
+    fan::broadcast_message_to_room(
+        serde_json::to_string(&response).unwrap(),
+        server_state,
+        room_id.clone(),
+    )
+    .await;
+    return true;
+}
+
+/// Hands every board the user owns in this room over to the
+/// new owner instead of disconnecting them, returns the
+/// external ids of the boards that changed hands.
+pub async fn transfer_owned_boards(
+    server_state: &mut ServerState,
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    current_owner_id: &i32,
+    new_owner_id: &i32,
+) -> Vec<String> {
+    let owned: Vec<String> = match server_state.rooms.get(room_id) {
+        Some(room) => room
+            .iot_server_connections
+            .values()
+            .filter(|board| &board.owner_user_id == current_owner_id)
+            .map(|board| board.external_server_id.clone())
+            .collect(),
+        None => Vec::new(),
+    };
+    for external_server_id in owned.iter() {
+        transfer_board(
+            server_state,
+            execution_handler,
+            room_id,
+            external_server_id,
+            new_owner_id,
+        )
+        .await;
+    }
+    return owned;
+}
+
+/// Owners can do anything, controllers only
+/// what their grant is scoped to.
+pub fn can_run_action(board: &Board, user_id: &i32, bot_name: &str, action: &str) -> bool {
+    if &board.owner_user_id == user_id {
+        return true;
+    }
+    return match board.users_with_permission.get(user_id) {
+        Some(grant) => scope_allows(&grant.bots, bot_name) && scope_allows(&grant.actions, action),
+        None => false,
+    };
+}
+
+pub fn grant_from_scopes(bots: Option<Vec<String>>, actions: Option<Vec<String>>) -> BoardGrant {
+    return BoardGrant {
+        bots: bots.map(|bots| bots.into_iter().collect()),
+        actions: actions.map(|actions| actions.into_iter().collect()),
+    };
+}
+
+pub fn controller_scopes(board: &Board) -> Vec<IoTControllerScope> {
+    return board
+        .users_with_permission
+        .iter()
+        .map(|(user_id, grant)| IoTControllerScope {
+            user_id: user_id.clone(),
+            bots: scope_to_vec(&grant.bots),
+            actions: scope_to_vec(&grant.actions),
+        })
+        .collect();
+}
+
+pub fn scope_to_vec(scope: &Option<HashSet<String>>) -> Option<Vec<String>> {
+    return scope.as_ref().map(|scope| scope.iter().cloned().collect());
+}
+
+fn scope_allows(scope: &Option<HashSet<String>>, name: &str) -> bool {
+    return match scope {
+        Some(scope) => scope.contains(name),
+        None => true,
+    };
+}
+
+fn scope_to_json(scope: &Option<HashSet<String>>) -> Option<String> {
+    return scope_to_vec(scope).map(|scope| serde_json::to_string(&scope).unwrap());
+}
+
+// scopes that fail to parse lock the controller out
+// rather than giving them full control.
+fn scope_from_json(scope: Option<String>) -> Option<HashSet<String>> {
+    return scope.map(|scope| serde_json::from_str(&scope).unwrap_or_default());
+}
+
+fn grants_from_db_permissions(
+    db_permissions: Vec<DBIoTBoardPermission>,
+) -> HashMap<i32, BoardGrant> {
+    return db_permissions
+        .into_iter()
+        .map(|permission| {
+            (
+                permission.user_id,
+                BoardGrant {
+                    bots: scope_from_json(permission.bots),
+                    actions: scope_from_json(permission.actions),
+                },
+            )
+        })
+        .collect();
+}
+
+fn db_board(board: &Board) -> DBIoTBoard {
+    return DBIoTBoard {
+        id: -1,
+        room_id: board.room_id.clone(),
+        external_server_id: board.external_server_id.clone(),
+        owner_id: board.owner_user_id.clone(),
+        outside_name: board.outside_name.clone(),
+        provider: board.provider.name().to_owned(),
+    };
+}
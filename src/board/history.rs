@@ -0,0 +1,115 @@
+use super::types::{GetIoTPassiveHistory, IoTPassiveHistory, PassiveDataSample};
+use crate::communication::{data_capturer, data_fetcher};
+use crate::data_store::db_models::DBIoTPassiveData;
+use crate::data_store::sql_execution_handler::ExecutionHandler;
+use crate::logging;
+use chrono::Utc;
+
+// Every passive data snapshot a board changes to is kept
+// for charting, the board itself only holds the latest.
+// Samples older than the retention are pruned by
+// server::setup_passive_history_retention_task.
+
+/// The most samples a single history request returns,
+/// the newest are kept when there are more.
+pub const MAX_HISTORY_SAMPLES: i64 = 5000;
+
+/// MERLIN_PASSIVE_HISTORY_RETENTION_DAYS is clamped to these
+pub const MIN_RETENTION_DAYS: i64 = 1;
+pub const MAX_RETENTION_DAYS: i64 = 3650;
+
+pub async fn persist_passive_data(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    external_server_id: &String,
+    data: &String,
+) {
+    let capture_result = data_capturer::capture_iot_passive_data(
+        execution_handler,
+        &DBIoTPassiveData {
+            id: -1,
+            room_id: room_id.clone(),
+            external_server_id: external_server_id.clone(),
+            data: data.clone(),
+            captured_at: Utc::now().timestamp_millis(),
+        },
+    )
+    .await;
+    if capture_result.encountered_error {
+        logging::console::log_failure(&format!(
+            "{} for board({}) in room({})",
+            capture_result.desc, external_server_id, room_id
+        ));
+    }
+}
+
+/// None if the time range or bucket size is invalid
+pub async fn gather_history(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    request: GetIoTPassiveHistory,
+) -> Option<IoTPassiveHistory> {
+    if request.from > request.to || request.bucket_ms.map_or(false, |bucket_ms| bucket_ms < 1) {
+        return None;
+    }
+    // without downsampling every millisecond is its own bucket
+    let bucket_ms = request.bucket_ms.unwrap_or(1);
+    let history = data_fetcher::get_iot_passive_history(
+        execution_handler,
+        room_id,
+        &request.external_id,
+        &request.from,
+        &request.to,
+        &bucket_ms,
+        &MAX_HISTORY_SAMPLES,
+    )
+    .await;
+    if history.0 {
+        return None;
+    }
+    return Some(IoTPassiveHistory {
+        external_id: request.external_id,
+        from: request.from,
+        to: request.to,
+        bucket_ms: request.bucket_ms,
+        // gathered newest first so the limit drops the oldest
+        samples: history
+            .1
+            .into_iter()
+            .rev()
+            .map(|sample| PassiveDataSample {
+                captured_at: sample.captured_at,
+                data: sample.data,
+            })
+            .collect(),
+    });
+}
+
+/// Returns the number of passive data samples and
+/// actions(see board::actions) removed.
+pub async fn prune_expired_history(execution_handler: &mut ExecutionHandler) -> (u64, u64) {
+    let oldest_kept = Utc::now()
+        .timestamp_millis()
+        .saturating_sub(retention_millis());
+    let num_samples_removed =
+        data_capturer::capture_expired_iot_passive_data_removal(execution_handler, &oldest_kept)
+            .await;
+    let num_actions_removed =
+        data_capturer::capture_expired_iot_action_removal(execution_handler, &oldest_kept).await;
+    return (num_samples_removed, num_actions_removed);
+}
+
+fn retention_millis() -> i64 {
+    let days: i64 = std::env::var("MERLIN_PASSIVE_HISTORY_RETENTION_DAYS")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(7);
+    return retention_days_to_millis(days);
+}
+
+pub fn retention_days_to_millis(days: i64) -> i64 {
+    return days
+        .clamp(MIN_RETENTION_DAYS, MAX_RETENTION_DAYS)
+        .checked_mul(24 * 60 * 60 * 1000)
+        .unwrap_or(i64::MAX);
+}
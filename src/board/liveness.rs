@@ -0,0 +1,180 @@
+use super::types::{BoardStatus, BoardStatusChanged};
+use crate::communication::types::BasicResponse;
+use crate::integration::types::IntegrationServerHeartbeat;
+use crate::logging;
+use crate::state::state::ServerState;
+use crate::state::types::Board;
+use crate::ws_fan::fan;
+use chrono::Utc;
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+static STALE_THRESHOLD_MS: OnceLock<i64> = OnceLock::new();
+
+// Boards only go away when the integration server says so,
+// so we keep track of whether they're actually still there:
+//  - every passive_data/action_response marks its board as seen,
+//    boards not seen within the stale threshold are unresponsive
+//  - integration servers send heartbeats, when those stop every
+//    board connected through that server is flagged since none
+//    of them can be reached
+// Rooms are told whenever a board's status changes, by
+// server::setup_board_liveness_task.
+
+/// Integration servers that haven't sent a heartbeat
+/// within this window are considered gone.
+pub const INTEGRATION_HEARTBEAT_TIMEOUT_MS: i64 = 15000;
+
+pub const DEFAULT_STALE_SECONDS: i64 = 60;
+
+pub fn handle_heartbeat(msg: String, server_state: &mut ServerState) {
+    let heartbeat: IntegrationServerHeartbeat = match serde_json::from_str(&msg) {
+        Ok(heartbeat) => heartbeat,
+        Err(_) => {
+            logging::console::log_failure("Invalid integration server heartbeat");
+            return;
+        }
+    };
+    let now = Utc::now().timestamp_millis();
+    let last_heartbeat = server_state
+        .integration_heartbeats
+        .insert(heartbeat.integration_server_id.clone(), now);
+    if last_heartbeat.map_or(true, |last_heartbeat| !heartbeat_alive(last_heartbeat, now)) {
+        logging::console::log_event(&format!(
+            "Integration server({}) is sending heartbeats",
+            heartbeat.integration_server_id
+        ));
+    }
+}
+
+pub fn mark_seen(server_state: &mut ServerState, external_id: &String, now: i64) {
+    let board = server_state
+        .external_servers
+        .get(external_id)
+        .and_then(|room_id| server_state.rooms.get_mut(room_id))
+        .and_then(|room| room.iot_server_connections.get_mut(external_id));
+    if let Some(board) = board {
+        board.last_seen = now;
+    }
+}
+
+/// Updates every board's status, returning the
+/// changes along with the room they're in.
+pub fn take_status_changes(
+    server_state: &mut ServerState,
+    now: i64,
+) -> Vec<(i32, BoardStatusChanged)> {
+    let stale_after = stale_threshold_millis();
+    let mut changes = Vec::new();
+    for room in server_state.rooms.values_mut() {
+        for board in room.iot_server_connections.values_mut() {
+            let integration_alive = integration_server_alive(
+                &server_state.integration_heartbeats,
+                &board.integration_server_id,
+                now,
+            );
+            let status = current_status(board, integration_alive, stale_after, now);
+            if status == board.status {
+                continue;
+            }
+            board.status = status;
+            changes.push((
+                room.room_id,
+                BoardStatusChanged {
+                    external_id: board.external_server_id.clone(),
+                    status: status,
+                    last_seen: board.last_seen,
+                },
+            ));
+        }
+    }
+    return changes;
+}
+
+/// Lets the rooms know which of their boards changed status
+pub async fn broadcast_status_changes(server_state: &mut ServerState) {
+    let changes = take_status_changes(server_state, Utc::now().timestamp_millis());
+    for (room_id, change) in changes {
+        let response = BasicResponse {
+            response_op_code: "iot_board_status".to_owned(),
+            response_containing_data: serde_json::to_string(&change).unwrap(),
+        };
+        fan::broadcast_message_to_room(
+            serde_json::to_string(&response).unwrap(),
+            server_state,
+            room_id,
+        )
+        .await;
+    }
+}
+
+/// Integration servers that never sent a heartbeat are assumed
+/// to be alive. Boards that don't know their integration server
+/// are only flagged once every integration server is gone.
+fn integration_server_alive(
+    integration_heartbeats: &HashMap<String, i64>,
+    integration_server_id: &Option<String>,
+    now: i64,
+) -> bool {
+    return match integration_server_id {
+        Some(integration_server_id) => integration_heartbeats
+            .get(integration_server_id)
+            .map_or(true, |last_heartbeat| heartbeat_alive(*last_heartbeat, now)),
+        None => {
+            integration_heartbeats.is_empty()
+                || integration_heartbeats
+                    .values()
+                    .any(|last_heartbeat| heartbeat_alive(*last_heartbeat, now))
+        }
+    };
+}
+
+fn heartbeat_alive(last_heartbeat: i64, now: i64) -> bool {
+    return now - last_heartbeat <= INTEGRATION_HEARTBEAT_TIMEOUT_MS;
+}
+
+fn current_status(
+    board: &Board,
+    integration_alive: bool,
+    stale_after: i64,
+    now: i64,
+) -> BoardStatus {
+    if !integration_alive {
+        return BoardStatus::IntegrationDown;
+    }
+    if now - board.last_seen > stale_after {
+        return BoardStatus::Unresponsive;
+    }
+    return BoardStatus::Online;
+}
+
+/// MERLIN_BOARD_STALE_SECONDS(60 by default), read once
+fn stale_threshold_millis() -> i64 {
+    return *STALE_THRESHOLD_MS.get_or_init(|| {
+        parse_stale_threshold_millis(std::env::var("MERLIN_BOARD_STALE_SECONDS").ok())
+    });
+}
+
+/// Anything that isn't a positive number of
+/// seconds falls back to the default.
+pub fn parse_stale_threshold_millis(value: Option<String>) -> i64 {
+    let value = match value {
+        Some(value) => value,
+        None => return DEFAULT_STALE_SECONDS * 1000,
+    };
+    let millis = value
+        .parse::<i64>()
+        .ok()
+        .filter(|seconds| *seconds > 0)
+        .and_then(|seconds| seconds.checked_mul(1000));
+    return match millis {
+        Some(millis) => millis,
+        None => {
+            logging::console::log_failure(&format!(
+                "Invalid MERLIN_BOARD_STALE_SECONDS({}), using {} seconds",
+                value, DEFAULT_STALE_SECONDS
+            ));
+            DEFAULT_STALE_SECONDS * 1000
+        }
+    };
+}
@@ -0,0 +1,147 @@
+use super::types::PassiveDataDiff;
+use crate::common::throttle;
+use crate::communication::types::{BasicResponse, PassiveData};
+use crate::state::state::ServerState;
+use crate::state::types::Board;
+use crate::ws_fan::fan;
+use chrono::Utc;
+use serde_json::{Map, Value};
+
+// Boards can report passive data far faster than
+// clients need it, so the room only hears about a
+// board's passive data when it actually changed and
+// at most once per window. Users in diff mode get the
+// changed fields(a json merge patch) instead of
+// the whole snapshot.
+
+/// Passive data is fanned out at most
+/// once per window for each board.
+pub const PASSIVE_FAN_OUT_THROTTLE_MS: i64 = 500;
+
+/// Returns false if the snapshot is identical
+/// to the current one, which is suppressed.
+pub fn update_snapshot(board: &mut Board, passive_data: String) -> bool {
+    if board.passive_data_snapshot.as_ref() == Some(&passive_data) {
+        return false;
+    }
+    board.passive_data_snapshot = Some(passive_data);
+    board.passive_fan_out.throttle.dirty = true;
+    return true;
+}
+
+/// The full snapshot and the diff against the last fanned
+/// out one, if the board changed and the throttle window passed.
+pub fn take_due_fan_out(board: &mut Board, now: i64) -> Option<(String, Value)> {
+    let fan_out = &mut board.passive_fan_out;
+    if !throttle::take_if_due(&mut fan_out.throttle, now, PASSIVE_FAN_OUT_THROTTLE_MS) {
+        return None;
+    }
+    let snapshot = board.passive_data_snapshot.clone()?;
+    let previous = match &fan_out.fanned_out_snapshot {
+        Some(previous) => parse_passive_data(previous),
+        None => Value::Null,
+    };
+    let diff = merge_diff(&previous, &parse_passive_data(&snapshot));
+    fan_out.fanned_out_snapshot = Some(snapshot.clone());
+    return Some((snapshot, diff));
+}
+
+/// Fans out the board's passive data if it changed and the
+/// throttle window passed, otherwise the change waits for
+/// the next snapshot or the flush task.
+pub async fn fan_out_if_due(state: &mut ServerState, room_id: &i32, external_id: &String) {
+    let now = Utc::now().timestamp_millis();
+    let due = state
+        .rooms
+        .get_mut(room_id)
+        .and_then(|room| room.iot_server_connections.get_mut(external_id))
+        .and_then(|board| take_due_fan_out(board, now));
+    let (snapshot, diff) = match due {
+        Some(due) => due,
+        None => return,
+    };
+    let full_response = serde_json::to_string(&BasicResponse {
+        response_op_code: "passive_data".to_owned(),
+        response_containing_data: serde_json::to_string(&PassiveData {
+            external_id: external_id.clone(),
+            passive_data: snapshot,
+        })
+        .unwrap(),
+    })
+    .unwrap();
+    let diff_response = serde_json::to_string(&BasicResponse {
+        response_op_code: "passive_data_diff".to_owned(),
+        response_containing_data: serde_json::to_string(&PassiveDataDiff {
+            external_id: external_id.clone(),
+            diff: diff,
+        })
+        .unwrap(),
+    })
+    .unwrap();
+    let user_ids: Vec<i32> = match state.rooms.get(room_id) {
+        Some(room) => room.user_ids.iter().cloned().collect(),
+        None => return,
+    };
+    for user_id in user_ids {
+        let wants_diffs = state
+            .active_users
+            .get(&user_id)
+            .map_or(false, |user| user.passive_data_diffs);
+        let response = if wants_diffs {
+            diff_response.clone()
+        } else {
+            full_response.clone()
+        };
+        fan::broadcast_message_to_single_user(response, state, &user_id).await;
+    }
+}
+
+pub async fn flush_throttled_updates(state: &mut ServerState) {
+    let dirty: Vec<(i32, String)> = state
+        .rooms
+        .values()
+        .flat_map(|room| room.iot_server_connections.values())
+        .filter(|board| board.passive_fan_out.throttle.dirty)
+        .map(|board| (board.room_id.clone(), board.external_server_id.clone()))
+        .collect();
+    for (room_id, external_id) in dirty {
+        fan_out_if_due(state, &room_id, &external_id).await;
+    }
+}
+
+/// Passive data usually arrives as a json string
+pub fn parse_passive_data(passive_data: &String) -> Value {
+    let value: Value = serde_json::from_str(passive_data).unwrap_or(Value::Null);
+    if let Value::String(inner) = &value {
+        return serde_json::from_str(inner).unwrap_or(Value::Null);
+    }
+    return value;
+}
+
+/// A json merge patch(RFC 7386) turning previous into current,
+/// removed fields are null and anything that isn't an
+/// object is replaced as a whole.
+pub fn merge_diff(previous: &Value, current: &Value) -> Value {
+    let (previous, current) = match (previous, current) {
+        (Value::Object(previous), Value::Object(current)) => (previous, current),
+        _ => return current.clone(),
+    };
+    let mut diff = Map::new();
+    for (key, current_value) in current {
+        match previous.get(key) {
+            Some(previous_value) if previous_value == current_value => {}
+            Some(previous_value) => {
+                diff.insert(key.clone(), merge_diff(previous_value, current_value));
+            }
+            None => {
+                diff.insert(key.clone(), current_value.clone());
+            }
+        }
+    }
+    for key in previous.keys() {
+        if !current.contains_key(key) {
+            diff.insert(key.clone(), Value::Null);
+        }
+    }
+    return Value::Object(diff);
+}
@@ -0,0 +1,216 @@
+use serde::{Deserialize, Serialize};
+
+/// An action the board owner scheduled, cron schedules
+/// repeat while one shot schedules are removed once they run.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct BoardSchedule {
+    pub schedule_id: String,
+    pub bot_name: String,
+    pub action: String,
+    /// sec min hour day-of-month month day-of-week(year)
+    pub cron: Option<String>,
+    pub next_run: i64, //unix millis
+}
+
+/// Runs an action when a passive data value meets
+/// the condition, once each time it starts being met.
+#[derive(Deserialize, Serialize, Clone)]
+pub struct BoardRule {
+    pub rule_id: String,
+    /// json pointer into the passive data, e.g. /thermostat/temperature
+    pub pointer: String,
+    pub comparison: RuleComparison,
+    pub value: serde_json::Value,
+    pub bot_name: String,
+    pub action: String,
+    #[serde(skip)]
+    pub condition_met: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq)]
+pub enum RuleComparison {
+    #[serde(rename = ">")]
+    GreaterThan,
+    #[serde(rename = ">=")]
+    GreaterThanOrEqual,
+    #[serde(rename = "<")]
+    LessThan,
+    #[serde(rename = "<=")]
+    LessThanOrEqual,
+    #[serde(rename = "==")]
+    Equal,
+    #[serde(rename = "!=")]
+    NotEqual,
+}
+
+/// An automation that is due, waiting on
+/// the automation task to send it.
+#[derive(Debug, PartialEq)]
+pub struct PendingBoardAction {
+    pub external_id: String,
+    pub bot_name: String,
+    pub action: String,
+    /// the schedule/rule that triggered it
+    pub automation_id: String,
+}
+
+/// Exactly one of cron/run_at has to be given
+#[derive(Deserialize, Serialize)]
+pub struct CreateBoardSchedule {
+    pub external_id: String,
+    pub bot_name: String,
+    pub action: String,
+    #[serde(default)]
+    pub cron: Option<String>,
+    #[serde(default)]
+    pub run_at: Option<i64>, //unix millis
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct CreateBoardRule {
+    pub external_id: String,
+    pub pointer: String,
+    pub comparison: RuleComparison,
+    pub value: serde_json::Value,
+    pub bot_name: String,
+    pub action: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RemoveBoardAutomation {
+    pub external_id: String,
+    /// a schedule or rule id
+    pub automation_id: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetBoardAutomations {
+    pub external_id: String,
+}
+
+/// Sent whenever a board's automations change
+#[derive(Deserialize, Serialize)]
+pub struct BoardAutomations {
+    pub external_id: String,
+    pub schedules: Vec<BoardSchedule>,
+    pub rules: Vec<BoardRule>,
+}
+
+/// from/to are unix millis(inclusive), bucket_ms downsamples
+/// the history to the latest sample of each bucket.
+#[derive(Deserialize, Serialize)]
+pub struct GetIoTPassiveHistory {
+    pub external_id: String,
+    pub from: i64,
+    pub to: i64,
+    #[serde(default)]
+    pub bucket_ms: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct PassiveDataSample {
+    pub captured_at: i64, //unix millis
+    pub data: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct IoTPassiveHistory {
+    pub external_id: String,
+    pub from: i64,
+    pub to: i64,
+    pub bucket_ms: Option<i64>,
+    /// oldest first
+    pub samples: Vec<PassiveDataSample>,
+}
+
+/// Switches the requester between full passive
+/// data snapshots and diffs, see board::passive.
+#[derive(Deserialize, Serialize)]
+pub struct SetPassiveDataMode {
+    pub diff: bool,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct PassiveDataDiff {
+    pub external_id: String,
+    /// a json merge patch against the previous snapshot
+    pub diff: serde_json::Value,
+}
+
+/// Sent to the requester once their action is on its way,
+/// the action id links it to its action_response_iot.
+#[derive(Deserialize, Serialize)]
+pub struct IoTActionRequested {
+    pub action_id: String,
+    pub external_id: String,
+    pub bot_name: String,
+    pub action: String,
+}
+
+/// An action the integration server never responded to
+#[derive(Deserialize, Serialize)]
+pub struct IoTActionTimedOut {
+    pub action_id: String,
+    pub external_id: String,
+    /// None for automations
+    pub requester_id: Option<i32>,
+    pub bot_name: String,
+    pub action: String,
+}
+
+/// How sure we are the board is still there
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum BoardStatus {
+    #[default]
+    #[serde(rename = "online")]
+    Online,
+    /// nothing came from the board within the stale threshold
+    #[serde(rename = "unresponsive")]
+    Unresponsive,
+    /// the integration server itself stopped sending heartbeats
+    #[serde(rename = "integration_down")]
+    IntegrationDown,
+}
+
+/// Broadcasted to the room when a board's status changes
+#[derive(Deserialize, Serialize, Debug, PartialEq)]
+pub struct BoardStatusChanged {
+    pub external_id: String,
+    pub status: BoardStatus,
+    pub last_seen: i64,
+}
+
+/// Reconnects a board with the credentials
+/// its owner asked Merlin to remember.
+#[derive(Deserialize, Serialize)]
+pub struct ReconnectIoTBoard {
+    pub external_id: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetIoTActionHistory {
+    pub external_id: String,
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct IoTActionRecord {
+    pub action_id: String,
+    /// None for automations
+    pub requester_id: Option<i32>,
+    pub bot_name: String,
+    pub action: String,
+    /// pending, responded or timed_out
+    pub status: String,
+    pub response: Option<String>,
+    pub requested_at: i64,         //unix millis
+    pub responded_at: Option<i64>, //unix millis
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct IoTActionHistory {
+    pub external_id: String,
+    /// newest first
+    pub actions: Vec<IoTActionRecord>,
+}
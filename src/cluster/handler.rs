@@ -0,0 +1,135 @@
+use crate::cluster::types::{ClusterConfig, ClusterMessage, UserPresence};
+use crate::communication::data_capturer;
+use crate::data_store::sql_execution_handler::ExecutionHandler;
+use crate::logging;
+use crate::state::state::ServerState;
+use chrono::Utc;
+use std::env;
+use std::fs;
+use std::sync::OnceLock;
+
+static CLUSTER_CONFIG: OnceLock<ClusterConfig> = OnceLock::new();
+
+/// How often every instance records that it's still alive.
+pub const INSTANCE_HEARTBEAT_INTERVAL_MS: u64 = 10000;
+
+/// Rooms owned by instances that haven't recorded
+/// a heartbeat within this window are reclaimed.
+pub const INSTANCE_EXPIRY_MS: i64 = 30000;
+
+/// - MERLIN_CLUSTERED=true turns on clustered mode.
+/// - MERLIN_INSTANCE_ID names this instance, it has to be
+///   stable across restarts so the rooms this instance owns
+///   can be rehydrated, the hostname is used when it isn't set.
+pub fn config() -> &'static ClusterConfig {
+    CLUSTER_CONFIG.get_or_init(|| {
+        let enabled = env::var("MERLIN_CLUSTERED").map_or(false, |value| value == "true");
+        let instance_id = match env::var("MERLIN_INSTANCE_ID") {
+            Ok(instance_id) => instance_id,
+            Err(_) if !enabled => "standalone".to_owned(),
+            // a random id would orphan our rooms on every restart
+            Err(_) => hostname().expect(
+                "MERLIN_INSTANCE_ID must be set in clustered mode when there is no hostname",
+            ),
+        };
+        ClusterConfig {
+            enabled: enabled,
+            instance_id: instance_id,
+        }
+    })
+}
+
+/// Lets the other instances know this instance
+/// still owns its rooms, only used in clustered mode.
+pub async fn record_heartbeat(execution_handler: &mut ExecutionHandler) {
+    let capture_result = data_capturer::capture_instance_heartbeat(
+        execution_handler,
+        &config().instance_id,
+        &Utc::now().timestamp_millis(),
+    )
+    .await;
+    if capture_result.encountered_error {
+        logging::console::log_failure(&format!(
+            "Issue recording heartbeat for instance({})",
+            config().instance_id
+        ));
+    }
+}
+
+/// Voice server responses come back on this queue,
+/// each instance has its own in clustered mode.
+pub fn voice_reply_queue() -> String {
+    return reply_queue("voice_server_publish");
+}
+
+/// Integration server responses come back on this queue,
+/// each instance has its own in clustered mode.
+pub fn integration_reply_queue() -> String {
+    return reply_queue("main_server_publish");
+}
+
+pub fn cluster_queue() -> String {
+    return format!("merlin_cluster.{}", config().instance_id);
+}
+
+pub fn consumer_tag(purpose: &str) -> String {
+    return format!("merlin.{}.{}", config().instance_id, purpose);
+}
+
+/// Lets the other instances know where a user
+/// is(or isn't), used for follow lists etc.
+pub fn announce_presence(server_state: &ServerState, user_id: &i32) {
+    if !config().enabled {
+        return;
+    }
+    let presence = match server_state.active_users.get(user_id) {
+        Some(user) => UserPresence {
+            user_id: user_id.clone(),
+            online: true,
+            current_room_id: user.current_room_id.clone(),
+        },
+        None => UserPresence {
+            user_id: user_id.clone(),
+            online: false,
+            current_room_id: -1,
+        },
+    };
+    publish_to_cluster(
+        server_state,
+        "user_presence",
+        serde_json::to_string(&presence).unwrap(),
+    );
+}
+
+fn hostname() -> Option<String> {
+    let hostname = env::var("HOSTNAME")
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())?;
+    let hostname = hostname.trim();
+    if hostname.is_empty() {
+        return None;
+    }
+    return Some(hostname.to_owned());
+}
+
+fn reply_queue(base_queue: &str) -> String {
+    if config().enabled {
+        return format!("{}.{}", base_queue, config().instance_id);
+    }
+    return base_queue.to_owned();
+}
+
+// The outbox is drained by the cluster publish task,
+// so we never have to hold a rabbit channel here.
+fn publish_to_cluster(server_state: &ServerState, category: &str, data: String) {
+    if let Some(outbox) = &server_state.cluster_outbox {
+        let message = ClusterMessage {
+            origin_instance_id: config().instance_id.clone(),
+            category: category.to_owned(),
+            data: data,
+        };
+        outbox
+            .send(serde_json::to_string(&message).unwrap())
+            .unwrap_or_default();
+    }
+}
@@ -0,0 +1,37 @@
+use crate::cluster::handler;
+use crate::cluster::types::{ClusterMessage, UserPresence};
+use crate::logging;
+use crate::state::state::ServerState;
+
+pub async fn route_msg(msg: String, server_state: &mut ServerState) {
+    let cluster_message: ClusterMessage = match serde_json::from_str(&msg) {
+        Ok(cluster_message) => cluster_message,
+        Err(_) => {
+            logging::console::log_failure("Invalid cluster message");
+            return;
+        }
+    };
+    // the exchange fans out to every instance, including us
+    if cluster_message.origin_instance_id == handler::config().instance_id {
+        return;
+    }
+    match cluster_message.category.as_str() {
+        "user_presence" => handle_user_presence(cluster_message.data, server_state),
+        _ => logging::console::log_failure(&format!(
+            "Unknown cluster message category:{}",
+            cluster_message.category
+        )),
+    }
+}
+
+fn handle_user_presence(data: String, server_state: &mut ServerState) {
+    if let Ok(presence) = serde_json::from_str::<UserPresence>(&data) {
+        if presence.online {
+            server_state
+                .remote_users
+                .insert(presence.user_id, presence.current_room_id);
+        } else {
+            server_state.remote_users.remove(&presence.user_id);
+        }
+    }
+}
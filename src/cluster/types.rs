@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+
+/// Clustered mode lets multiple Merlin instances
+/// share the same database and rabbitmq broker,
+/// each instance needs its own unique id.
+pub struct ClusterConfig {
+    pub enabled: bool,
+    pub instance_id: String,
+}
+
+/// Every message sent over the cluster exchange,
+/// instances ignore the messages they published themselves.
+#[derive(Deserialize, Serialize)]
+pub struct ClusterMessage {
+    pub origin_instance_id: String,
+    pub category: String,
+    pub data: String,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct UserPresence {
+    pub user_id: i32,
+    pub online: bool,
+    pub current_room_id: i32,
+}
+
+/// Sent to users trying to join a room
+/// that another instance owns, so they can
+/// reconnect to the correct instance.
+#[derive(Deserialize, Serialize)]
+pub struct RoomOnOtherInstance {
+    pub room_id: i32,
+    pub instance_id: String,
+}
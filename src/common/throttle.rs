@@ -0,0 +1,12 @@
+use crate::state::types::FanOutThrottle;
+
+/// Returns true(starting a new window) if something changed
+/// since the last fan out and the window passed.
+pub fn take_if_due(throttle: &mut FanOutThrottle, now: i64, window_ms: i64) -> bool {
+    if !throttle.dirty || now - throttle.last_fan_out < window_ms {
+        return false;
+    }
+    throttle.dirty = false;
+    throttle.last_fan_out = now;
+    return true;
+}
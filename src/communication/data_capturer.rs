@@ -6,10 +6,12 @@ the same user twice etc.
 
 use crate::communication::types::{ScheduledRoomUpdate, UserProfileEdit};
 use crate::data_store::db_models::{
-    DBFollower, DBRoom, DBRoomBlock, DBRoomPermissions, DBScheduledRoom, DBScheduledRoomAttendance,
-    DBUser, DBUserBlock,
+    DBAuditEvent, DBFollower, DBIoTAction, DBIoTBoard, DBIoTBoardPermission, DBIoTPassiveData,
+    DBPersistentRoom, DBRoom, DBRoomBlock, DBRoomPermissions, DBRoomRecording, DBScheduledRoom,
+    DBScheduledRoomAttendance, DBUser, DBUserBlock,
 };
 use crate::data_store::sql_execution_handler::ExecutionHandler;
+use chrono::Utc;
 use futures_util::Future;
 use tokio_postgres::{row::Row, Error};
 
@@ -48,6 +50,251 @@ pub async fn capture_new_room(execution_handler: &mut ExecutionHandler, room: &D
     return capture_room(insert_future_for_execution).await;
 }
 
+pub async fn capture_new_persistent_room(
+    execution_handler: &mut ExecutionHandler,
+    persistent_room: &DBPersistentRoom,
+) -> CaptureResult {
+    let insert_future = execution_handler.insert_persistent_room(persistent_room);
+    return handle_basic_insert_with_no_returning(insert_future).await;
+}
+
+pub async fn capture_new_room_instance(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    instance_id: &String,
+) -> CaptureResult {
+    let insert_future = execution_handler.insert_room_instance(room_id, instance_id);
+    return handle_basic_insert_with_no_returning(insert_future).await;
+}
+
+pub async fn capture_instance_heartbeat(
+    execution_handler: &mut ExecutionHandler,
+    instance_id: &String,
+    last_heartbeat: &i64,
+) -> CaptureResult {
+    let insert_future = execution_handler.upsert_instance_heartbeat(instance_id, last_heartbeat);
+    return handle_basic_insert_with_no_returning(insert_future).await;
+}
+
+/// Fails if another instance claimed the room first
+pub async fn capture_room_instance_claim(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    instance_id: &String,
+    previous_instance_id: &String,
+) -> CaptureResult {
+    let update_result = execution_handler
+        .update_room_instance_owner(room_id, instance_id, previous_instance_id)
+        .await;
+    return handle_removal_or_update_capture(
+        "Room claimed".to_owned(),
+        "Room was already claimed".to_owned(),
+        1,
+        update_result,
+    );
+}
+
+pub async fn capture_new_audit_event(
+    execution_handler: &mut ExecutionHandler,
+    audit_event: &DBAuditEvent,
+) -> CaptureResult {
+    let insert_future = execution_handler.insert_audit_event(audit_event);
+    return handle_basic_insert_with_no_returning(insert_future).await;
+}
+
+pub async fn capture_new_room_recording(
+    execution_handler: &mut ExecutionHandler,
+    recording: &DBRoomRecording,
+) -> i32 {
+    let insert_future = execution_handler.insert_room_recording(recording);
+    return capture_room(insert_future).await;
+}
+
+/// Boards are inserted the first time their external
+/// server connects to the room, after that their
+/// owner/outside name is just updated.
+pub async fn capture_iot_board(
+    execution_handler: &mut ExecutionHandler,
+    board: &DBIoTBoard,
+) -> CaptureResult {
+    if row_exists(execution_handler.select_iot_board(&board.room_id, &board.external_server_id))
+        .await
+    {
+        let update_result = execution_handler.update_iot_board(board).await;
+        return handle_removal_or_update_capture(
+            "Board updated".to_owned(),
+            "Unexpected error updating board".to_owned(),
+            1,
+            update_result,
+        );
+    }
+    let insert_future = execution_handler.insert_iot_board(board);
+    return handle_basic_insert_with_no_returning(insert_future).await;
+}
+
+/// Credentials should already be encrypted,
+/// None forgets them.
+pub async fn capture_iot_board_credentials(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    external_server_id: &String,
+    credentials: &Option<String>,
+) -> CaptureResult {
+    let update_result = execution_handler
+        .update_iot_board_credentials(room_id, external_server_id, credentials)
+        .await;
+    return handle_removal_or_update_capture(
+        "Board credentials updated".to_owned(),
+        "Unexpected error updating board credentials".to_owned(),
+        1,
+        update_result,
+    );
+}
+
+pub async fn capture_iot_board_automations(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    external_server_id: &String,
+    automations: &String,
+) -> CaptureResult {
+    let update_result = execution_handler
+        .update_iot_board_automations(room_id, external_server_id, automations)
+        .await;
+    return handle_removal_or_update_capture(
+        "Board automations updated".to_owned(),
+        "Unexpected error updating board automations".to_owned(),
+        1,
+        update_result,
+    );
+}
+
+/// Granting control to an existing controller
+/// just changes what they're scoped to.
+pub async fn capture_iot_board_permission(
+    execution_handler: &mut ExecutionHandler,
+    permission: &DBIoTBoardPermission,
+) -> CaptureResult {
+    if row_exists(execution_handler.select_iot_board_permission(permission)).await {
+        let update_result = execution_handler
+            .update_iot_board_permission(permission)
+            .await;
+        return handle_removal_or_update_capture(
+            "Board permission updated".to_owned(),
+            "Unexpected error updating board permission".to_owned(),
+            1,
+            update_result,
+        );
+    }
+    let insert_future = execution_handler.insert_iot_board_permission(permission);
+    return handle_basic_insert_with_no_returning(insert_future).await;
+}
+
+pub async fn capture_iot_board_permission_removal(
+    execution_handler: &mut ExecutionHandler,
+    permission: &DBIoTBoardPermission,
+) -> CaptureResult {
+    let removal_result = execution_handler
+        .delete_iot_board_permission(
+            &permission.room_id,
+            &permission.external_server_id,
+            &permission.user_id,
+        )
+        .await;
+    return handle_removal_or_update_capture(
+        "Board permission removed".to_owned(),
+        "Unexpected error removing board permission".to_owned(),
+        1,
+        removal_result,
+    );
+}
+
+pub async fn capture_iot_passive_data(
+    execution_handler: &mut ExecutionHandler,
+    passive_data: &DBIoTPassiveData,
+) -> CaptureResult {
+    let insert_future = execution_handler.insert_iot_passive_data(passive_data);
+    return handle_basic_insert_with_no_returning(insert_future).await;
+}
+
+pub async fn capture_iot_action(
+    execution_handler: &mut ExecutionHandler,
+    action: &DBIoTAction,
+) -> CaptureResult {
+    let insert_future = execution_handler.insert_iot_action(action);
+    return handle_basic_insert_with_no_returning(insert_future).await;
+}
+
+/// Records the response(or timeout) of an action
+pub async fn capture_iot_action_result(
+    execution_handler: &mut ExecutionHandler,
+    action_id: &String,
+    status: &String,
+    response: &Option<String>,
+    responded_at: &i64,
+) -> CaptureResult {
+    let update_result = execution_handler
+        .update_iot_action_result(action_id, status, response, responded_at)
+        .await;
+    return handle_removal_or_update_capture(
+        "Action result recorded".to_owned(),
+        "Unexpected error recording action result".to_owned(),
+        1,
+        update_result,
+    );
+}
+
+/// Removes every passive data sample captured before
+/// oldest_kept, returns the amount removed.
+pub async fn capture_expired_iot_passive_data_removal(
+    execution_handler: &mut ExecutionHandler,
+    oldest_kept: &i64,
+) -> u64 {
+    return execution_handler
+        .delete_expired_iot_passive_data(oldest_kept)
+        .await
+        .unwrap_or_default();
+}
+
+pub async fn capture_expired_iot_action_removal(
+    execution_handler: &mut ExecutionHandler,
+    oldest_kept: &i64,
+) -> u64 {
+    return execution_handler
+        .delete_expired_iot_actions(oldest_kept)
+        .await
+        .unwrap_or_default();
+}
+
+/// Removes the board along with everyone it granted control to
+/// and its passive data/action history
+pub async fn capture_iot_board_removal(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    external_server_id: &String,
+) -> CaptureResult {
+    execution_handler
+        .delete_iot_board_permissions(room_id, external_server_id)
+        .await
+        .unwrap_or_default();
+    execution_handler
+        .delete_iot_passive_data(room_id, external_server_id)
+        .await
+        .unwrap_or_default();
+    execution_handler
+        .delete_iot_actions(room_id, external_server_id)
+        .await
+        .unwrap_or_default();
+    let removal_result = execution_handler
+        .delete_iot_board(room_id, external_server_id)
+        .await;
+    return handle_removal_or_update_capture(
+        "Board removed".to_owned(),
+        "Unexpected error removing board".to_owned(),
+        1,
+        removal_result,
+    );
+}
+
 pub async fn capture_new_scheduled_room(
     execution_handler: &mut ExecutionHandler,
     room: &DBScheduledRoom,
@@ -163,6 +410,16 @@ pub async fn capture_new_room_block(
     }
 }
 
+/// Removes every room block that is past its expiry,
+/// returns the amount removed.
+pub async fn capture_expired_room_block_removal(execution_handler: &mut ExecutionHandler) -> u64 {
+    let now = Utc::now().timestamp_millis();
+    return execution_handler
+        .delete_expired_room_blocks(&now)
+        .await
+        .unwrap_or_default();
+}
+
 pub async fn capture_user_block_removal(
     execution_handler: &mut ExecutionHandler,
     owner_id: &i32,
@@ -226,6 +483,35 @@ pub async fn capture_room_removal(
         .delete_room_blocks(room_id)
         .await
         .unwrap_or_default();
+    execution_handler
+        .delete_persistent_room(room_id)
+        .await
+        .unwrap_or_default();
+    execution_handler
+        .delete_room_instance(room_id)
+        .await
+        .unwrap_or_default();
+    execution_handler
+        .delete_room_iot_boards(room_id)
+        .await
+        .unwrap_or_default();
+    execution_handler
+        .delete_room_iot_board_permissions(room_id)
+        .await
+        .unwrap_or_default();
+    execution_handler
+        .delete_room_iot_passive_data(room_id)
+        .await
+        .unwrap_or_default();
+    execution_handler
+        .delete_room_iot_actions(room_id)
+        .await
+        .unwrap_or_default();
+    // recordings are kept, they just can't be left open
+    execution_handler
+        .update_open_room_recordings_stopped_at(room_id, &Utc::now().timestamp_millis())
+        .await
+        .unwrap_or_default();
     return handle_removal_or_update_capture(
         "Room Removed".to_owned(),
         "Unexpected error removing room".to_owned(),
@@ -234,6 +520,32 @@ pub async fn capture_room_removal(
     );
 }
 
+/// Removes room_permission, room_block, persistent_room
+/// and room_instance rows that belong to rooms that no longer exist.
+///
+/// returns (permissions removed, blocks removed)
+pub async fn capture_orphaned_room_data_removal(
+    execution_handler: &mut ExecutionHandler,
+) -> (u64, u64) {
+    let permissions_removed = execution_handler
+        .delete_orphaned_room_permissions()
+        .await
+        .unwrap_or_default();
+    let blocks_removed = execution_handler
+        .delete_orphaned_room_blocks()
+        .await
+        .unwrap_or_default();
+    execution_handler
+        .delete_orphaned_persistent_rooms()
+        .await
+        .unwrap_or_default();
+    execution_handler
+        .delete_orphaned_room_instances()
+        .await
+        .unwrap_or_default();
+    return (permissions_removed, blocks_removed);
+}
+
 pub async fn capture_user_update(
     execution_handler: &mut ExecutionHandler,
     user_id: &i32,
@@ -256,6 +568,34 @@ pub async fn capture_user_update(
     return generic_error_capture_result();
 }
 
+pub async fn capture_persistent_room_update(
+    execution_handler: &mut ExecutionHandler,
+    persistent_room: &DBPersistentRoom,
+) -> CaptureResult {
+    let update_result = execution_handler
+        .update_persistent_room(persistent_room)
+        .await;
+    return handle_removal_or_update_capture(
+        "Persistent Room Updated".to_owned(),
+        "Unexpected error updating persistent room".to_owned(),
+        1,
+        update_result,
+    );
+}
+
+pub async fn capture_persistent_room_removal(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+) -> CaptureResult {
+    let deletion_result = execution_handler.delete_persistent_room(room_id).await;
+    return handle_removal_or_update_capture(
+        "Persistent Room Removed".to_owned(),
+        "Unexpected error removing persistent room".to_owned(),
+        1,
+        deletion_result,
+    );
+}
+
 pub async fn capture_scheduled_room_update(
     user_id: &i32,
     update: &ScheduledRoomUpdate,
@@ -292,6 +632,24 @@ pub async fn capture_scheduled_room_update(
     };
 }
 
+/// Closes the room's open recording, only
+/// one can be open at a time.
+pub async fn capture_room_recording_stop(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    stopped_at: &i64,
+) -> CaptureResult {
+    let update_result = execution_handler
+        .update_open_room_recordings_stopped_at(room_id, stopped_at)
+        .await;
+    return handle_removal_or_update_capture(
+        "Recording stopped".to_owned(),
+        "Unexpected error stopping recording".to_owned(),
+        1,
+        update_result,
+    );
+}
+
 pub async fn capture_new_room_owner_update(
     room_id: &i32,
     new_owner_id: &i32,
@@ -3,7 +3,10 @@ abstracts usage of the sql execution handler
 by fetching and converts rows to correct response types.
 */
 use crate::communication::types::{RoomPermissions, User, UserPreview};
-use crate::data_store::db_models::DBScheduledRoom;
+use crate::data_store::db_models::{
+    DBAuditEvent, DBIoTAction, DBIoTBoard, DBIoTBoardPermission, DBIoTPassiveData,
+    DBPersistentRoom, DBRoomBlock, DBScheduledRoom,
+};
 use crate::data_store::sql_execution_handler::ExecutionHandler;
 use futures_util::Future;
 use std::collections::{HashMap, HashSet};
@@ -273,6 +276,290 @@ pub async fn get_blocked_user_ids_for_room(
     return blocked_users_result;
 }
 
+/// Gathers the blocks(including reason, expiry etc)
+/// for a room, expired blocks are ignored.
+pub async fn get_room_blocks(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+) -> (bool, Vec<DBRoomBlock>) {
+    let gather_result = execution_handler
+        .select_all_blocked_users_for_room(room_id)
+        .await;
+    if let Ok(selected_rows) = gather_result {
+        let room_blocks: Vec<DBRoomBlock> = selected_rows
+            .iter()
+            .map(|row| construct_room_block(row))
+            .collect();
+        return (false, room_blocks);
+    }
+    return (true, Vec::new());
+}
+
+/// Gathers the most recent audit events of a room,
+/// newest first.
+pub async fn get_audit_events_for_room(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    limit: &i64,
+) -> (bool, Vec<DBAuditEvent>) {
+    let gather_result = execution_handler
+        .select_audit_events_for_room(room_id, limit)
+        .await;
+    return construct_audit_events(gather_result);
+}
+
+pub async fn get_audit_events_for_room_by_action(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    action: &String,
+) -> (bool, Vec<DBAuditEvent>) {
+    let gather_result = execution_handler
+        .select_audit_events_for_room_by_action(room_id, action)
+        .await;
+    return construct_audit_events(gather_result);
+}
+
+pub async fn get_iot_boards_for_room(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+) -> (bool, Vec<DBIoTBoard>) {
+    let gather_result = execution_handler.select_iot_boards_for_room(room_id).await;
+    if let Ok(selected_rows) = gather_result {
+        let boards: Vec<DBIoTBoard> = selected_rows.iter().map(construct_iot_board).collect();
+        return (false, boards);
+    }
+    return (true, Vec::new());
+}
+
+/// The board saved for this room, whether it's connected or not
+pub async fn get_iot_board(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    external_server_id: &String,
+) -> (bool, Option<DBIoTBoard>) {
+    let gather_result = execution_handler
+        .select_iot_board(room_id, external_server_id)
+        .await;
+    if let Ok(selected_rows) = gather_result {
+        return (false, selected_rows.first().map(construct_iot_board));
+    }
+    return (true, None);
+}
+
+fn construct_iot_board(row: &Row) -> DBIoTBoard {
+    return DBIoTBoard {
+        id: row.get(0),
+        room_id: row.get(1),
+        external_server_id: row.get(2),
+        owner_id: row.get(3),
+        outside_name: row.get(4),
+        provider: row.get(5),
+    };
+}
+
+/// The board's encrypted credentials, if its owner asked to remember them
+pub async fn get_iot_board_credentials(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    external_server_id: &String,
+) -> (bool, Option<String>) {
+    let gather_result = execution_handler
+        .select_iot_board(room_id, external_server_id)
+        .await;
+    if let Ok(selected_rows) = gather_result {
+        let credentials = selected_rows.first().and_then(|row| row.get(6));
+        return (false, credentials);
+    }
+    return (true, None);
+}
+
+/// The board's schedules/rules as json, if any were saved
+pub async fn get_iot_board_automations(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    external_server_id: &String,
+) -> (bool, Option<String>) {
+    let gather_result = execution_handler
+        .select_iot_board(room_id, external_server_id)
+        .await;
+    if let Ok(selected_rows) = gather_result {
+        let automations = selected_rows.first().and_then(|row| row.get(7));
+        return (false, automations);
+    }
+    return (true, None);
+}
+
+/// external server id -> users granted control of its board
+pub async fn get_iot_board_permissions_for_room(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+) -> (bool, HashMap<String, Vec<DBIoTBoardPermission>>) {
+    let gather_result = execution_handler
+        .select_iot_board_permissions_for_room(room_id)
+        .await;
+    if let Ok(selected_rows) = gather_result {
+        let mut permissions: HashMap<String, Vec<DBIoTBoardPermission>> = HashMap::new();
+        for row in selected_rows {
+            let permission = DBIoTBoardPermission {
+                id: row.get(0),
+                room_id: row.get(1),
+                external_server_id: row.get(2),
+                user_id: row.get(3),
+                bots: row.get(4),
+                actions: row.get(5),
+            };
+            permissions
+                .entry(permission.external_server_id.clone())
+                .or_insert_with(Vec::new)
+                .push(permission);
+        }
+        return (false, permissions);
+    }
+    return (true, HashMap::new());
+}
+
+/// Gathers a board's passive data between from and to(inclusive),
+/// oldest first. Only the latest sample of each bucket is kept.
+pub async fn get_iot_passive_history(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    external_server_id: &String,
+    from: &i64,
+    to: &i64,
+    bucket_ms: &i64,
+    limit: &i64,
+) -> (bool, Vec<DBIoTPassiveData>) {
+    let gather_result = execution_handler
+        .select_iot_passive_data_in_range(room_id, external_server_id, from, to, bucket_ms, limit)
+        .await;
+    if let Ok(selected_rows) = gather_result {
+        let samples: Vec<DBIoTPassiveData> = selected_rows
+            .iter()
+            .map(|row| DBIoTPassiveData {
+                id: row.get(0),
+                room_id: row.get(1),
+                external_server_id: row.get(2),
+                data: row.get(3),
+                captured_at: row.get(4),
+            })
+            .collect();
+        return (false, samples);
+    }
+    return (true, Vec::new());
+}
+
+/// Gathers the most recent actions requested
+/// on a board, newest first.
+pub async fn get_iot_actions_for_board(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    external_server_id: &String,
+    limit: &i64,
+) -> (bool, Vec<DBIoTAction>) {
+    let gather_result = execution_handler
+        .select_iot_actions_for_board(room_id, external_server_id, limit)
+        .await;
+    if let Ok(selected_rows) = gather_result {
+        let actions: Vec<DBIoTAction> = selected_rows
+            .iter()
+            .map(|row| DBIoTAction {
+                id: row.get(0),
+                action_id: row.get(1),
+                room_id: row.get(2),
+                external_server_id: row.get(3),
+                requester_id: row.get(4),
+                bot_name: row.get(5),
+                action: row.get(6),
+                status: row.get(7),
+                response: row.get(8),
+                requested_at: row.get(9),
+                responded_at: row.get(10),
+            })
+            .collect();
+        return (false, actions);
+    }
+    return (true, Vec::new());
+}
+
+/// Gathers every room id in the database,
+/// live rooms and persistent rooms alike.
+pub async fn get_all_room_ids(execution_handler: &mut ExecutionHandler) -> (bool, Vec<i32>) {
+    let gather_result = execution_handler.select_all_rooms().await;
+    if let Ok(selected_rows) = gather_result {
+        let room_ids: Vec<i32> = selected_rows.iter().map(|row| row.get(0)).collect();
+        return (false, room_ids);
+    }
+    return (true, Vec::new());
+}
+
+pub async fn get_persistent_rooms(
+    execution_handler: &mut ExecutionHandler,
+) -> (bool, Vec<DBPersistentRoom>) {
+    let gather_result = execution_handler.select_all_persistent_rooms().await;
+    if let Ok(selected_rows) = gather_result {
+        let persistent_rooms: Vec<DBPersistentRoom> = selected_rows
+            .iter()
+            .map(|row| construct_persistent_room(row))
+            .collect();
+        return (false, persistent_rooms);
+    }
+    return (true, Vec::new());
+}
+
+/// room id -> id of the instance that owns the room
+pub async fn get_room_instances(
+    execution_handler: &mut ExecutionHandler,
+) -> (bool, HashMap<i32, String>) {
+    let gather_result = execution_handler.select_all_room_instances().await;
+    if let Ok(selected_rows) = gather_result {
+        let room_instances: HashMap<i32, String> = selected_rows
+            .iter()
+            .map(|row| (row.get(1), row.get(2)))
+            .collect();
+        return (false, room_instances);
+    }
+    return (true, HashMap::new());
+}
+
+/// room id -> id of the dead instance that owns the room,
+/// instances are dead once their last heartbeat is before expired_before.
+pub async fn get_expired_room_instances(
+    execution_handler: &mut ExecutionHandler,
+    instance_id: &String,
+    expired_before: &i64,
+) -> (bool, HashMap<i32, String>) {
+    let gather_result = execution_handler
+        .select_expired_room_instances(instance_id, expired_before)
+        .await;
+    if let Ok(selected_rows) = gather_result {
+        let room_instances: HashMap<i32, String> = selected_rows
+            .iter()
+            .map(|row| (row.get(0), row.get(1)))
+            .collect();
+        return (false, room_instances);
+    }
+    return (true, HashMap::new());
+}
+
+/// Gathers the id of the instance that owns the room,
+/// None if no instance has claimed it.
+pub async fn get_room_instance(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+) -> (bool, Option<String>) {
+    let gather_result = execution_handler
+        .select_room_instance_by_room_id(room_id)
+        .await;
+    if let Ok(selected_rows) = gather_result {
+        if selected_rows.len() == 1 {
+            let instance_id: String = selected_rows[0].get(2);
+            return (false, Some(instance_id));
+        }
+        return (false, None);
+    }
+    return (true, None);
+}
+
 pub async fn get_room_owner_and_settings(
     execution_handler: &mut ExecutionHandler,
     room_id: &i32,
@@ -378,6 +665,50 @@ pub async fn get_single_column_of_all_rows_by_id<
     return (encountered_error, data_set);
 }
 
+fn construct_persistent_room(row: &Row) -> DBPersistentRoom {
+    return DBPersistentRoom {
+        id: row.get(0),
+        room_id: row.get(1),
+        name: row.get(2),
+        desc: row.get(3),
+        public: row.get(4),
+        auto_speaker: row.get(5),
+        chat_throttle: row.get(6),
+        created_at: row.get(7),
+        speaker_cap: row.get(8),
+    };
+}
+
+fn construct_audit_events(gather_result: Result<Vec<Row>, Error>) -> (bool, Vec<DBAuditEvent>) {
+    if let Ok(selected_rows) = gather_result {
+        let audit_events: Vec<DBAuditEvent> = selected_rows
+            .iter()
+            .map(|row| DBAuditEvent {
+                id: row.get(0),
+                room_id: row.get(1),
+                actor_id: row.get(2),
+                target_id: row.get(3),
+                action: row.get(4),
+                payload: row.get(5),
+                created_at: row.get(6),
+            })
+            .collect();
+        return (false, audit_events);
+    }
+    return (true, Vec::new());
+}
+
+fn construct_room_block(row: &Row) -> DBRoomBlock {
+    return DBRoomBlock {
+        id: row.get(0),
+        owner_room_id: row.get(1),
+        blocked_user_id: row.get(2),
+        expires_at: row.get(3),
+        reason: row.get(4),
+        blocked_by: row.get(5),
+    };
+}
+
 fn construct_scheduled_room(row: &Row) -> DBScheduledRoom {
     let room_id: i32 = row.get(0);
     let room_name: String = row.get(1);
@@ -1,22 +1,35 @@
+use crate::board;
+use crate::board::types::{
+    CreateBoardRule, CreateBoardSchedule, GetBoardAutomations, GetIoTActionHistory,
+    GetIoTPassiveHistory, IoTActionRequested, ReconnectIoTBoard, RemoveBoardAutomation,
+    SetPassiveDataMode,
+};
+use crate::cluster;
+use crate::cluster::types::RoomOnOtherInstance;
 use crate::common::response_logic::send_to_requester_channel;
 use crate::communication::data_fetcher;
 use crate::communication::helpers;
 use crate::communication::types::{
     AllUsersInRoomResponse, BasicRequest, BasicRoomCreation, BlockUserFromRoom, CommunicationRoom,
-    GenericRoomId, GenericRoomIdAndPeerId, GetFollowList, UnblockUserFromRoom, User, UserPreview,
+    GenericRoomId, GenericRoomIdAndPeerId, GetFollowList, LeaveRoom, UnblockUserFromRoom, User,
+    UserPreview,
 };
-use crate::data_store::db_models::{DBFollower, DBUserBlock};
+use crate::data_store::db_models::{DBFollower, DBRoomBlock, DBUserBlock};
 use crate::data_store::sql_execution_handler::ExecutionHandler;
+use crate::integration;
+use crate::integration::providers::{self, IntegrationProvider};
 use crate::integration::types::DisconnectMsg;
 use crate::integration::types::GeneralMessage;
 use crate::integration::types::HOIActionDataIncoming;
-use crate::integration::types::HOIActionDataOutgoing;
 use crate::integration::types::HouseOfIoTCredentials;
+use crate::integration::types::{
+    ConnectIntegration, IntegrationProviderDescription, StoredCredentials,
+};
 use crate::logging;
 use crate::rabbitmq::rabbit;
 use crate::rooms::handler::EncounteredError;
 use crate::state::state::ServerState;
-use crate::state::types::Room;
+use crate::state::types::{Board, PendingIntegrationConnect, Room};
 use crate::{rooms, ws_fan};
 use futures::lock::Mutex;
 use serde_json::Result;
@@ -25,21 +38,29 @@ use std::collections::{HashMap, HashSet};
 use std::mem::drop;
 use std::sync::Arc;
 use tokio::sync::RwLock;
+use uuid::Uuid;
 
 use super::data_capturer::{self, CaptureResult};
+use super::types::AuditEvent;
 use super::types::BlockedFromRoom;
 use super::types::ExistingIotServer;
+use super::types::GetRoomAuditLog;
 use super::types::GiveOrRevokeIot;
 use super::types::InitRoomData;
 use super::types::JoinTypeInfo;
+use super::types::KickUserFromRoom;
 use super::types::LooseUserPreviewRequest;
+use super::types::ModMuteOrDeafenUser;
 use super::types::NewIoTController;
 use super::types::NewModStatus;
 use super::types::RelationModification;
 use super::types::RemovedIoTController;
+use super::types::RoomAuditLog;
+use super::types::RoomBlockDetails;
 use super::types::RoomDetails;
 use super::types::SingleUserDataResults;
 use super::types::SingleUserPermissionResults;
+use super::types::TransferBoardOwnership;
 use super::types::UserProfileEdit;
 use super::types::{
     BasicResponse, DeafAndMuteStatus, DeafAndMuteStatusUpdate, GenericUserId, RoomUpdate,
@@ -77,6 +98,7 @@ pub async fn create_room(
                 request_data.name,
                 request_data.desc,
                 request_data.public,
+                request_data.persistent,
             )
             .await;
             return Ok(());
@@ -103,9 +125,99 @@ pub async fn block_user_from_room(
         // The owner checking happens in the room handler
         if room.user_ids.contains(&requester_id) && room.user_ids.contains(&request_data.user_id) {
             rooms::handler::block_user_from_room(
-                request_data.user_id,
+                request_data,
+                requester_id,
+                &mut write_state,
+                execution_handler,
+                publish_channel,
+            )
+            .await;
+            return Ok(());
+        }
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    return Ok(());
+}
+
+pub async fn kick_user_from_room(
+    request: BasicRequest,
+    requester_id: i32,
+    server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+) -> Result<()> {
+    let request_data: KickUserFromRoom = serde_json::from_str(&request.request_containing_data)?;
+    let mut write_state = server_state.write().await;
+
+    // Make sure this room actually exists
+    if let Some(room) = write_state.rooms.get(&request_data.room_id) {
+        // Make sure both users are in the room
+        // The mod/owner checking happens in the room handler
+        if room.user_ids.contains(&requester_id) && room.user_ids.contains(&request_data.user_id) {
+            rooms::handler::kick_user_from_room(
+                request_data,
+                requester_id,
+                &mut write_state,
+                execution_handler,
+                publish_channel,
+            )
+            .await;
+            return Ok(());
+        }
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    return Ok(());
+}
+
+pub async fn mod_mute_or_deafen_user(
+    request: BasicRequest,
+    requester_id: i32,
+    server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+) -> Result<()> {
+    let request_data: ModMuteOrDeafenUser = serde_json::from_str(&request.request_containing_data)?;
+    let action = rooms::handler::ModVoiceAction::from_op_code(&request.request_op_code);
+    let mut write_state = server_state.write().await;
+
+    // Make sure this room actually exists
+    if let (Some(room), Some(action)) = (write_state.rooms.get(&request_data.room_id), action) {
+        // Make sure both users are in the room
+        // The mod/owner checking happens in the room handler
+        if room.user_ids.contains(&requester_id) && room.user_ids.contains(&request_data.user_id) {
+            rooms::handler::mod_mute_or_deafen_user(
+                request_data,
+                requester_id,
+                action,
+                &mut write_state,
+                execution_handler,
+                publish_channel,
+            )
+            .await;
+            return Ok(());
+        }
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    return Ok(());
+}
+
+/// Used for start_recording and stop_recording,
+/// the owner checking happens in the room handler.
+pub async fn start_or_stop_recording(
+    request: BasicRequest,
+    requester_id: i32,
+    server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+) -> Result<()> {
+    let request_data: GenericRoomId = serde_json::from_str(&request.request_containing_data)?;
+    let mut write_state = server_state.write().await;
+    if let Some(room) = write_state.rooms.get(&request_data.room_id) {
+        if room.user_ids.contains(&requester_id) {
+            rooms::handler::start_or_stop_recording(
                 request_data.room_id,
                 requester_id,
+                request.request_op_code == "start_recording",
                 &mut write_state,
                 execution_handler,
                 publish_channel,
@@ -118,6 +230,32 @@ pub async fn block_user_from_room(
     return Ok(());
 }
 
+/// Must be accepted before joining a
+/// room that is being recorded.
+pub async fn accept_recording_consent(
+    request: BasicRequest,
+    requester_id: i32,
+    server_state: &Arc<RwLock<ServerState>>,
+) -> Result<()> {
+    let request_data: GenericRoomId = serde_json::from_str(&request.request_containing_data)?;
+    let mut write_state = server_state.write().await;
+    if rooms::handler::accept_recording_consent(
+        &mut write_state,
+        &request_data.room_id,
+        &requester_id,
+    ) {
+        send_to_requester_channel(
+            request_data.room_id.to_string(),
+            requester_id,
+            &mut write_state,
+            "recording_consent_accepted".to_string(),
+        );
+        return Ok(());
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    return Ok(());
+}
+
 pub async fn unblock_user_from_room(
     request: BasicRequest,
     requester_id: i32,
@@ -126,12 +264,23 @@ pub async fn unblock_user_from_room(
     let request_data: UnblockUserFromRoom = serde_json::from_str(&request.request_containing_data)?;
     let mut handler = execution_handler.lock().await;
     if is_mod_or_owner(&request_data.room_id, &mut handler, &requester_id).await {
-        data_capturer::capture_room_block_removal(
+        let capture_result = data_capturer::capture_room_block_removal(
             &mut handler,
             &request_data.room_id,
             &request_data.user_id,
         )
         .await;
+        if !capture_result.encountered_error {
+            rooms::audit::record(
+                &mut handler,
+                &request_data.room_id,
+                &requester_id,
+                Some(request_data.user_id.clone()),
+                "unblock_user",
+                None,
+            )
+            .await;
+        }
     }
 
     Ok(())
@@ -141,6 +290,7 @@ pub async fn join_room(
     request: BasicRequest,
     server_state: &Arc<RwLock<ServerState>>,
     publish_channel: &Arc<Mutex<lapin::Channel>>,
+    integration_publish_channel: Option<&Arc<Mutex<lapin::Channel>>>,
     execution_handler: &Arc<Mutex<ExecutionHandler>>,
     requester_id: i32,
     type_of_join: &str,
@@ -161,6 +311,19 @@ pub async fn join_room(
         // and user isn't blocked
         if blocked_result.0 == false && !blocked_result.1.contains(&peer_id) {
             drop(handler);
+            // recorded rooms need the user's consent first,
+            // see accept_recording_consent
+            if rooms::handler::needs_recording_consent(&write_state, &room_id, &peer_id) {
+                let state =
+                    rooms::handler::recording_state(write_state.rooms.get(&room_id).unwrap());
+                send_to_requester_channel(
+                    serde_json::to_string(&state).unwrap(),
+                    requester_id,
+                    &mut write_state,
+                    "recording_consent_required".to_string(),
+                );
+                return Ok(());
+            }
             rooms::handler::join_room(
                 request_data,
                 &mut write_state,
@@ -170,13 +333,68 @@ pub async fn join_room(
                 type_of_join,
             )
             .await;
+            let joined = write_state
+                .active_users
+                .get(&requester_id)
+                .map_or(false, |user| user.current_room_id == room_id);
+            // owners get their saved boards back, see reconnect_saved_boards
+            if let Some(integration_publish_channel) = integration_publish_channel {
+                if joined {
+                    reconnect_saved_boards(
+                        integration_publish_channel,
+                        &mut write_state,
+                        execution_handler,
+                        requester_id,
+                        room_id,
+                    )
+                    .await;
+                }
+            }
             return Ok(());
         }
+    } else if !write_state.rooms.contains_key(&room_id)
+        && send_room_on_other_instance(&mut write_state, execution_handler, requester_id, room_id)
+            .await
+    {
+        return Ok(());
     }
     send_error_response_to_requester(requester_id, &mut write_state);
     return Ok(());
 }
 
+/// In clustered mode the room may live on another instance,
+/// let the user know which one so they can reconnect there.
+/// returns whether the user was redirected.
+async fn send_room_on_other_instance(
+    server_state: &mut ServerState,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    requester_id: i32,
+    room_id: i32,
+) -> bool {
+    if !cluster::handler::config().enabled {
+        return false;
+    }
+    let mut handler = execution_handler.lock().await;
+    let instance_result = data_fetcher::get_room_instance(&mut handler, &room_id).await;
+    drop(handler);
+    if let (false, Some(instance_id)) = instance_result {
+        if instance_id != cluster::handler::config().instance_id {
+            let response = RoomOnOtherInstance {
+                room_id: room_id,
+                instance_id: instance_id,
+            };
+            send_to_requester_channel(
+                serde_json::to_string(&response).unwrap(),
+                requester_id,
+                server_state,
+                "room_on_other_instance".to_owned(),
+            );
+            return true;
+        }
+    }
+    return false;
+}
+
 pub async fn add_or_remove_speaker(
     request: BasicRequest,
     publish_channel: &Arc<Mutex<lapin::Channel>>,
@@ -232,9 +450,17 @@ pub async fn handle_web_rtc_request(
     let request_data: serde_json::Value = serde_json::from_str(&request.request_containing_data)?;
     let mut write_state = server_state.write().await;
 
-    if helpers::web_rtc_request_is_valid(&write_state, &request_data, &requester_id) {
+    if helpers::web_rtc_request_is_valid(&write_state, &request_data, &requester_id)
+        && !helpers::web_rtc_request_is_blocked_by_mod(
+            &write_state,
+            &request_data,
+            &requester_id,
+            &request.request_op_code,
+        )
+    {
         rooms::handler::handle_web_rtc_specific_requests(
             request_data,
+            &write_state,
             publish_channel,
             &request.request_op_code,
         )
@@ -435,7 +661,7 @@ pub async fn leave_room(
     execution_handler: &Arc<Mutex<ExecutionHandler>>,
     requester_id: i32,
 ) -> Result<()> {
-    let request_data: GenericRoomId = serde_json::from_str(&request.request_containing_data)?;
+    let request_data: LeaveRoom = serde_json::from_str(&request.request_containing_data)?;
     let mut write_state = server_state.write().await;
     //the user is in this room
     if let Some(user) = write_state.active_users.get(&requester_id) {
@@ -444,6 +670,7 @@ pub async fn leave_room(
                 &mut write_state,
                 &requester_id,
                 &request_data.room_id,
+                request_data.transfer_boards_to,
                 voice_server_publish_channel,
                 integration_publish_channel,
                 execution_handler,
@@ -461,6 +688,7 @@ pub async fn raise_hand_or_lower_hand(
     request: BasicRequest,
     server_state: &Arc<RwLock<ServerState>>,
     requester_id: i32,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
     execution_handler: &Arc<Mutex<ExecutionHandler>>,
     type_of_hand_action: &str,
 ) -> Result<()> {
@@ -493,6 +721,7 @@ pub async fn raise_hand_or_lower_hand(
                     &mut write_state,
                     &room_id,
                     &requester_id,
+                    publish_channel,
                     execution_handler,
                 )
                 .await;
@@ -586,6 +815,8 @@ pub async fn gather_all_users_in_room(
             let response = AllUsersInRoomResponse {
                 room_id: room_id,
                 users: users.1,
+                active_speaker: room.speaking.active_speaker,
+                currently_speaking: rooms::speaking::currently_speaking(room),
             };
             let response_str = serde_json::to_string(&response).unwrap();
             send_to_requester_channel(
@@ -739,6 +970,15 @@ pub async fn change_user_mod_status(
                 )
                 .await;
                 if !result.encountered_error {
+                    rooms::audit::record(
+                        &mut handler,
+                        &user_current_room,
+                        &requester_id,
+                        Some(data_obj.user_id.clone()),
+                        &type_of_mod_op(data_obj.new_status.clone()),
+                        None,
+                    )
+                    .await;
                     let basic_response = BasicResponse {
                         response_op_code: type_of_mod_op(data_obj.new_status.clone()),
                         response_containing_data: data_obj.user_id.to_string(),
@@ -796,6 +1036,18 @@ pub async fn update_mute_and_deaf_status(
 ) -> Result<()> {
     let mute_and_deaf: DeafAndMuteStatus = serde_json::from_str(&request.request_containing_data)?;
     let mut write_state = server_state.write().await;
+    let (mod_muted, mod_deafened) =
+        rooms::handler::mod_mute_and_deafen_state(&write_state, &requester_id);
+    // mod imposed mutes/deafens can only be lifted by a mod
+    if (mod_muted && !mute_and_deaf.muted) || (mod_deafened && !mute_and_deaf.deaf) {
+        send_to_requester_channel(
+            "muted or deafened by a mod".to_owned(),
+            requester_id,
+            &mut write_state,
+            "issue_updating_mute_and_deaf".to_owned(),
+        );
+        return Ok(());
+    }
     if let Some(user) = write_state.active_users.get_mut(&requester_id) {
         //you can only update your muted/deaf status if you aren't in a room
         if user.current_room_id != -1 {
@@ -807,6 +1059,8 @@ pub async fn update_mute_and_deaf_status(
                 deaf: mute_and_deaf.deaf,
                 muted: mute_and_deaf.muted,
                 user_id: requester_id,
+                mod_muted: mod_muted,
+                mod_deafened: mod_deafened,
             };
             let basic_response = BasicResponse {
                 response_op_code: "user_mute_and_deaf_update".to_owned(),
@@ -998,6 +1252,15 @@ pub async fn give_owner(
                     &request_data.peerId,
                 )
                 .await;
+                rooms::audit::record(
+                    &mut handler,
+                    &request_data.roomId,
+                    &requester_id,
+                    Some(request_data.peerId.clone()),
+                    "give_owner",
+                    None,
+                )
+                .await;
                 return Ok(());
             }
         }
@@ -1050,19 +1313,76 @@ pub async fn create_hoi_connection(
     let mut write_state = server_state.write().await;
     let mut handler = execution_handler.lock().await;
     if let Some(user) = write_state.active_users.get(&requester_id) {
+        let room_id = user.current_room_id.clone();
         //ensure our user is actually in a room
         if user.current_room_id != -1 {
             // Only mods can actually make the request to connect to a server
             if is_mod_or_owner(&user.current_room_id, &mut handler, &requester_id).await {
                 // Users can only make this request on behalf of themselves.
                 if request_data.user_id == requester_id {
-                    send_request_to_integration_server(
+                    let credentials = serde_json::json!({
+                        "connection_str": request_data.connection_str,
+                        "name_and_type": request_data.name_and_type,
+                        "password": request_data.password,
+                        "admin_password": request_data.admin_password,
+                    });
+                    if request_integration_connection(
                         integration_publish_channel,
-                        serde_json::to_string(&request_data).unwrap(),
-                        "connect_hoi".to_owned(),
-                        "-1".to_owned(),
+                        &mut write_state,
+                        IntegrationProvider::HouseOfIoT,
+                        credentials,
+                        request_data.outside_name,
+                        requester_id,
+                        room_id,
+                        request_data.remember_credentials,
                     )
-                    .await;
+                    .await
+                    {
+                        return Ok(());
+                    }
+                }
+            }
+        }
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    Ok(())
+}
+
+/// Same as create_hoi_connection but for any provider,
+/// the credentials have to match what the provider declares.
+pub async fn create_integration_connection(
+    request: BasicRequest,
+    integration_publish_channel: &Arc<Mutex<lapin::Channel>>,
+    server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    requester_id: i32,
+) -> Result<()> {
+    let request_data: ConnectIntegration = serde_json::from_str(&request.request_containing_data)?;
+    let mut write_state = server_state.write().await;
+    let mut handler = execution_handler.lock().await;
+    if let Some(user) = write_state.active_users.get(&requester_id) {
+        let room_id = user.current_room_id.clone();
+        //ensure our user is actually in a room
+        if user.current_room_id != -1
+            && request_data.user_id == requester_id
+            && request_data
+                .provider
+                .validate_credentials(&request_data.credentials)
+        {
+            // Only mods can actually make the request to connect to a server
+            if is_mod_or_owner(&user.current_room_id, &mut handler, &requester_id).await {
+                if request_integration_connection(
+                    integration_publish_channel,
+                    &mut write_state,
+                    request_data.provider,
+                    request_data.credentials,
+                    request_data.outside_name,
+                    requester_id,
+                    room_id,
+                    request_data.remember_credentials,
+                )
+                .await
+                {
                     return Ok(());
                 }
             }
@@ -1072,6 +1392,216 @@ pub async fn create_hoi_connection(
     Ok(())
 }
 
+/// Only the board's owner can reconnect it, with the credentials
+/// they asked us to remember. The board is usually disconnected
+/// so ownership is checked against the saved board.
+pub async fn reconnect_iot_board(
+    request: BasicRequest,
+    integration_publish_channel: &Arc<Mutex<lapin::Channel>>,
+    server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    requester_id: i32,
+) -> Result<()> {
+    let request_data: ReconnectIoTBoard = serde_json::from_str(&request.request_containing_data)?;
+    let mut write_state = server_state.write().await;
+    let mut handler = execution_handler.lock().await;
+    let current_room_id = write_state
+        .active_users
+        .get(&requester_id)
+        .map_or(-1, |user| user.current_room_id);
+    let saved_board =
+        data_fetcher::get_iot_board(&mut handler, &current_room_id, &request_data.external_id)
+            .await;
+    if saved_board
+        .1
+        .map_or(false, |saved_board| saved_board.owner_id == requester_id)
+    {
+        let stored = board::handler::remembered_credentials(
+            &mut handler,
+            &current_room_id,
+            &request_data.external_id,
+        )
+        .await;
+        if let Some(stored) = stored {
+            if request_integration_connection(
+                integration_publish_channel,
+                &mut write_state,
+                stored.provider,
+                stored.credentials,
+                stored.outside_name,
+                requester_id,
+                current_room_id,
+                false,
+            )
+            .await
+            {
+                return Ok(());
+            }
+        }
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    Ok(())
+}
+
+/// Boards are disconnected when their owner leaves a room that
+/// isn't persistent, the ones with remembered credentials are
+/// reconnected when the owner rejoins(getting their controllers back).
+async fn reconnect_saved_boards(
+    integration_publish_channel: &Arc<Mutex<lapin::Channel>>,
+    write_state: &mut ServerState,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    requester_id: i32,
+    room_id: i32,
+) {
+    let mut handler = execution_handler.lock().await;
+    let saved_boards = data_fetcher::get_iot_boards_for_room(&mut handler, &room_id).await;
+    if saved_boards.0 {
+        return;
+    }
+    for saved_board in saved_boards.1 {
+        if saved_board.owner_id != requester_id
+            || write_state
+                .external_servers
+                .contains_key(&saved_board.external_server_id)
+        {
+            continue;
+        }
+        let stored = board::handler::remembered_credentials(
+            &mut handler,
+            &room_id,
+            &saved_board.external_server_id,
+        )
+        .await;
+        if let Some(stored) = stored {
+            request_integration_connection(
+                integration_publish_channel,
+                write_state,
+                stored.provider,
+                stored.credentials,
+                stored.outside_name,
+                requester_id,
+                room_id,
+                false,
+            )
+            .await;
+        }
+    }
+}
+
+/// Asks the integration server to connect a board, the provider
+/// and credentials(encrypted, if the owner wants them remembered)
+/// are held on to until the auth result with the same request id
+/// arrives(see integration::router::take_pending_connect).
+/// Returns false if the credentials couldn't be sent.
+async fn request_integration_connection(
+    integration_publish_channel: &Arc<Mutex<lapin::Channel>>,
+    write_state: &mut ServerState,
+    provider: IntegrationProvider,
+    credentials: serde_json::Value,
+    outside_name: String,
+    user_id: i32,
+    room_id: i32,
+    remember_credentials: bool,
+) -> bool {
+    let request_id = Uuid::new_v4().to_string();
+    let payload = provider.connect_payload(&credentials, &outside_name, user_id, &request_id);
+    if !send_credentials_to_integration_server(
+        integration_publish_channel,
+        payload,
+        provider.connect_category(),
+    )
+    .await
+    {
+        return false;
+    }
+    let mut remembered = None;
+    if remember_credentials {
+        match integration::credentials::storage_key() {
+            Some(key) => {
+                let stored = StoredCredentials {
+                    provider: provider,
+                    credentials: credentials,
+                    outside_name: outside_name,
+                };
+                remembered = Some(integration::credentials::encrypt(
+                    &key,
+                    &serde_json::to_string(&stored).unwrap(),
+                ));
+            }
+            None => logging::console::log_failure(&format!(
+                "Can't remember credentials for user({}) without {}",
+                user_id,
+                integration::credentials::STORAGE_KEY_VAR
+            )),
+        }
+    }
+    write_state.pending_integration_connects.insert(
+        request_id,
+        PendingIntegrationConnect {
+            user_id: user_id,
+            room_id: room_id,
+            provider: provider,
+            credentials: remembered,
+            requested_at: chrono::Utc::now().timestamp_millis(),
+        },
+    );
+    return true;
+}
+
+/// Credentials are encrypted with the queue key, they only go out
+/// as is when plaintext is explicitly allowed. Returns false if
+/// they weren't sent.
+pub async fn send_credentials_to_integration_server(
+    integration_publish_channel: &Arc<Mutex<lapin::Channel>>,
+    data: String,
+    category: String,
+) -> bool {
+    let (data, encrypted) = match integration::credentials::queue_key() {
+        Some(key) => (integration::credentials::encrypt(&key, &data), true),
+        None if integration::credentials::plaintext_allowed() => {
+            logging::console::log_failure(&format!(
+                "Sending integration credentials unencrypted, set {}",
+                integration::credentials::QUEUE_KEY_VAR
+            ));
+            (data, false)
+        }
+        None => {
+            logging::console::log_failure(&format!(
+                "Refusing to send integration credentials unencrypted, set {} or {}=true",
+                integration::credentials::QUEUE_KEY_VAR,
+                integration::credentials::ALLOW_PLAINTEXT_VAR
+            ));
+            return false;
+        }
+    };
+    let channel = integration_publish_channel.lock().await;
+    let new_general_msg = GeneralMessage {
+        category,
+        data,
+        server_id: "-1".to_owned(),
+        encrypted,
+    };
+    rabbit::publish_integration_message(&channel, serde_json::to_string(&new_general_msg).unwrap())
+        .await
+        .unwrap_or_default();
+    return true;
+}
+
+/// What every provider needs to connect and act
+pub async fn get_integration_providers(server_state: &Arc<RwLock<ServerState>>, requester_id: i32) {
+    let mut write_state = server_state.write().await;
+    let descriptions: Vec<IntegrationProviderDescription> = providers::ALL_PROVIDERS
+        .iter()
+        .map(|provider| provider.describe())
+        .collect();
+    send_to_requester_channel(
+        serde_json::to_string(&descriptions).unwrap(),
+        requester_id,
+        &mut write_state,
+        "integration_providers".to_owned(),
+    );
+}
+
 pub async fn send_request_to_integration_server(
     integration_publish_channel: &Arc<Mutex<lapin::Channel>>,
     data: String,
@@ -1083,6 +1613,7 @@ pub async fn send_request_to_integration_server(
         category,
         data,
         server_id,
+        encrypted: false,
     };
     rabbit::publish_integration_message(&channel, serde_json::to_string(&new_general_msg).unwrap())
         .await
@@ -1093,12 +1624,17 @@ pub async fn remove_hoi_connection(
     request: BasicRequest,
     integration_publish_channel: &Arc<Mutex<lapin::Channel>>,
     server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
     requester_id: i32,
 ) -> Result<()> {
     let request_data: DisconnectMsg = serde_json::from_str(&request.request_containing_data)?;
     let mut write_state = server_state.write().await;
+    let current_room_id = write_state
+        .active_users
+        .get(&requester_id)
+        .map_or(-1, |user| user.current_room_id.clone());
     let remove_result = remove_hoi_connection_directly(
-        request_data.server_id,
+        request_data.server_id.clone(),
         integration_publish_channel,
         &mut write_state,
         requester_id,
@@ -1108,6 +1644,12 @@ pub async fn remove_hoi_connection(
     if let Ok(res) = remove_result {
         if res == false {
             send_error_response_to_requester(requester_id, &mut write_state);
+        } else {
+            // disconnecting on purpose means the board
+            // shouldn't be restored later
+            let mut handler = execution_handler.lock().await;
+            board::handler::forget_board(&mut handler, &current_room_id, &request_data.server_id)
+                .await;
         }
     } else {
         send_error_response_to_requester(requester_id, &mut write_state);
@@ -1134,7 +1676,7 @@ pub async fn remove_hoi_connection_directly(
                     send_request_to_integration_server(
                         integration_publish_channel,
                         String::new(),
-                        "disconnect_hoi".to_owned(),
+                        board.provider.disconnect_category(),
                         server_id.clone(),
                     )
                     .await;
@@ -1162,32 +1704,102 @@ pub async fn request_hoi_action(
     request: BasicRequest,
     integration_publish_channel: &Arc<Mutex<lapin::Channel>>,
     server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
     requester_id: i32,
 ) -> Result<()> {
     let request_data: HOIActionDataIncoming =
         serde_json::from_str(&request.request_containing_data)?;
     let mut write_state = server_state.write().await;
     if let Some(user) = write_state.active_users.get(&requester_id) {
+        let current_room_id = user.current_room_id.clone();
         //ensure our user is actually in a room
-        if let Some(room) = write_state.rooms.get(&user.current_room_id) {
-            // Only people with permission can make requests
+        if let Some(room) = write_state.rooms.get(&current_room_id) {
+            // Only people with permission can make requests,
+            // scoped to the bots/actions they were granted
             if let Some(board) = room.iot_server_connections.get(&request_data.server_id) {
-                if board.users_with_permission.contains(&requester_id)
-                    || board.owner_user_id == requester_id
-                {
+                if board::handler::can_run_action(
+                    board,
+                    &requester_id,
+                    &request_data.bot_name,
+                    &request_data.action,
+                ) {
                     logging::console::log_event(&format!(
                         "Executing HOI Action:{:?}",
                         request_data
                     ));
-                    send_request_to_integration_server(
+                    let mut handler = execution_handler.lock().await;
+                    let action_id = board::actions::send_action(
+                        &mut write_state,
+                        &mut handler,
                         integration_publish_channel,
-                        serde_json::to_string(&HOIActionDataOutgoing {
+                        &current_room_id,
+                        &request_data.server_id,
+                        Some(requester_id),
+                        request_data.bot_name.clone(),
+                        request_data.action.clone(),
+                    )
+                    .await;
+                    drop(handler);
+                    // the action id links the request to its action_response_iot
+                    send_to_requester_channel(
+                        serde_json::to_string(&IoTActionRequested {
+                            action_id: action_id,
+                            external_id: request_data.server_id,
                             bot_name: request_data.bot_name,
                             action: request_data.action,
                         })
                         .unwrap(),
-                        "action_hoi".to_owned(),
-                        request_data.server_id,
+                        requester_id,
+                        &mut write_state,
+                        "action_requested_iot".to_owned(),
+                    );
+                    return Ok(());
+                }
+            }
+        }
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    Ok(())
+}
+
+/// Only the board owner can hand it over,
+/// and only to someone in the same room.
+pub async fn transfer_board_ownership(
+    request: BasicRequest,
+    server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    requester_id: i32,
+) -> Result<()> {
+    let request_data: TransferBoardOwnership =
+        serde_json::from_str(&request.request_containing_data)?;
+    let mut write_state = server_state.write().await;
+    if let Some(user) = write_state.active_users.get(&requester_id) {
+        let current_room_id = user.current_room_id.clone();
+        if let Some(room) = write_state.rooms.get(&current_room_id) {
+            if let Some(board) = room.iot_server_connections.get(&request_data.external_id) {
+                if board.owner_user_id == requester_id
+                    && request_data.new_owner_id != requester_id
+                    && room.user_ids.contains(&request_data.new_owner_id)
+                {
+                    let mut handler = execution_handler.lock().await;
+                    board::handler::transfer_board(
+                        &mut write_state,
+                        &mut handler,
+                        &current_room_id,
+                        &request_data.external_id,
+                        &request_data.new_owner_id,
+                    )
+                    .await;
+                    rooms::audit::record(
+                        &mut handler,
+                        &current_room_id,
+                        &requester_id,
+                        Some(request_data.new_owner_id.clone()),
+                        "transfer_board_ownership",
+                        Some(
+                            serde_json::json!({ "external_id": request_data.external_id })
+                                .to_string(),
+                        ),
                     )
                     .await;
                     return Ok(());
@@ -1199,12 +1811,211 @@ pub async fn request_hoi_action(
     Ok(())
 }
 
+/// Board owners schedule actions on their boards,
+/// either once(run_at) or on a cron schedule.
+pub async fn create_board_schedule(
+    request: BasicRequest,
+    server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    requester_id: i32,
+) -> Result<()> {
+    let request_data: CreateBoardSchedule = serde_json::from_str(&request.request_containing_data)?;
+    let mut write_state = server_state.write().await;
+    let external_id = request_data.external_id.clone();
+    if let Some((room_id, board)) =
+        owned_board_in_current_room(&mut write_state, requester_id, &external_id)
+    {
+        let now = chrono::Utc::now().timestamp_millis();
+        if board::automation::add_schedule(board, request_data, now) {
+            let mut handler = execution_handler.lock().await;
+            board::automation::persist_automations(&mut handler, board).await;
+            drop(handler);
+            board::automation::broadcast_automations(&mut write_state, room_id, &external_id).await;
+            return Ok(());
+        }
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    Ok(())
+}
+
+/// Board owners run actions when the board's
+/// passive data meets a condition.
+pub async fn create_board_rule(
+    request: BasicRequest,
+    server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    requester_id: i32,
+) -> Result<()> {
+    let request_data: CreateBoardRule = serde_json::from_str(&request.request_containing_data)?;
+    let mut write_state = server_state.write().await;
+    let external_id = request_data.external_id.clone();
+    if let Some((room_id, board)) =
+        owned_board_in_current_room(&mut write_state, requester_id, &external_id)
+    {
+        if board::automation::add_rule(board, request_data) {
+            let mut handler = execution_handler.lock().await;
+            board::automation::persist_automations(&mut handler, board).await;
+            drop(handler);
+            board::automation::broadcast_automations(&mut write_state, room_id, &external_id).await;
+            return Ok(());
+        }
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    Ok(())
+}
+
+pub async fn remove_board_automation(
+    request: BasicRequest,
+    server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    requester_id: i32,
+) -> Result<()> {
+    let request_data: RemoveBoardAutomation =
+        serde_json::from_str(&request.request_containing_data)?;
+    let mut write_state = server_state.write().await;
+    if let Some((room_id, board)) =
+        owned_board_in_current_room(&mut write_state, requester_id, &request_data.external_id)
+    {
+        if board::automation::remove_automation(board, &request_data.automation_id) {
+            let mut handler = execution_handler.lock().await;
+            board::automation::persist_automations(&mut handler, board).await;
+            drop(handler);
+            board::automation::broadcast_automations(
+                &mut write_state,
+                room_id,
+                &request_data.external_id,
+            )
+            .await;
+            return Ok(());
+        }
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    Ok(())
+}
+
+/// Anyone in the room can see a board's automations
+pub async fn get_board_automations(
+    request: BasicRequest,
+    server_state: &Arc<RwLock<ServerState>>,
+    requester_id: i32,
+) -> Result<()> {
+    let request_data: GetBoardAutomations = serde_json::from_str(&request.request_containing_data)?;
+    let mut write_state = server_state.write().await;
+    if let Some(user) = write_state.active_users.get(&requester_id) {
+        if let Some(room) = write_state.rooms.get(&user.current_room_id) {
+            if let Some(board) = room.iot_server_connections.get(&request_data.external_id) {
+                let automations = board::automation::automations(board);
+                send_to_requester_channel(
+                    serde_json::to_string(&automations).unwrap(),
+                    requester_id,
+                    &mut write_state,
+                    "board_automations".to_owned(),
+                );
+                return Ok(());
+            }
+        }
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    Ok(())
+}
+
+/// Only the board owner can see who did what with it
+pub async fn get_iot_action_history(
+    request: BasicRequest,
+    server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    requester_id: i32,
+) -> Result<()> {
+    let request_data: GetIoTActionHistory = serde_json::from_str(&request.request_containing_data)?;
+    let mut write_state = server_state.write().await;
+    if let Some((room_id, _)) =
+        owned_board_in_current_room(&mut write_state, requester_id, &request_data.external_id)
+    {
+        let limit = request_data.limit.unwrap_or(100).clamp(1, 500);
+        let mut handler = execution_handler.lock().await;
+        let history =
+            board::actions::gather_history(&mut handler, &room_id, request_data.external_id, limit)
+                .await;
+        drop(handler);
+        if let Some(history) = history {
+            send_to_requester_channel(
+                serde_json::to_string(&history).unwrap(),
+                requester_id,
+                &mut write_state,
+                "iot_action_history".to_owned(),
+            );
+            return Ok(());
+        }
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    Ok(())
+}
+
+/// Anyone in the room can chart a board's passive data
+pub async fn get_iot_passive_history(
+    request: BasicRequest,
+    server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    requester_id: i32,
+) -> Result<()> {
+    let request_data: GetIoTPassiveHistory =
+        serde_json::from_str(&request.request_containing_data)?;
+    let mut write_state = server_state.write().await;
+    if let Some(user) = write_state.active_users.get(&requester_id) {
+        let current_room_id = user.current_room_id.clone();
+        let board_in_room = write_state
+            .rooms
+            .get(&current_room_id)
+            .map_or(false, |room| {
+                room.iot_server_connections
+                    .contains_key(&request_data.external_id)
+            });
+        if board_in_room {
+            let mut handler = execution_handler.lock().await;
+            let history =
+                board::history::gather_history(&mut handler, &current_room_id, request_data).await;
+            drop(handler);
+            if let Some(history) = history {
+                send_to_requester_channel(
+                    serde_json::to_string(&history).unwrap(),
+                    requester_id,
+                    &mut write_state,
+                    "iot_passive_history".to_owned(),
+                );
+                return Ok(());
+            }
+        }
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    Ok(())
+}
+
+/// The board if the requester owns it and
+/// it's connected to their current room.
+fn owned_board_in_current_room<'a>(
+    write_state: &'a mut ServerState,
+    requester_id: i32,
+    external_id: &String,
+) -> Option<(i32, &'a mut Board)> {
+    let current_room_id = write_state.active_users.get(&requester_id)?.current_room_id;
+    let board = write_state
+        .rooms
+        .get_mut(&current_room_id)?
+        .iot_server_connections
+        .get_mut(external_id)?;
+    if board.owner_user_id != requester_id {
+        return None;
+    }
+    return Some((current_room_id, board));
+}
+
 /// Give a users permission to control an iot server
 /// if that user is in your current room.
 /// TODO:Break this method up
 pub async fn give_or_revoke_iot_permission(
     request: BasicRequest,
     server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
     requester_id: i32,
 ) -> Result<()> {
     let mut write_state = server_state.write().await;
@@ -1222,6 +2033,27 @@ pub async fn give_or_revoke_iot_permission(
                 {
                     //only owners can give permission
                     if board.owner_user_id == requester_id {
+                        let mut handler = execution_handler.lock().await;
+                        rooms::audit::record(
+                            &mut handler,
+                            &current_room_id,
+                            &requester_id,
+                            Some(request_data.user_id.clone()),
+                            if request_data.now_has_permission {
+                                "give_iot_controller"
+                            } else {
+                                "revoke_iot_controller"
+                            },
+                            Some(
+                                serde_json::json!({
+                                    "external_id": request_data.external_id,
+                                    "bots": request_data.bots,
+                                    "actions": request_data.actions,
+                                })
+                                .to_string(),
+                            ),
+                        )
+                        .await;
                         //default -> revoke permissions
                         let mut outgoing_op_code = "removed_hoi_controller";
                         let mut outgoing_response_data =
@@ -1232,19 +2064,34 @@ pub async fn give_or_revoke_iot_permission(
                             })
                             .unwrap();
 
-                        //if we are giving permission
+                        //if we are giving permission(or changing its scope)
                         if request_data.now_has_permission {
-                            board.users_with_permission.insert(request_data.user_id);
+                            board.users_with_permission.insert(
+                                request_data.user_id.clone(),
+                                board::handler::grant_from_scopes(
+                                    request_data.bots.clone(),
+                                    request_data.actions.clone(),
+                                ),
+                            );
                             outgoing_op_code = "new_hoi_controller";
                             outgoing_response_data = serde_json::to_string(&NewIoTController {
                                 external_id: request_data.external_id,
                                 user_id: request_data.user_id,
                                 outside_name: board.outside_name.clone(),
+                                bots: request_data.bots,
+                                actions: request_data.actions,
                             })
                             .unwrap();
                         } else {
                             board.users_with_permission.remove(&request_data.user_id);
                         }
+                        board::handler::persist_board_permission(
+                            &mut handler,
+                            board,
+                            &request_data.user_id,
+                        )
+                        .await;
+                        drop(handler);
 
                         //Let the room know this user has been removed
                         //from controlling this board
@@ -1268,6 +2115,25 @@ pub async fn give_or_revoke_iot_permission(
     Ok(())
 }
 
+/// Diff mode starts with a full existing_iot_data
+/// snapshot for the diffs to be applied to.
+pub async fn set_passive_data_mode(
+    request: BasicRequest,
+    server_state: &Arc<RwLock<ServerState>>,
+    requester_id: i32,
+) -> Result<()> {
+    let request_data: SetPassiveDataMode = serde_json::from_str(&request.request_containing_data)?;
+    let mut write_state = server_state.write().await;
+    if let Some(user) = write_state.active_users.get_mut(&requester_id) {
+        user.passive_data_diffs = request_data.diff;
+        drop(write_state);
+        get_passive_data_snapshot(server_state, requester_id).await;
+        return Ok(());
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    Ok(())
+}
+
 pub async fn get_passive_data_snapshot(server_state: &Arc<RwLock<ServerState>>, requester_id: i32) {
     let mut write_state = server_state.write().await;
     if let Some(user) = write_state.active_users.get(&requester_id) {
@@ -1279,10 +2145,15 @@ pub async fn get_passive_data_snapshot(server_state: &Arc<RwLock<ServerState>>,
                     owner_id: server.owner_user_id.clone(),
                     external_id: server.external_server_id.clone(),
                     controllers_of_room: Vec::new(),
-                    passive_data_snap_shot: server.passive_data_snapshot.clone(),
+                    controller_scopes: board::handler::controller_scopes(server),
+                    // diffs are taken against what the room last heard
+                    passive_data_snap_shot: server.passive_fan_out.fanned_out_snapshot.clone(),
                     outside_name: server.outside_name.clone(),
+                    provider: server.provider,
+                    status: server.status,
+                    last_seen: server.last_seen,
                 };
-                for controller in server.users_with_permission.iter() {
+                for controller in server.users_with_permission.keys() {
                     existing.controllers_of_room.push(controller.clone());
                 }
                 all_existing.push(existing);
@@ -1308,20 +2179,31 @@ pub async fn get_blocked_users_for_room(
             let mut handler = execution_handler.lock().await;
 
             if is_mod_or_owner(&user.current_room_id, &mut handler, &requester_id).await {
-                let blocked_user_ids: Vec<i32> = data_fetcher::get_blocked_user_ids_for_room(
-                    &mut handler,
-                    &user.current_room_id,
-                )
-                .await
-                .1
-                .iter()
-                .cloned()
-                .collect();
+                let room_blocks: Vec<DBRoomBlock> =
+                    data_fetcher::get_room_blocks(&mut handler, &user.current_room_id)
+                        .await
+                        .1;
+                let blocked_user_ids: Vec<i32> = room_blocks
+                    .iter()
+                    .map(|block| block.blocked_user_id.clone())
+                    .collect();
 
                 let users =
                     data_fetcher::get_users_for_user(requester_id, blocked_user_ids, &mut handler)
                         .await;
-                let response_data = BlockedFromRoom { users: users.1 };
+                let blocks: Vec<RoomBlockDetails> = room_blocks
+                    .into_iter()
+                    .map(|block| RoomBlockDetails {
+                        user_id: block.blocked_user_id,
+                        blocked_by: block.blocked_by,
+                        reason: block.reason,
+                        expires_at: block.expires_at,
+                    })
+                    .collect();
+                let response_data = BlockedFromRoom {
+                    users: users.1,
+                    blocks: blocks,
+                };
                 send_to_requester_channel(
                     serde_json::to_string(&response_data).unwrap(),
                     requester_id,
@@ -1333,6 +2215,64 @@ pub async fn get_blocked_users_for_room(
     }
 }
 
+/// Owners and mods can read the audit log of their room,
+/// once the room is destroyed only its creator can.
+pub async fn get_room_audit_log(
+    request: BasicRequest,
+    server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    requester_id: i32,
+) -> Result<()> {
+    let request_data: GetRoomAuditLog = serde_json::from_str(&request.request_containing_data)?;
+    let mut write_state = server_state.write().await;
+    let mut handler = execution_handler.lock().await;
+    let room_id = request_data.room_id;
+    let mut allowed = is_mod_or_owner(&room_id, &mut handler, &requester_id).await;
+    if !allowed && !write_state.rooms.contains_key(&room_id) {
+        let creation_events = data_fetcher::get_audit_events_for_room_by_action(
+            &mut handler,
+            &room_id,
+            &"create_room".to_owned(),
+        )
+        .await;
+        allowed = creation_events
+            .1
+            .iter()
+            .any(|audit_event| audit_event.actor_id == requester_id);
+    }
+    if allowed {
+        let limit = request_data.limit.unwrap_or(100).clamp(1, 500);
+        let audit_events =
+            data_fetcher::get_audit_events_for_room(&mut handler, &room_id, &limit).await;
+        if !audit_events.0 {
+            let events: Vec<AuditEvent> = audit_events
+                .1
+                .into_iter()
+                .map(|audit_event| AuditEvent {
+                    actor_id: audit_event.actor_id,
+                    target_id: audit_event.target_id,
+                    action: audit_event.action,
+                    payload: audit_event.payload,
+                    created_at: audit_event.created_at,
+                })
+                .collect();
+            send_to_requester_channel(
+                serde_json::to_string(&RoomAuditLog {
+                    room_id: room_id,
+                    events: events,
+                })
+                .unwrap(),
+                requester_id,
+                &mut write_state,
+                "room_audit_log".to_owned(),
+            );
+            return Ok(());
+        }
+    }
+    send_error_response_to_requester(requester_id, &mut write_state);
+    return Ok(());
+}
+
 pub fn room_is_joinable(
     read_state: &ServerState,
     peer_id: &i32,
@@ -1341,7 +2281,11 @@ pub fn room_is_joinable(
 ) -> bool {
     if let Some(user) = read_state.active_users.get(peer_id) {
         if let Some(room) = read_state.rooms.get(room_id) {
-            if user.current_room_id == -1 && room.public && peer_id == requester_id {
+            if user.current_room_id == -1
+                && room.public
+                && peer_id == requester_id
+                && !rooms::handler::user_is_on_kick_cooldown(read_state, room_id, peer_id)
+            {
                 return true;
             }
         }
@@ -50,6 +50,29 @@ pub fn web_rtc_request_is_valid(
     return false;
 }
 
+/// Mod muted users can't send tracks and
+/// mod deafened users can't receive them.
+pub fn web_rtc_request_is_blocked_by_mod(
+    server_state: &ServerState,
+    request_data: &serde_json::Value,
+    requester_id: &i32,
+    op_code: &str,
+) -> bool {
+    // the room id is validated before we get here
+    let room_id: i32 = match request_data["roomId"].to_string().parse() {
+        Ok(room_id) => room_id,
+        Err(_) => return true,
+    };
+    if let Some(room) = server_state.rooms.get(&room_id) {
+        return match op_code {
+            "@send-track" => room.muted.contains(requester_id),
+            "@get-recv-tracks" => room.deaf.contains(requester_id),
+            _ => false,
+        };
+    }
+    return false;
+}
+
 pub fn parse_peer_and_room_id(
     peer_id: &String,
     room_id: &String,
@@ -87,7 +110,8 @@ pub async fn send_follow_list(
                 username: user.username,
                 avatar_url: user.avatar_url,
                 room_id: grab_current_room(&mut write_state, &user.user_id),
-                online: write_state.active_users.contains_key(&user.user_id),
+                online: write_state.active_users.contains_key(&user.user_id)
+                    || write_state.remote_users.contains_key(&user.user_id),
                 user_id: user.user_id,
             });
         }
@@ -138,10 +162,12 @@ pub fn construct_communication_room(
     holder.push(new_communication_room);
 }
 
+// users connected to other instances are
+// known through their presence announcements.
 fn grab_current_room(write_state: &mut ServerState, user_id: &i32) -> Option<i32> {
     if let Some(data) = write_state.active_users.get(user_id) {
         Some(data.current_room_id)
     } else {
-        None
+        write_state.remote_users.get(user_id).cloned()
     }
 }
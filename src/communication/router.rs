@@ -77,6 +77,39 @@ pub async fn route_msg(
             )
             .await
         }
+        "kick_user_from_room" => {
+            handler::kick_user_from_room(
+                basic_request,
+                user_id,
+                server_state,
+                execution_handler,
+                voice_publish_channel,
+            )
+            .await
+        }
+        "mod_mute_user" | "mod_unmute_user" | "mod_deafen_user" | "mod_undeafen_user" => {
+            handler::mod_mute_or_deafen_user(
+                basic_request,
+                user_id,
+                server_state,
+                execution_handler,
+                voice_publish_channel,
+            )
+            .await
+        }
+        "start_recording" | "stop_recording" => {
+            handler::start_or_stop_recording(
+                basic_request,
+                user_id,
+                server_state,
+                execution_handler,
+                voice_publish_channel,
+            )
+            .await
+        }
+        "accept_recording_consent" => {
+            handler::accept_recording_consent(basic_request, user_id, server_state).await
+        }
         "get_followers" => {
             handler::get_followers_or_following_list(
                 basic_request,
@@ -102,6 +135,7 @@ pub async fn route_msg(
                 basic_request,
                 server_state,
                 voice_publish_channel,
+                integration_publish_channel,
                 execution_handler,
                 user_id,
                 "join-as-speaker",
@@ -113,6 +147,7 @@ pub async fn route_msg(
                 basic_request,
                 server_state,
                 voice_publish_channel,
+                integration_publish_channel,
                 execution_handler,
                 user_id,
                 "join-as-new-peer",
@@ -127,6 +162,7 @@ pub async fn route_msg(
                 basic_request,
                 server_state,
                 user_id,
+                voice_publish_channel,
                 execution_handler,
                 "raise",
             )
@@ -137,6 +173,7 @@ pub async fn route_msg(
                 basic_request,
                 server_state,
                 user_id,
+                voice_publish_channel,
                 execution_handler,
                 "lower",
             )
@@ -252,6 +289,29 @@ pub async fn route_msg(
             )
             .await
         }
+        "connect_integration" => {
+            handler::create_integration_connection(
+                basic_request,
+                integration_publish_channel.unwrap(),
+                server_state,
+                execution_handler,
+                user_id,
+            )
+            .await
+        }
+        "reconnect_iot_board" => {
+            handler::reconnect_iot_board(
+                basic_request,
+                integration_publish_channel.unwrap(),
+                server_state,
+                execution_handler,
+                user_id,
+            )
+            .await
+        }
+        "get_integration_providers" => {
+            Ok(handler::get_integration_providers(server_state, user_id).await)
+        }
         "disconnect_hoi" => {
             handler::remove_hoi_connection(
                 basic_request,
@@ -260,12 +320,57 @@ pub async fn route_msg(
                 //provide this value.
                 integration_publish_channel.unwrap(),
                 server_state,
+                execution_handler,
+                user_id,
+            )
+            .await
+        }
+        "transfer_board_ownership" => {
+            handler::transfer_board_ownership(
+                basic_request,
+                server_state,
+                execution_handler,
+                user_id,
+            )
+            .await
+        }
+        "create_board_schedule" => {
+            handler::create_board_schedule(basic_request, server_state, execution_handler, user_id)
+                .await
+        }
+        "create_board_rule" => {
+            handler::create_board_rule(basic_request, server_state, execution_handler, user_id)
+                .await
+        }
+        "remove_board_automation" => {
+            handler::remove_board_automation(
+                basic_request,
+                server_state,
+                execution_handler,
+                user_id,
+            )
+            .await
+        }
+        "get_board_automations" => {
+            handler::get_board_automations(basic_request, server_state, user_id).await
+        }
+        "get_iot_passive_history" => {
+            handler::get_iot_passive_history(
+                basic_request,
+                server_state,
+                execution_handler,
                 user_id,
             )
             .await
         }
         "give_or_revoke_controller_iot" => {
-            handler::give_or_revoke_iot_permission(basic_request, server_state, user_id).await
+            handler::give_or_revoke_iot_permission(
+                basic_request,
+                server_state,
+                execution_handler,
+                user_id,
+            )
+            .await
         }
 
         "relation_modification" => {
@@ -278,6 +383,10 @@ pub async fn route_msg(
             .await
         }
 
+        "get_room_audit_log" => {
+            handler::get_room_audit_log(basic_request, server_state, execution_handler, user_id)
+                .await
+        }
         "get_room_blocked" => {
             Ok(handler::get_blocked_users_for_room(server_state, execution_handler, user_id).await)
         }
@@ -289,11 +398,19 @@ pub async fn route_msg(
                 basic_request,
                 integration_publish_channel.unwrap(),
                 server_state,
+                execution_handler,
                 user_id,
             )
             .await
         }
+        "get_iot_action_history" => {
+            handler::get_iot_action_history(basic_request, server_state, execution_handler, user_id)
+                .await
+        }
         "get_iot_passive" => Ok(handler::get_passive_data_snapshot(server_state, user_id).await),
+        "set_passive_data_mode" => {
+            handler::set_passive_data_mode(basic_request, server_state, user_id).await
+        }
 
         _ => Ok(handler::normal_invalid_request(server_state, user_id).await),
     }
@@ -448,6 +448,9 @@ fn generate_room_block(room_id: &i32, user_id: i32) -> DBRoomBlock {
         id: -1,
         owner_room_id: room_id.clone(),
         blocked_user_id: user_id,
+        expires_at: None,
+        reason: None,
+        blocked_by: None,
     };
 }
 fn generate_different_room_block(room_id: &i32, user_id: i32) -> DBRoomBlock {
@@ -455,6 +458,9 @@ fn generate_different_room_block(room_id: &i32, user_id: i32) -> DBRoomBlock {
         id: -1,
         owner_room_id: room_id.clone(),
         blocked_user_id: user_id,
+        expires_at: None,
+        reason: None,
+        blocked_by: None,
     };
 }
 
@@ -1,8 +1,10 @@
 use crate::communication::router;
 use crate::communication::tests::helpers::helpers;
+use crate::communication::types::GenericRoomIdAndPeerId;
 use crate::data_store::sql_execution_handler::ExecutionHandler;
 use crate::state::state::ServerState;
 use futures::lock::Mutex;
+use lapin::Consumer;
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use tokio_stream::wrappers::UnboundedReceiverStream;
@@ -137,3 +139,38 @@ pub async fn users_not_in_room_cannot_make_requests(
     )
     .await;
 }
+
+pub async fn users_in_auto_speaker_room_are_promoted(
+    consume_channel: &mut Consumer,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    state: &Arc<RwLock<ServerState>>,
+) {
+    // TESTCASE - AUTO SPEAKER ROOMS PROMOTE HAND RAISERS
+    // Raising your hand in an auto speaker room skips the
+    // asking stage and goes straight to the voice server
+    // as an add speaker request.
+    // From previous tests, we know user number 34 is a listener.
+    helpers::set_room_auto_speaker(state, 3, true).await;
+    let raise_hand_message = helpers::basic_request(
+        "raise_hand".to_owned(),
+        helpers::basic_hand_raise_or_lower(3, 34),
+    );
+    router::route_msg(
+        raise_hand_message,
+        34,
+        state,
+        publish_channel,
+        None,
+        execution_handler,
+    )
+    .await
+    .unwrap();
+    helpers::grab_and_assert_message_to_voice_server::<GenericRoomIdAndPeerId>(
+        consume_channel,
+        helpers::generic_room_and_peer_id(34, 3),
+        "34".to_owned(),
+        "add-speaker".to_owned(),
+    )
+    .await;
+}
@@ -9,7 +9,7 @@ pub mod helpers {
     use crate::communication::types::{
         AllUsersInRoomResponse, BasicRequest, BasicResponse, BasicRoomCreation, CommunicationRoom,
         GenericRoomIdAndPeerId, GenericUserId, User, UserPreview, VoiceServerClosePeer,
-        VoiceServerCreateRoom, VoiceServerRequest,
+        VoiceServerCreateRoom,
     };
     use crate::communication::{data_capturer, router};
     use crate::data_store::db_models::DBUser;
@@ -21,7 +21,7 @@ pub mod helpers {
     #[allow(unused_imports)]
     use futures_util::{stream::SplitSink, SinkExt, StreamExt, TryFutureExt};
     use lapin::{options::*, types::FieldTable, Channel, Connection, Consumer};
-    use serde::Serialize;
+    use serde::{Deserialize, Serialize};
     use std::collections::HashMap;
     use std::sync::Arc;
     use tokio::sync::mpsc;
@@ -77,6 +77,7 @@ pub mod helpers {
             deaf: true,
             ip: "test".to_string(),
             current_room_id: -1,
+            passive_data_diffs: false,
         };
         state.active_users.insert(user_id, user);
     }
@@ -134,12 +135,20 @@ pub mod helpers {
         op: String,
     ) {
         let message = consume_message(consume_channel).await;
-        let vs_message: VoiceServerRequest<T> = serde_json::from_str(&message).unwrap();
+        let vs_message: SentVoiceServerRequest<T> = serde_json::from_str(&message).unwrap();
         assert_eq!(serde_json::to_string(&vs_message.d).unwrap(), d);
         assert_eq!(op, vs_message.op);
         assert_eq!(uid, vs_message.uid);
     }
 
+    // what the voice server sees on the wire
+    #[derive(Deserialize)]
+    struct SentVoiceServerRequest<T> {
+        op: String,
+        d: T,
+        uid: String,
+    }
+
     pub fn basic_request(op: String, data: String) -> String {
         return serde_json::to_string(&BasicRequest {
             request_op_code: op,
@@ -161,6 +170,7 @@ pub mod helpers {
             name: "test".to_owned(),
             desc: "test".to_owned(),
             public: true,
+            persistent: false,
         })
         .unwrap();
     }
@@ -247,6 +257,15 @@ pub mod helpers {
         return user;
     }
 
+    pub async fn set_room_auto_speaker(
+        server_state: &Arc<RwLock<ServerState>>,
+        room_id: i32,
+        auto_speaker: bool,
+    ) {
+        let mut write_state = server_state.write().await;
+        write_state.rooms.get_mut(&room_id).unwrap().auto_speaker = auto_speaker;
+    }
+
     //This helps clear all of the fluff from room state
     //one time users
     pub async fn clear_all_users_except_owner(server_state: &Arc<RwLock<ServerState>>) {
@@ -328,6 +347,8 @@ pub mod helpers {
         let response = AllUsersInRoomResponse {
             room_id: 3,
             users: vec![user],
+            active_speaker: None,
+            currently_speaking: Vec::new(),
         };
         return serde_json::to_string(&response).unwrap();
     }
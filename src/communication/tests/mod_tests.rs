@@ -1,6 +1,8 @@
 use crate::communication::router;
 use crate::communication::tests::helpers::helpers;
-use crate::communication::types::GenericRoomIdAndPeerId;
+use crate::communication::types::{
+    DeafAndMuteStatus, DeafAndMuteStatusUpdate, GenericRoomIdAndPeerId, ModMuteOrDeafenUser,
+};
 use crate::data_store::sql_execution_handler::ExecutionHandler;
 use crate::state::state::ServerState;
 use futures::lock::Mutex;
@@ -170,3 +172,112 @@ pub async fn mods_can_lower_hands(
     helpers::grab_and_assert_request_response(speaker_rx, "user_hand_lowered", "34").await;
     helpers::clear_message_that_was_fanned(vec![listener_rx]).await;
 }
+
+pub async fn mods_can_mute_and_deafen(
+    consume_channel: &mut Consumer,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    state: &Arc<RwLock<ServerState>>,
+    owner_rx: &mut UnboundedReceiverStream<Message>,
+    listener_rx: &mut UnboundedReceiverStream<Message>,
+) {
+    //TESTCASE - Mods can mute/deafen and lift it again,
+    //the user's own flags are left alone.
+    //From previous tests, we know user number 34 is a listener.
+    let mut write_state = state.write().await;
+    let user = write_state.active_users.get_mut(&34).unwrap();
+    user.muted = false;
+    user.deaf = false;
+    drop(write_state);
+    let data = serde_json::to_string(&ModMuteOrDeafenUser {
+        user_id: 34,
+        room_id: 3,
+    })
+    .unwrap();
+    let steps = [
+        ("mod_mute_user", "pause-producer", true, false),
+        ("mod_unmute_user", "resume-producer", false, false),
+        ("mod_deafen_user", "pause-consumers", false, true),
+        ("mod_undeafen_user", "resume-consumers", false, false),
+    ];
+    for (op, voice_server_op, mod_muted, mod_deafened) in steps {
+        let request = helpers::basic_request(op.to_owned(), data.clone());
+        router::route_msg(request, 33, state, publish_channel, None, execution_handler)
+            .await
+            .unwrap();
+        helpers::grab_and_assert_message_to_voice_server::<GenericRoomIdAndPeerId>(
+            consume_channel,
+            helpers::generic_room_and_peer_id(34, 3),
+            "34".to_owned(),
+            voice_server_op.to_owned(),
+        )
+        .await;
+        let update = serde_json::to_string(&DeafAndMuteStatusUpdate {
+            deaf: false,
+            muted: false,
+            user_id: 34,
+            mod_muted: mod_muted,
+            mod_deafened: mod_deafened,
+        })
+        .unwrap();
+        helpers::grab_and_assert_request_response(owner_rx, "user_mute_and_deaf_update", &update)
+            .await;
+        helpers::grab_and_assert_request_response(
+            listener_rx,
+            "user_mute_and_deaf_update",
+            &update,
+        )
+        .await;
+        let read_state = state.read().await;
+        let user = read_state.active_users.get(&34).unwrap();
+        assert_eq!(user.muted, false);
+        assert_eq!(user.deaf, false);
+    }
+
+    //TESTCASE - Lifting a mod mute doesn't unmute
+    //a user who muted themselves first.
+    let request = helpers::basic_request(
+        "update_deaf_and_mute".to_owned(),
+        serde_json::to_string(&DeafAndMuteStatus {
+            deaf: false,
+            muted: true,
+        })
+        .unwrap(),
+    );
+    router::route_msg(request, 34, state, publish_channel, None, execution_handler)
+        .await
+        .unwrap();
+    helpers::clear_message_that_was_fanned(vec![owner_rx, listener_rx]).await;
+    let request = helpers::basic_request("mod_mute_user".to_owned(), data.clone());
+    router::route_msg(request, 33, state, publish_channel, None, execution_handler)
+        .await
+        .unwrap();
+    helpers::grab_and_assert_message_to_voice_server::<GenericRoomIdAndPeerId>(
+        consume_channel,
+        helpers::generic_room_and_peer_id(34, 3),
+        "34".to_owned(),
+        "pause-producer".to_owned(),
+    )
+    .await;
+    helpers::clear_message_that_was_fanned(vec![owner_rx, listener_rx]).await;
+    // 34 is still muted so nothing is resumed
+    let request = helpers::basic_request("mod_unmute_user".to_owned(), data);
+    router::route_msg(request, 33, state, publish_channel, None, execution_handler)
+        .await
+        .unwrap();
+    let update = serde_json::to_string(&DeafAndMuteStatusUpdate {
+        deaf: false,
+        muted: true,
+        user_id: 34,
+        mod_muted: false,
+        mod_deafened: false,
+    })
+    .unwrap();
+    helpers::grab_and_assert_request_response(owner_rx, "user_mute_and_deaf_update", &update).await;
+    helpers::grab_and_assert_request_response(listener_rx, "user_mute_and_deaf_update", &update)
+        .await;
+    let mut write_state = state.write().await;
+    let user = write_state.active_users.get_mut(&34).unwrap();
+    assert_eq!(user.muted, true);
+    user.muted = false;
+}
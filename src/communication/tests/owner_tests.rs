@@ -33,6 +33,8 @@ pub async fn owner_can_block_from_room(
     let data = serde_json::to_string(&BlockUserFromRoom {
         user_id: new_real_user_id.clone(),
         room_id: 3,
+        duration_secs: None,
+        reason: None,
     })
     .unwrap();
     let request = helpers::basic_request("block_user_from_room".to_string(), data.clone());
@@ -72,6 +74,8 @@ pub async fn non_owner_can_not_block_from_room(
     let data = serde_json::to_string(&BlockUserFromRoom {
         user_id: 38,
         room_id: 3,
+        duration_secs: None,
+        reason: None,
     })
     .unwrap();
     let request = helpers::basic_request("block_user_from_room".to_string(), data);
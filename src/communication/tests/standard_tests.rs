@@ -3,8 +3,9 @@ use crate::communication::types::{
     DeafAndMuteStatus, DeafAndMuteStatusUpdate, GenericRoomId, GenericRoomIdAndPeerId,
     GenericUserId, RoomUpdate, VoiceServerClosePeer, VoiceServerCreateRoom, VoiceServerDestroyRoom,
 };
-use crate::communication::{data_fetcher, router};
+use crate::communication::{data_capturer, data_fetcher, router};
 use crate::data_store::sql_execution_handler::ExecutionHandler;
+use crate::rooms::permission_configs;
 use crate::state::state::ServerState;
 use futures::lock::Mutex;
 use lapin::Consumer;
@@ -531,6 +532,106 @@ pub async fn test_leaving_room_without_cleanup(
     );
 }
 
+//persistent rooms keep their permission rows after
+//users leave, so rejoining has to reuse them.
+pub async fn test_leaving_and_rejoining_persistent_room(
+    consume_channel: &mut Consumer,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+    state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+) {
+    println!("testing leaving and rejoining a persistent room");
+    state.write().await.rooms.get_mut(&3).unwrap().persistent = true;
+    let new_user = helpers::spawn_new_real_user_and_join_room(
+        publish_channel,
+        execution_handler,
+        state,
+        consume_channel,
+        "9@#$1234234persistent42345kqw1321241".to_string(),
+        "%12312$$$$persistent833234024nsdocikndv0".to_string(),
+    )
+    .await;
+    let request = helpers::basic_request(
+        "leave_room".to_owned(),
+        serde_json::to_string(&GenericRoomId { room_id: 3 }).unwrap(),
+    );
+    router::route_msg(
+        request,
+        new_user.0.clone(),
+        state,
+        publish_channel,
+        None,
+        execution_handler,
+    )
+    .await
+    .unwrap();
+    helpers::consume_message(consume_channel).await;
+    let join_msg = helpers::basic_request(
+        "join-as-new-peer".to_owned(),
+        helpers::generic_room_and_peer_id(new_user.0.clone(), 3),
+    );
+    helpers::send_create_or_join_room_request(
+        state,
+        join_msg,
+        publish_channel,
+        execution_handler,
+        -1,
+        &new_user.0,
+    )
+    .await;
+    helpers::consume_message(consume_channel).await;
+    let mut handler = execution_handler.lock().await;
+    let rows = handler
+        .select_all_room_permissions_for_user(&new_user.0, &3)
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    assert!(
+        data_fetcher::get_single_user_permissions(&3, &new_user.0, &mut handler)
+            .await
+            .is_some()
+    );
+    //the owner re-entering the empty room gets
+    //mod back without a second row
+    let owner_permissions = permission_configs::regular_listener(3, 33);
+    data_capturer::capture_new_room_permissions_update(&owner_permissions, &mut handler).await;
+    drop(handler);
+    let mut write_state = state.write().await;
+    let room = write_state.rooms.get_mut(&3).unwrap();
+    let users_in_room = room.user_ids.clone();
+    room.user_ids = HashSet::new();
+    drop(write_state);
+    let join_msg = helpers::basic_request(
+        "join-as-speaker".to_owned(),
+        helpers::generic_room_and_peer_id(33, 3),
+    );
+    helpers::send_create_or_join_room_request(
+        state,
+        join_msg,
+        publish_channel,
+        execution_handler,
+        -1,
+        &33,
+    )
+    .await;
+    helpers::consume_message(consume_channel).await;
+    let mut handler = execution_handler.lock().await;
+    let rows = handler
+        .select_all_room_permissions_for_user(&33, &3)
+        .await
+        .unwrap();
+    assert_eq!(rows.len(), 1);
+    let owner_permissions = data_fetcher::get_single_user_permissions(&3, &33, &mut handler)
+        .await
+        .unwrap();
+    assert!(owner_permissions.is_mod);
+    drop(handler);
+    let mut write_state = state.write().await;
+    let room = write_state.rooms.get_mut(&3).unwrap();
+    room.user_ids.extend(users_in_room);
+    room.persistent = false;
+}
+
 pub async fn test_leaving_room_with_cleanup(
     consume_channel: &mut Consumer,
     publish_channel: &Arc<Mutex<lapin::Channel>>,
@@ -611,6 +712,8 @@ pub async fn test_updating_room_meta_data(
         chat_throttle: 2000,
         description: "for the best".to_owned(),
         auto_speaker: true,
+        speaker_cap: None,
+        persistent: None,
     };
     let basic_request = helpers::basic_request(
         "update_room_meta".to_owned(),
@@ -635,6 +738,35 @@ pub async fn test_updating_room_meta_data(
     )
     .await;
 
+    //speaker caps below one are rejected, even for mods
+    let room_update: RoomUpdate = RoomUpdate {
+        name: "test90432840".to_owned(),
+        public: false,
+        chat_throttle: 2000,
+        description: "for the best".to_owned(),
+        auto_speaker: true,
+        speaker_cap: Some(0),
+        persistent: None,
+    };
+    let basic_request = helpers::basic_request(
+        "update_room_meta".to_owned(),
+        serde_json::to_string(&room_update).unwrap(),
+    );
+
+    router::route_msg(
+        basic_request,
+        33,
+        state,
+        publish_channel,
+        None,
+        execution_handler,
+    )
+    .await
+    .unwrap();
+
+    helpers::grab_and_assert_request_response(user_one_rx, "invalid_request", "issue with request")
+        .await;
+
     //test valid update
     //user 33 is the owner and is a mod so it should complete
     let room_update: RoomUpdate = RoomUpdate {
@@ -643,6 +775,8 @@ pub async fn test_updating_room_meta_data(
         chat_throttle: 3000,
         description: "for the bes333".to_owned(),
         auto_speaker: true,
+        speaker_cap: Some(5),
+        persistent: None,
     };
     let basic_request = helpers::basic_request(
         "update_room_meta".to_owned(),
@@ -674,6 +808,7 @@ pub async fn test_updating_room_meta_data(
     assert_eq!(room.desc, room_update.description);
     assert_eq!(room.auto_speaker, room_update.auto_speaker);
     assert_eq!(room.name, room_update.name);
+    assert_eq!(room.speaker_cap, room_update.speaker_cap);
 }
 
 pub async fn test_updating_muted_and_deaf(
@@ -691,6 +826,8 @@ pub async fn test_updating_muted_and_deaf(
         deaf: true,
         muted: true,
         user_id: 33,
+        mod_muted: false,
+        mod_deafened: false,
     };
 
     let basic_request = helpers::basic_request(
@@ -116,6 +116,10 @@ pub async fn tests() {
         34,
     )
     .await;
+    //hand raising in auto speaker rooms promotes
+    //the user right away, so turn it off to test
+    //the asking flow.
+    helpers::set_room_auto_speaker(&mock_state, 3, false).await;
     test_raising_and_lowering_hand(
         &publish_channel,
         &mock_state,
@@ -153,6 +157,22 @@ pub async fn tests() {
         &mock_state,
     )
     .await;
+    mod_tests::mods_can_mute_and_deafen(
+        &mut consumer,
+        &publish_channel,
+        &execution_handler,
+        &mock_state,
+        &mut rx_user_one,
+        &mut rx_user_two,
+    )
+    .await;
+    hand_tests::users_in_auto_speaker_room_are_promoted(
+        &mut consumer,
+        &publish_channel,
+        &execution_handler,
+        &mock_state,
+    )
+    .await;
     //after this method there are no more
     //mock users in the room, all users
     //have a db user linked to it
@@ -271,6 +291,13 @@ pub async fn tests() {
         &execution_handler,
     )
     .await;
+    standard_tests::test_leaving_and_rejoining_persistent_room(
+        &mut consumer,
+        &publish_channel,
+        &mock_state,
+        &execution_handler,
+    )
+    .await;
     standard_tests::test_leaving_room_with_cleanup(
         &mut consumer,
         &publish_channel,
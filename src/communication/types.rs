@@ -12,6 +12,9 @@ use std::collections::HashMap;
 
 use std::option::Option;
 
+use crate::board::types::BoardStatus;
+use crate::integration::providers::IntegrationProvider;
+
 //Gathering from client/sending to rabbitmq
 
 #[derive(Deserialize, Serialize)]
@@ -47,6 +50,8 @@ pub struct BasicRoomCreation {
     pub name: String,
     pub desc: String,
     pub public: bool,
+    #[serde(default)]
+    pub persistent: bool,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -60,6 +65,9 @@ pub struct DeafAndMuteStatusUpdate {
     pub muted: bool,
     pub deaf: bool,
     pub user_id: i32,
+    /// imposed by a mod, the user can't lift these themselves
+    pub mod_muted: bool,
+    pub mod_deafened: bool,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -93,6 +101,18 @@ pub struct CommunicationRoom {
 pub struct AllUsersInRoomResponse {
     pub room_id: i32,
     pub users: Vec<User>,
+    pub active_speaker: Option<i32>,
+    pub currently_speaking: Vec<i32>,
+}
+
+/// Throttled, see rooms::speaking
+#[derive(Deserialize, Serialize)]
+pub struct RoomSpeakingUpdate {
+    pub room_id: i32,
+    pub active_speaker: Option<i32>,
+    pub currently_speaking: Vec<i32>,
+    /// user id -> volume(dBov, 0 is the loudest)
+    pub audio_levels: HashMap<i32, f64>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -136,6 +156,46 @@ pub struct RoomSettingsEditOrCreation {
 pub struct BlockUserFromRoom {
     pub user_id: i32,
     pub room_id: i32,
+    /// None blocks the user permanently
+    #[serde(default)]
+    pub duration_secs: Option<i64>,
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct KickUserFromRoom {
+    pub user_id: i32,
+    pub room_id: i32,
+    #[serde(default)]
+    pub reason: String,
+}
+
+/// Used for mod_mute_user, mod_unmute_user,
+/// mod_deafen_user and mod_undeafen_user
+#[derive(Deserialize, Serialize)]
+pub struct ModMuteOrDeafenUser {
+    pub user_id: i32,
+    pub room_id: i32,
+}
+
+/// Broadcasted to the room whenever recording starts or stops,
+/// also sent to users that need to consent before joining.
+#[derive(Deserialize, Serialize)]
+pub struct RecordingState {
+    pub room_id: i32,
+    pub recording: bool,
+    pub started_by: Option<i32>,
+    pub started_at: Option<i64>, //unix millis
+}
+
+/// Broadcasted to the room(including the kicked user)
+#[derive(Deserialize, Serialize)]
+pub struct UserKicked {
+    pub user_id: i32,
+    pub room_id: i32,
+    pub kicked_by: i32,
+    pub reason: String,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -159,6 +219,11 @@ pub struct RoomUpdate {
     pub chat_throttle: i32,
     pub description: String,
     pub auto_speaker: bool,
+    #[serde(default)]
+    pub speaker_cap: Option<i32>,
+    /// None leaves the room's persistence as is.
+    #[serde(default)]
+    pub persistent: Option<bool>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -217,6 +282,27 @@ pub struct VoiceServerClosePeer {
     pub kicked: bool,
 }
 
+/// Used for start-recording and stop-recording,
+/// the recording id is our room_recording row.
+#[allow(non_snake_case)]
+#[derive(Deserialize, Serialize)]
+pub struct VoiceServerRecording {
+    pub roomId: String,
+    pub recordingId: String,
+}
+
+/// Asks a voice server for every room
+/// and peer it currently has.
+#[derive(Deserialize, Serialize)]
+pub struct VoiceServerListRoomsAndPeers {}
+
+/// Sent to users whose voice server lost track of them,
+/// they need to reconnect their transports.
+#[derive(Deserialize, Serialize)]
+pub struct VoiceReconnectRequired {
+    pub room_id: i32,
+}
+
 #[allow(non_snake_case)]
 #[derive(Deserialize, Serialize, Debug)]
 pub struct GenericRoomIdAndPeerId {
@@ -224,6 +310,15 @@ pub struct GenericRoomIdAndPeerId {
     pub peerId: i32,
 }
 
+/// Boards the leaving user owns are disconnected,
+/// unless they're handed over to someone in the room.
+#[derive(Deserialize, Serialize)]
+pub struct LeaveRoom {
+    pub room_id: i32,
+    #[serde(default)]
+    pub transfer_boards_to: Option<i32>,
+}
+
 #[derive(Deserialize, Serialize)]
 pub struct GenericRoomId {
     pub room_id: i32,
@@ -234,11 +329,12 @@ pub struct GenericUserId {
     pub user_id: i32,
 }
 
+/// Sent to the requester when the voice
+/// server never responded to their request.
 #[derive(Deserialize, Serialize)]
-pub struct VoiceServerRequest<T: Serialize> {
+pub struct VoiceRequestTimedOut {
     pub op: String,
-    pub d: T,
-    pub uid: String,
+    pub room_id: i32,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -318,11 +414,64 @@ pub struct NewIoTServer {
     pub external_id: String,
     pub owner_id: i32,
     pub outside_name: String,
+    pub provider: IntegrationProvider,
+    /// restored from the last time this server
+    /// was connected to the room
+    pub controllers_of_room: Vec<i32>,
+}
+
+/// Broadcasted when a board's owner hands it over
+#[derive(Deserialize, Serialize)]
+pub struct BoardOwnerChanged {
+    pub external_id: String,
+    pub old_owner_id: i32,
+    pub new_owner_id: i32,
+    pub outside_name: String,
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct BlockedFromRoom {
     pub users: Vec<User>,
+    pub blocks: Vec<RoomBlockDetails>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct GetRoomAuditLog {
+    pub room_id: i32,
+    /// defaults to the 100 most recent events
+    #[serde(default)]
+    pub limit: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RoomAuditLog {
+    pub room_id: i32,
+    pub events: Vec<AuditEvent>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct AuditEvent {
+    pub actor_id: i32,
+    pub target_id: Option<i32>,
+    pub action: String,
+    /// json describing the action(if any)
+    pub payload: Option<String>,
+    pub created_at: i64, //unix millis
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct RoomBlockDetails {
+    pub user_id: i32,
+    pub blocked_by: Option<i32>,
+    pub reason: Option<String>,
+    /// unix millis, None means the block is permanent
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct TransferBoardOwnership {
+    pub external_id: String,
+    pub new_owner_id: i32,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -330,6 +479,13 @@ pub struct GiveOrRevokeIot {
     pub external_id: String,
     pub user_id: i32,
     pub now_has_permission: bool,
+    /// Bots/actions the grant is limited to, leaving one
+    /// out means no restriction, an empty list of actions
+    /// makes the user a viewer.
+    #[serde(default)]
+    pub bots: Option<Vec<String>>,
+    #[serde(default)]
+    pub actions: Option<Vec<String>>,
 }
 #[derive(Deserialize, Serialize)]
 pub struct PassiveData {
@@ -341,8 +497,20 @@ pub struct ExistingIotServer {
     pub owner_id: i32,
     pub external_id: String,
     pub controllers_of_room: Vec<i32>,
+    pub controller_scopes: Vec<IoTControllerScope>,
     pub passive_data_snap_shot: Option<String>,
     pub outside_name: String,
+    pub provider: IntegrationProvider,
+    pub status: BoardStatus,
+    pub last_seen: i64,
+}
+
+/// None means the controller isn't restricted
+#[derive(Deserialize, Serialize)]
+pub struct IoTControllerScope {
+    pub user_id: i32,
+    pub bots: Option<Vec<String>>,
+    pub actions: Option<Vec<String>>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -350,6 +518,8 @@ pub struct NewIoTController {
     pub external_id: String,
     pub user_id: i32,
     pub outside_name: String,
+    pub bots: Option<Vec<String>>,
+    pub actions: Option<Vec<String>>,
 }
 
 #[derive(Deserialize, Serialize)]
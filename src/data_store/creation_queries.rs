@@ -51,13 +51,24 @@ pub const USER_BLOCK_TABLE_CREATION: &str = "
     );
 ";
 //ownerRoomId is the room that owns this block.
+//expiresAt is in unix millis, null means the block is permanent.
 pub const ROOM_BLOCK_CREATION: &str = "
     CREATE TABLE IF NOT EXISTS room_block(
         Id SERIAL PRIMARY KEY,
         ownerRoomId int,
-        blockedUserId int
+        blockedUserId int,
+        expiresAt BIGINT,
+        reason VARCHAR(255),
+        blockedBy int
     );
 ";
+//room_block tables created before blocks could expire
+pub const ROOM_BLOCK_EXPIRY_MIGRATION: &str = "
+    ALTER TABLE room_block
+        ADD COLUMN IF NOT EXISTS expiresAt BIGINT,
+        ADD COLUMN IF NOT EXISTS reason VARCHAR(255),
+        ADD COLUMN IF NOT EXISTS blockedBy int;
+";
 pub const SCHEDULED_ROOM_CREATION: &str = "
     CREATE TABLE IF NOT EXISTS scheduled_room(
         Id SERIAL PRIMARY KEY,
@@ -75,3 +86,150 @@ pub const SHEDULED_ROOM_ATTENDANCE: &str = "
         isOwner BOOLEAN NOT NULL
     );
 ";
+//roomId is the room this persisted metadata belongs to,
+//only rooms created as persistent have a row.
+pub const PERSISTENT_ROOM_CREATION: &str = "
+    CREATE TABLE IF NOT EXISTS persistent_room(
+        Id SERIAL PRIMARY KEY,
+        roomId int NOT NULL,
+        name VARCHAR(255),
+        description VARCHAR(255),
+        public BOOLEAN NOT NULL,
+        autoSpeaker BOOLEAN NOT NULL,
+        chatThrottle int NOT NULL,
+        createdAt VARCHAR(255),
+        speakerCap int
+    );
+";
+//persistent_room tables created before speaker caps were persisted
+pub const PERSISTENT_ROOM_SPEAKER_CAP_MIGRATION: &str = "
+    ALTER TABLE persistent_room
+        ADD COLUMN IF NOT EXISTS speakerCap int;
+";
+//instanceId is the merlin instance that owns the room,
+//only that instance holds the room in its state(clustered mode).
+pub const ROOM_INSTANCE_CREATION: &str = "
+    CREATE TABLE IF NOT EXISTS room_instance(
+        Id SERIAL PRIMARY KEY,
+        roomId int NOT NULL,
+        instanceId VARCHAR(255) NOT NULL
+    );
+";
+//lastHeartbeat is in unix millis, rooms owned by instances
+//that stopped sending heartbeats get reclaimed(clustered mode).
+pub const INSTANCE_HEARTBEAT_CREATION: &str = "
+    CREATE TABLE IF NOT EXISTS instance_heartbeat(
+        instanceId VARCHAR(255) PRIMARY KEY,
+        lastHeartbeat BIGINT NOT NULL
+    );
+";
+//audit events are never removed with their room,
+//createdAt is in unix millis.
+pub const AUDIT_EVENT_CREATION: &str = "
+    CREATE TABLE IF NOT EXISTS audit_event(
+        Id SERIAL PRIMARY KEY,
+        roomId int NOT NULL,
+        actorId int NOT NULL,
+        targetId int,
+        action VARCHAR(255) NOT NULL,
+        payload TEXT,
+        createdAt BIGINT NOT NULL
+    );
+";
+//recordings are kept after their room is removed,
+//both times are in unix millis, stoppedAt is null while recording.
+pub const ROOM_RECORDING_CREATION: &str = "
+    CREATE TABLE IF NOT EXISTS room_recording(
+        Id SERIAL PRIMARY KEY,
+        roomId int NOT NULL,
+        startedBy int NOT NULL,
+        startedAt BIGINT NOT NULL,
+        stoppedAt BIGINT
+    );
+";
+//boards are keyed by their room and external server,
+//they're kept when their owner leaves so they can be restored.
+pub const IOT_BOARD_CREATION: &str = "
+    CREATE TABLE IF NOT EXISTS iot_board(
+        Id SERIAL PRIMARY KEY,
+        roomId int NOT NULL,
+        externalServerId VARCHAR(255) NOT NULL,
+        ownerId int NOT NULL,
+        outsideName VARCHAR(255) NOT NULL,
+        provider VARCHAR(255) NOT NULL DEFAULT 'house_of_iot',
+        credentials TEXT,
+        automations TEXT
+    );
+";
+//users the board owner granted control to,
+//bots/actions are json arrays, null means no restriction.
+pub const IOT_BOARD_PERMISSION_CREATION: &str = "
+    CREATE TABLE IF NOT EXISTS iot_board_permission(
+        Id SERIAL PRIMARY KEY,
+        roomId int NOT NULL,
+        externalServerId VARCHAR(255) NOT NULL,
+        userId int NOT NULL,
+        bots TEXT,
+        actions TEXT
+    );
+";
+//every passive data snapshot a board sent, pruned
+//once older than the retention(see board::history).
+pub const IOT_PASSIVE_DATA_CREATION: &str = "
+    CREATE TABLE IF NOT EXISTS iot_passive_data(
+        Id SERIAL PRIMARY KEY,
+        roomId int NOT NULL,
+        externalServerId VARCHAR(255) NOT NULL,
+        data TEXT NOT NULL,
+        capturedAt BIGINT NOT NULL
+    );
+";
+pub const IOT_PASSIVE_DATA_INDEX_CREATION: &str = "
+    CREATE INDEX IF NOT EXISTS iot_passive_data_board_time
+        ON iot_passive_data(roomId, externalServerId, capturedAt);
+";
+//actions requested on a board, requesterId is null for
+//automations. status is pending, responded or timed_out.
+pub const IOT_ACTION_CREATION: &str = "
+    CREATE TABLE IF NOT EXISTS iot_action(
+        Id SERIAL PRIMARY KEY,
+        actionId VARCHAR(255) NOT NULL,
+        roomId int NOT NULL,
+        externalServerId VARCHAR(255) NOT NULL,
+        requesterId int,
+        botName VARCHAR(255) NOT NULL,
+        action VARCHAR(255) NOT NULL,
+        status VARCHAR(255) NOT NULL,
+        response TEXT,
+        requestedAt BIGINT NOT NULL,
+        respondedAt BIGINT
+    );
+";
+//responses are matched by actionId
+pub const IOT_ACTION_INDEX_CREATION: &str = "
+    CREATE INDEX IF NOT EXISTS iot_action_action_id
+        ON iot_action(actionId);
+";
+//iot_board tables created before other providers were supported
+pub const IOT_BOARD_PROVIDER_MIGRATION: &str = "
+    ALTER TABLE iot_board
+        ADD COLUMN IF NOT EXISTS provider VARCHAR(255) NOT NULL DEFAULT 'house_of_iot';
+";
+//iot_board tables created before credentials could be remembered,
+//they're only ever stored encrypted(see integration::credentials).
+pub const IOT_BOARD_CREDENTIALS_MIGRATION: &str = "
+    ALTER TABLE iot_board
+        ADD COLUMN IF NOT EXISTS credentials TEXT;
+";
+//iot_board tables created before automations were saved,
+//the board's schedules/rules as json(see board::automation).
+pub const IOT_BOARD_AUTOMATIONS_MIGRATION: &str = "
+    ALTER TABLE iot_board
+        ADD COLUMN IF NOT EXISTS automations TEXT;
+";
+//iot_board_permission tables created before grants were scoped
+pub const IOT_BOARD_PERMISSION_SCOPE_MIGRATION: &str = "
+    ALTER TABLE iot_board_permission
+        ADD COLUMN IF NOT EXISTS bots TEXT,
+        ADD COLUMN IF NOT EXISTS actions TEXT;
+";
@@ -9,6 +9,69 @@ pub struct DBRoom {
     pub owner_id: i32,
     pub chat_mode: String,
 }
+pub struct DBPersistentRoom {
+    pub id: i32,
+    pub room_id: i32,
+    pub name: String,
+    pub desc: String,
+    pub public: bool,
+    pub auto_speaker: bool,
+    pub chat_throttle: i32,
+    pub created_at: String,
+    pub speaker_cap: Option<i32>,
+}
+pub struct DBAuditEvent {
+    pub id: i32,
+    pub room_id: i32,
+    pub actor_id: i32,
+    pub target_id: Option<i32>,
+    pub action: String,
+    pub payload: Option<String>,
+    pub created_at: i64, //unix millis
+}
+pub struct DBIoTBoard {
+    pub id: i32,
+    pub room_id: i32,
+    pub external_server_id: String,
+    pub owner_id: i32,
+    pub outside_name: String,
+    pub provider: String,
+}
+pub struct DBIoTBoardPermission {
+    pub id: i32,
+    pub room_id: i32,
+    pub external_server_id: String,
+    pub user_id: i32,
+    pub bots: Option<String>,    //json array, none means any bot
+    pub actions: Option<String>, //json array, none means any action
+}
+pub struct DBIoTPassiveData {
+    pub id: i32,
+    pub room_id: i32,
+    pub external_server_id: String,
+    pub data: String,
+    pub captured_at: i64, //unix millis
+}
+pub struct DBIoTAction {
+    pub id: i32,
+    pub action_id: String,
+    pub room_id: i32,
+    pub external_server_id: String,
+    pub requester_id: Option<i32>, //none for automations
+    pub bot_name: String,
+    pub action: String,
+    pub status: String,
+    pub response: Option<String>,
+    pub requested_at: i64,         //unix millis
+    pub responded_at: Option<i64>, //unix millis
+}
+pub struct DBRoomRecording {
+    pub id: i32,
+    pub room_id: i32,
+    pub started_by: i32,
+    pub started_at: i64,         //unix millis
+    pub stopped_at: Option<i64>, //unix millis, none while recording
+}
 pub struct DBRoomPermissions {
     pub id: i32,
     pub user_id: i32,
@@ -47,6 +110,10 @@ pub struct DBRoomBlock {
     pub id: i32,
     pub owner_room_id: i32,
     pub blocked_user_id: i32,
+    /// unix millis, None means the block is permanent
+    pub expires_at: Option<i64>,
+    pub reason: Option<String>,
+    pub blocked_by: Option<i32>,
 }
 pub struct DBScheduledRoom {
     pub id: i32,
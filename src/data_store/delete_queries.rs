@@ -21,6 +21,11 @@ DELETE FROM room_block
 WHERE ownerRoomId = $1 and blockedUserId = $2;
 ";
 
+pub const DELETE_EXPIRED_ROOM_BLOCKS_QUERY: &str = "
+DELETE FROM room_block
+WHERE expiresAt IS NOT NULL AND expiresAt <= $1;
+";
+
 pub const DELETE_USER_BLOCK_QUERY: &str = "
 DELETE FROM user_block
 WHERE ownerUserId = $1 and blockedUserId = $2;
@@ -45,3 +50,90 @@ pub const DELETE_FOLLOWER_FOR_USER: &str = "
 DELETE FROM follower
 WHERE followerId = $1 and userId = $2;
 ";
+
+pub const DELETE_PERSISTENT_ROOM_QUERY: &str = "
+DELETE FROM persistent_room
+WHERE roomId = $1;
+";
+
+pub const DELETE_ROOM_INSTANCE_QUERY: &str = "
+DELETE FROM room_instance
+WHERE roomId = $1;
+";
+
+pub const DELETE_IOT_BOARD_QUERY: &str = "
+DELETE FROM iot_board
+WHERE roomId = $1 AND externalServerId = $2;
+";
+
+pub const DELETE_IOT_BOARD_PERMISSIONS_QUERY: &str = "
+DELETE FROM iot_board_permission
+WHERE roomId = $1 AND externalServerId = $2;
+";
+
+pub const DELETE_IOT_BOARD_PERMISSION_QUERY: &str = "
+DELETE FROM iot_board_permission
+WHERE roomId = $1 AND externalServerId = $2 AND userId = $3;
+";
+
+pub const DELETE_ROOM_IOT_BOARDS_QUERY: &str = "
+DELETE FROM iot_board
+WHERE roomId = $1;
+";
+
+pub const DELETE_ROOM_IOT_BOARD_PERMISSIONS_QUERY: &str = "
+DELETE FROM iot_board_permission
+WHERE roomId = $1;
+";
+
+pub const DELETE_IOT_PASSIVE_DATA_QUERY: &str = "
+DELETE FROM iot_passive_data
+WHERE roomId = $1 AND externalServerId = $2;
+";
+
+pub const DELETE_ROOM_IOT_PASSIVE_DATA_QUERY: &str = "
+DELETE FROM iot_passive_data
+WHERE roomId = $1;
+";
+
+pub const DELETE_IOT_ACTIONS_QUERY: &str = "
+DELETE FROM iot_action
+WHERE roomId = $1 AND externalServerId = $2;
+";
+
+pub const DELETE_ROOM_IOT_ACTIONS_QUERY: &str = "
+DELETE FROM iot_action
+WHERE roomId = $1;
+";
+
+pub const DELETE_EXPIRED_IOT_PASSIVE_DATA_QUERY: &str = "
+DELETE FROM iot_passive_data
+WHERE capturedAt < $1;
+";
+
+pub const DELETE_EXPIRED_IOT_ACTIONS_QUERY: &str = "
+DELETE FROM iot_action
+WHERE requestedAt < $1;
+";
+
+//Orphaned rows belong to rooms that no longer exist,
+//these are left behind when the server goes down unexpectedly.
+pub const DELETE_ORPHANED_ROOM_PERMISSIONS_QUERY: &str = "
+DELETE FROM room_permission
+WHERE roomId NOT IN (SELECT Id FROM room);
+";
+
+pub const DELETE_ORPHANED_ROOM_BLOCKS_QUERY: &str = "
+DELETE FROM room_block
+WHERE ownerRoomId NOT IN (SELECT Id FROM room);
+";
+
+pub const DELETE_ORPHANED_PERSISTENT_ROOMS_QUERY: &str = "
+DELETE FROM persistent_room
+WHERE roomId NOT IN (SELECT Id FROM room);
+";
+
+pub const DELETE_ORPHANED_ROOM_INSTANCES_QUERY: &str = "
+DELETE FROM room_instance
+WHERE roomId NOT IN (SELECT Id FROM room);
+";
@@ -24,6 +24,8 @@ INSERT INTO room(ownerId,chatMode)
 VALUES($1,$2) RETURNING Id;
 ";
 
+// rows are kept after leaving, so a user
+// never gets a second row for the same room
 pub const INSERT_ROOM_PERMISSION_QUERY: &str = "
 INSERT INTO room_permission(
     userId,
@@ -31,7 +33,11 @@ INSERT INTO room_permission(
     isMod,
     isSpeaker,
     askedToSpeak)
-VALUES($1,$2,$3,$4,$5);
+SELECT $1::INT,$2::INT,$3::BOOLEAN,$4::BOOLEAN,$5::BOOLEAN
+WHERE NOT EXISTS(
+    SELECT 1 FROM room_permission
+    WHERE userId = $1 AND roomId = $2
+);
 ";
 
 pub const INSERT_FOLLOWER_QUERY: &str = "
@@ -45,8 +51,8 @@ VALUES($1,$2);
 ";
 
 pub const INSERT_ROOM_BLOCK_QUERY: &str = "
-INSERT INTO room_block(ownerRoomId,blockedUserId)
-VALUES($1,$2);
+INSERT INTO room_block(ownerRoomId,blockedUserId,expiresAt,reason,blockedBy)
+VALUES($1,$2,$3,$4,$5);
 ";
 
 pub const INSERT_SCHEDULED_ROOM_QUERY: &str = "
@@ -62,3 +68,57 @@ pub const INSERT_SCHEDULED_ATTENDANCE_QUERY: &str = "
 INSERT INTO scheduled_room_attendance(userId,scheduledRoomId,isOwner)
 VALUES($1,$2,$3);
 ";
+
+pub const INSERT_PERSISTENT_ROOM_QUERY: &str = "
+INSERT INTO persistent_room(
+    roomId,
+    name,
+    description,
+    public,
+    autoSpeaker,
+    chatThrottle,
+    createdAt,
+    speakerCap)
+VALUES($1,$2,$3,$4,$5,$6,$7,$8);
+";
+
+pub const INSERT_ROOM_INSTANCE_QUERY: &str = "
+INSERT INTO room_instance(roomId,instanceId)
+VALUES($1,$2);
+";
+
+pub const UPSERT_INSTANCE_HEARTBEAT_QUERY: &str = "
+INSERT INTO instance_heartbeat(instanceId,lastHeartbeat)
+VALUES($1,$2)
+ON CONFLICT (instanceId) DO UPDATE SET lastHeartbeat = $2;
+";
+
+pub const INSERT_AUDIT_EVENT_QUERY: &str = "
+INSERT INTO audit_event(roomId,actorId,targetId,action,payload,createdAt)
+VALUES($1,$2,$3,$4,$5,$6);
+";
+
+pub const INSERT_ROOM_RECORDING_QUERY: &str = "
+INSERT INTO room_recording(roomId,startedBy,startedAt)
+VALUES($1,$2,$3) RETURNING Id;
+";
+
+pub const INSERT_IOT_BOARD_QUERY: &str = "
+INSERT INTO iot_board(roomId,externalServerId,ownerId,outsideName,provider)
+VALUES($1,$2,$3,$4,$5);
+";
+
+pub const INSERT_IOT_PASSIVE_DATA_QUERY: &str = "
+INSERT INTO iot_passive_data(roomId,externalServerId,data,capturedAt)
+VALUES($1,$2,$3,$4);
+";
+
+pub const INSERT_IOT_ACTION_QUERY: &str = "
+INSERT INTO iot_action(actionId,roomId,externalServerId,requesterId,botName,action,status,requestedAt)
+VALUES($1,$2,$3,$4,$5,$6,$7,$8);
+";
+
+pub const INSERT_IOT_BOARD_PERMISSION_QUERY: &str = "
+INSERT INTO iot_board_permission(roomId,externalServerId,userId,bots,actions)
+VALUES($1,$2,$3,$4,$5);
+";
@@ -8,6 +8,11 @@ SELECT * FROM room
 WHERE Id = $1;
 ";
 
+pub const SELECT_PERSISTENT_ROOM_BY_ROOM_ID: &str = "
+SELECT * FROM persistent_room
+WHERE roomId = $1;
+";
+
 pub const SELECT_SCHEDULED_ROOM_BY_ID: &str = "
 SELECT * FROM scheduled_room
 WHERE Id = $1;
@@ -59,7 +64,8 @@ WHERE ownerUserId = $1 AND blockedUserId = $2;
 
 pub const SELECT_SINGLE_ROOM_BLOCK_FOR_USER_QUERY: &str = "
 SELECT * FROM room_block 
-WHERE ownerRoomId = $1 AND blockedUserId = $2;
+WHERE ownerRoomId = $1 AND blockedUserId = $2
+AND (expiresAt IS NULL OR expiresAt > EXTRACT(EPOCH FROM NOW()) * 1000);
 ";
 
 pub const SELECT_ALL_BLOCKED_FOR_USER_QUERY: &str = "
@@ -73,7 +79,8 @@ WHERE blockedUserId = $1;
 ";
 pub const SELECT_ALL_BLOCKED_USERS_FOR_ROOM_QUERY: &str = "
 SELECT * FROM room_block
-WHERE ownerRoomId = $1;
+WHERE ownerRoomId = $1
+AND (expiresAt IS NULL OR expiresAt > EXTRACT(EPOCH FROM NOW()) * 1000);
 ";
 
 pub const SELECT_ALL_ROOM_PERMISSIONS_FOR_USER: &str = "
@@ -107,3 +114,82 @@ pub const SELECT_USER_BY_CREATION_IDENTIFIERS: &str = "
 SELECT * FROM users
 WHERE githubId = $1 AND discordId = $2;
 ";
+
+pub const SELECT_ALL_PERSISTENT_ROOMS_QUERY: &str = "
+SELECT * FROM persistent_room;
+";
+
+pub const SELECT_ALL_ROOM_INSTANCES_QUERY: &str = "
+SELECT * FROM room_instance;
+";
+
+pub const SELECT_ROOM_INSTANCE_BY_ROOM_ID: &str = "
+SELECT * FROM room_instance
+WHERE roomId = $1;
+";
+
+//instances that never sent a heartbeat are from before heartbeats existed
+pub const SELECT_EXPIRED_ROOM_INSTANCES_QUERY: &str = "
+SELECT room_instance.roomId, room_instance.instanceId FROM room_instance
+LEFT JOIN instance_heartbeat ON room_instance.instanceId = instance_heartbeat.instanceId
+WHERE room_instance.instanceId != $1
+AND (instance_heartbeat.lastHeartbeat IS NULL OR instance_heartbeat.lastHeartbeat < $2);
+";
+
+pub const SELECT_AUDIT_EVENTS_FOR_ROOM_QUERY: &str = "
+SELECT * FROM audit_event
+WHERE roomId = $1
+ORDER BY createdAt DESC
+LIMIT $2;
+";
+
+pub const SELECT_AUDIT_EVENTS_FOR_ROOM_BY_ACTION_QUERY: &str = "
+SELECT * FROM audit_event
+WHERE roomId = $1 AND action = $2;
+";
+
+pub const SELECT_IOT_BOARD_QUERY: &str = "
+SELECT * FROM iot_board
+WHERE roomId = $1 AND externalServerId = $2;
+";
+
+pub const SELECT_IOT_BOARDS_FOR_ROOM_QUERY: &str = "
+SELECT * FROM iot_board
+WHERE roomId = $1;
+";
+
+pub const SELECT_IOT_BOARD_PERMISSION_QUERY: &str = "
+SELECT * FROM iot_board_permission
+WHERE roomId = $1 AND externalServerId = $2 AND userId = $3;
+";
+
+pub const SELECT_IOT_BOARD_PERMISSIONS_FOR_ROOM_QUERY: &str = "
+SELECT * FROM iot_board_permission
+WHERE roomId = $1;
+";
+
+//$5 is the bucket size in millis, only the
+//latest sample of each bucket is selected.
+//Newest first so the limit drops the oldest buckets.
+pub const SELECT_IOT_PASSIVE_DATA_IN_RANGE_QUERY: &str = "
+SELECT * FROM (
+    SELECT DISTINCT ON ((capturedAt - $3) / $5) * FROM iot_passive_data
+    WHERE roomId = $1 AND externalServerId = $2 AND capturedAt BETWEEN $3 AND $4
+    ORDER BY (capturedAt - $3) / $5, capturedAt DESC
+) AS bucketed
+ORDER BY capturedAt DESC
+LIMIT $6;
+";
+
+pub const SELECT_IOT_ACTIONS_FOR_BOARD_QUERY: &str = "
+SELECT * FROM iot_action
+WHERE roomId = $1 AND externalServerId = $2
+ORDER BY requestedAt DESC
+LIMIT $3;
+";
+
+pub const SELECT_ROOM_RECORDINGS_FOR_ROOM_QUERY: &str = "
+SELECT * FROM room_recording
+WHERE roomId = $1
+ORDER BY startedAt DESC;
+";
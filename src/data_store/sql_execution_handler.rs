@@ -1,6 +1,7 @@
 use crate::data_store::db_models::{
-    DBFollower, DBRoom, DBRoomBlock, DBRoomPermissions, DBScheduledRoom, DBScheduledRoomAttendance,
-    DBUser, DBUserBlock,
+    DBAuditEvent, DBFollower, DBIoTAction, DBIoTBoard, DBIoTBoardPermission, DBIoTPassiveData,
+    DBPersistentRoom, DBRoom, DBRoomBlock, DBRoomPermissions, DBRoomRecording, DBScheduledRoom,
+    DBScheduledRoomAttendance, DBUser, DBUserBlock,
 };
 
 use crate::communication::types::BaseUser;
@@ -46,6 +47,40 @@ impl ExecutionHandler {
             .await?;
         self.create_table_if_needed(creation_queries::SHEDULED_ROOM_ATTENDANCE)
             .await?;
+        self.create_table_if_needed(creation_queries::PERSISTENT_ROOM_CREATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::PERSISTENT_ROOM_SPEAKER_CAP_MIGRATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::ROOM_INSTANCE_CREATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::INSTANCE_HEARTBEAT_CREATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::ROOM_BLOCK_EXPIRY_MIGRATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::AUDIT_EVENT_CREATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::ROOM_RECORDING_CREATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::IOT_BOARD_CREATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::IOT_BOARD_PROVIDER_MIGRATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::IOT_BOARD_CREDENTIALS_MIGRATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::IOT_BOARD_AUTOMATIONS_MIGRATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::IOT_BOARD_PERMISSION_CREATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::IOT_BOARD_PERMISSION_SCOPE_MIGRATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::IOT_PASSIVE_DATA_CREATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::IOT_PASSIVE_DATA_INDEX_CREATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::IOT_ACTION_CREATION)
+            .await?;
+        self.create_table_if_needed(creation_queries::IOT_ACTION_INDEX_CREATION)
+            .await?;
         return Ok(());
     }
 
@@ -87,6 +122,165 @@ impl ExecutionHandler {
         return Ok(room_id);
     }
 
+    pub async fn insert_persistent_room(
+        &mut self,
+        persistent_room: &DBPersistentRoom,
+    ) -> Result<(), Error> {
+        let query = insert_queries::INSERT_PERSISTENT_ROOM_QUERY;
+        self.client
+            .query(
+                query,
+                &[
+                    &persistent_room.room_id,
+                    &persistent_room.name,
+                    &persistent_room.desc,
+                    &persistent_room.public,
+                    &persistent_room.auto_speaker,
+                    &persistent_room.chat_throttle,
+                    &persistent_room.created_at,
+                    &persistent_room.speaker_cap,
+                ],
+            )
+            .await?;
+        return Ok(());
+    }
+
+    pub async fn insert_room_instance(
+        &mut self,
+        room_id: &i32,
+        instance_id: &String,
+    ) -> Result<(), Error> {
+        let query = insert_queries::INSERT_ROOM_INSTANCE_QUERY;
+        self.client.query(query, &[room_id, instance_id]).await?;
+        return Ok(());
+    }
+
+    pub async fn upsert_instance_heartbeat(
+        &mut self,
+        instance_id: &String,
+        last_heartbeat: &i64,
+    ) -> Result<(), Error> {
+        let query = insert_queries::UPSERT_INSTANCE_HEARTBEAT_QUERY;
+        self.client
+            .query(query, &[instance_id, last_heartbeat])
+            .await?;
+        return Ok(());
+    }
+
+    pub async fn insert_audit_event(&mut self, audit_event: &DBAuditEvent) -> Result<(), Error> {
+        let query = insert_queries::INSERT_AUDIT_EVENT_QUERY;
+        self.client
+            .query(
+                query,
+                &[
+                    &audit_event.room_id,
+                    &audit_event.actor_id,
+                    &audit_event.target_id,
+                    &audit_event.action,
+                    &audit_event.payload,
+                    &audit_event.created_at,
+                ],
+            )
+            .await?;
+        return Ok(());
+    }
+
+    pub async fn insert_room_recording(
+        &mut self,
+        recording: &DBRoomRecording,
+    ) -> Result<i32, Error> {
+        let query = insert_queries::INSERT_ROOM_RECORDING_QUERY;
+        let rows = self
+            .client
+            .query(
+                query,
+                &[
+                    &recording.room_id,
+                    &recording.started_by,
+                    &recording.started_at,
+                ],
+            )
+            .await?;
+        let id: i32 = rows[0].get(0);
+        return Ok(id);
+    }
+
+    pub async fn insert_iot_board(&mut self, board: &DBIoTBoard) -> Result<(), Error> {
+        let query = insert_queries::INSERT_IOT_BOARD_QUERY;
+        self.client
+            .query(
+                query,
+                &[
+                    &board.room_id,
+                    &board.external_server_id,
+                    &board.owner_id,
+                    &board.outside_name,
+                    &board.provider,
+                ],
+            )
+            .await?;
+        return Ok(());
+    }
+
+    pub async fn insert_iot_passive_data(
+        &mut self,
+        passive_data: &DBIoTPassiveData,
+    ) -> Result<(), Error> {
+        let query = insert_queries::INSERT_IOT_PASSIVE_DATA_QUERY;
+        self.client
+            .query(
+                query,
+                &[
+                    &passive_data.room_id,
+                    &passive_data.external_server_id,
+                    &passive_data.data,
+                    &passive_data.captured_at,
+                ],
+            )
+            .await?;
+        return Ok(());
+    }
+
+    pub async fn insert_iot_action(&mut self, action: &DBIoTAction) -> Result<(), Error> {
+        let query = insert_queries::INSERT_IOT_ACTION_QUERY;
+        self.client
+            .query(
+                query,
+                &[
+                    &action.action_id,
+                    &action.room_id,
+                    &action.external_server_id,
+                    &action.requester_id,
+                    &action.bot_name,
+                    &action.action,
+                    &action.status,
+                    &action.requested_at,
+                ],
+            )
+            .await?;
+        return Ok(());
+    }
+
+    pub async fn insert_iot_board_permission(
+        &mut self,
+        permission: &DBIoTBoardPermission,
+    ) -> Result<(), Error> {
+        let query = insert_queries::INSERT_IOT_BOARD_PERMISSION_QUERY;
+        self.client
+            .query(
+                query,
+                &[
+                    &permission.room_id,
+                    &permission.external_server_id,
+                    &permission.user_id,
+                    &permission.bots,
+                    &permission.actions,
+                ],
+            )
+            .await?;
+        return Ok(());
+    }
+
     pub async fn insert_room_permission(
         &mut self,
         permissions: &DBRoomPermissions,
@@ -131,7 +325,13 @@ impl ExecutionHandler {
         self.client
             .query(
                 query,
-                &[&room_block.owner_room_id, &room_block.blocked_user_id],
+                &[
+                    &room_block.owner_room_id,
+                    &room_block.blocked_user_id,
+                    &room_block.expires_at,
+                    &room_block.reason,
+                    &room_block.blocked_by,
+                ],
             )
             .await?;
         return Ok(());
@@ -195,6 +395,153 @@ impl ExecutionHandler {
         return Ok(num_modified);
     }
 
+    pub async fn delete_persistent_room(&mut self, room_id: &i32) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_PERSISTENT_ROOM_QUERY;
+        let num_modified = self.client.execute(query, &[room_id]).await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_room_instance(&mut self, room_id: &i32) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_ROOM_INSTANCE_QUERY;
+        let num_modified = self.client.execute(query, &[room_id]).await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_iot_board(
+        &mut self,
+        room_id: &i32,
+        external_server_id: &String,
+    ) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_IOT_BOARD_QUERY;
+        let num_modified = self
+            .client
+            .execute(query, &[room_id, external_server_id])
+            .await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_iot_board_permissions(
+        &mut self,
+        room_id: &i32,
+        external_server_id: &String,
+    ) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_IOT_BOARD_PERMISSIONS_QUERY;
+        let num_modified = self
+            .client
+            .execute(query, &[room_id, external_server_id])
+            .await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_iot_board_permission(
+        &mut self,
+        room_id: &i32,
+        external_server_id: &String,
+        user_id: &i32,
+    ) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_IOT_BOARD_PERMISSION_QUERY;
+        let num_modified = self
+            .client
+            .execute(query, &[room_id, external_server_id, user_id])
+            .await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_room_iot_boards(&mut self, room_id: &i32) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_ROOM_IOT_BOARDS_QUERY;
+        let num_modified = self.client.execute(query, &[room_id]).await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_room_iot_board_permissions(&mut self, room_id: &i32) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_ROOM_IOT_BOARD_PERMISSIONS_QUERY;
+        let num_modified = self.client.execute(query, &[room_id]).await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_iot_passive_data(
+        &mut self,
+        room_id: &i32,
+        external_server_id: &String,
+    ) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_IOT_PASSIVE_DATA_QUERY;
+        let num_modified = self
+            .client
+            .execute(query, &[room_id, external_server_id])
+            .await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_iot_actions(
+        &mut self,
+        room_id: &i32,
+        external_server_id: &String,
+    ) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_IOT_ACTIONS_QUERY;
+        let num_modified = self
+            .client
+            .execute(query, &[room_id, external_server_id])
+            .await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_room_iot_actions(&mut self, room_id: &i32) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_ROOM_IOT_ACTIONS_QUERY;
+        let num_modified = self.client.execute(query, &[room_id]).await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_room_iot_passive_data(&mut self, room_id: &i32) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_ROOM_IOT_PASSIVE_DATA_QUERY;
+        let num_modified = self.client.execute(query, &[room_id]).await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_expired_iot_passive_data(
+        &mut self,
+        oldest_kept: &i64,
+    ) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_EXPIRED_IOT_PASSIVE_DATA_QUERY;
+        let num_modified = self.client.execute(query, &[oldest_kept]).await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_expired_iot_actions(&mut self, oldest_kept: &i64) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_EXPIRED_IOT_ACTIONS_QUERY;
+        let num_modified = self.client.execute(query, &[oldest_kept]).await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_orphaned_room_permissions(&mut self) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_ORPHANED_ROOM_PERMISSIONS_QUERY;
+        let num_modified = self.client.execute(query, &[]).await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_orphaned_room_blocks(&mut self) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_ORPHANED_ROOM_BLOCKS_QUERY;
+        let num_modified = self.client.execute(query, &[]).await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_orphaned_persistent_rooms(&mut self) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_ORPHANED_PERSISTENT_ROOMS_QUERY;
+        let num_modified = self.client.execute(query, &[]).await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_orphaned_room_instances(&mut self) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_ORPHANED_ROOM_INSTANCES_QUERY;
+        let num_modified = self.client.execute(query, &[]).await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn delete_expired_room_blocks(&mut self, now: &i64) -> Result<u64, Error> {
+        let query = delete_queries::DELETE_EXPIRED_ROOM_BLOCKS_QUERY;
+        let num_modified = self.client.execute(query, &[now]).await?;
+        return Ok(num_modified);
+    }
+
     pub async fn delete_room_block_for_user(
         &mut self,
         room_id: &i32,
@@ -294,6 +641,135 @@ impl ExecutionHandler {
         return Ok(num_modified);
     }
 
+    pub async fn update_open_room_recordings_stopped_at(
+        &mut self,
+        room_id: &i32,
+        stopped_at: &i64,
+    ) -> Result<u64, Error> {
+        let query = update_queries::UPDATE_OPEN_ROOM_RECORDINGS_STOPPED_AT_QUERY;
+        let num_modified = self.client.execute(query, &[stopped_at, room_id]).await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn update_iot_action_result(
+        &mut self,
+        action_id: &String,
+        status: &String,
+        response: &Option<String>,
+        responded_at: &i64,
+    ) -> Result<u64, Error> {
+        let query = update_queries::UPDATE_IOT_ACTION_RESULT_QUERY;
+        let num_modified = self
+            .client
+            .execute(query, &[status, response, responded_at, action_id])
+            .await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn update_iot_board(&mut self, board: &DBIoTBoard) -> Result<u64, Error> {
+        let query = update_queries::UPDATE_IOT_BOARD_QUERY;
+        let num_modified = self
+            .client
+            .execute(
+                query,
+                &[
+                    &board.owner_id,
+                    &board.outside_name,
+                    &board.provider,
+                    &board.room_id,
+                    &board.external_server_id,
+                ],
+            )
+            .await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn update_iot_board_credentials(
+        &mut self,
+        room_id: &i32,
+        external_server_id: &String,
+        credentials: &Option<String>,
+    ) -> Result<u64, Error> {
+        let query = update_queries::UPDATE_IOT_BOARD_CREDENTIALS_QUERY;
+        let num_modified = self
+            .client
+            .execute(query, &[credentials, room_id, external_server_id])
+            .await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn update_iot_board_automations(
+        &mut self,
+        room_id: &i32,
+        external_server_id: &String,
+        automations: &String,
+    ) -> Result<u64, Error> {
+        let query = update_queries::UPDATE_IOT_BOARD_AUTOMATIONS_QUERY;
+        let num_modified = self
+            .client
+            .execute(query, &[automations, room_id, external_server_id])
+            .await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn update_iot_board_permission(
+        &mut self,
+        permission: &DBIoTBoardPermission,
+    ) -> Result<u64, Error> {
+        let query = update_queries::UPDATE_IOT_BOARD_PERMISSION_QUERY;
+        let num_modified = self
+            .client
+            .execute(
+                query,
+                &[
+                    &permission.bots,
+                    &permission.actions,
+                    &permission.room_id,
+                    &permission.external_server_id,
+                    &permission.user_id,
+                ],
+            )
+            .await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn update_room_instance_owner(
+        &mut self,
+        room_id: &i32,
+        instance_id: &String,
+        previous_instance_id: &String,
+    ) -> Result<u64, Error> {
+        let query = update_queries::UPDATE_ROOM_INSTANCE_OWNER_QUERY;
+        let num_modified = self
+            .client
+            .execute(query, &[instance_id, room_id, previous_instance_id])
+            .await?;
+        return Ok(num_modified);
+    }
+
+    pub async fn update_persistent_room(
+        &mut self,
+        persistent_room: &DBPersistentRoom,
+    ) -> Result<u64, Error> {
+        let query = update_queries::UPDATE_PERSISTENT_ROOM_QUERY;
+        let num_modified = self
+            .client
+            .execute(
+                query,
+                &[
+                    &persistent_room.name,
+                    &persistent_room.desc,
+                    &persistent_room.public,
+                    &persistent_room.auto_speaker,
+                    &persistent_room.chat_throttle,
+                    &persistent_room.speaker_cap,
+                    &persistent_room.room_id,
+                ],
+            )
+            .await?;
+        return Ok(num_modified);
+    }
+
     pub async fn update_entire_room_permissions(
         &mut self,
         room_permission: &DBRoomPermissions,
@@ -506,6 +982,159 @@ impl ExecutionHandler {
         return Ok(result);
     }
 
+    pub async fn select_all_persistent_rooms(&mut self) -> Result<Vec<Row>, Error> {
+        let query = select_queries::SELECT_ALL_PERSISTENT_ROOMS_QUERY;
+        let result: Vec<Row> = self.client.query(query, &[]).await?;
+        return Ok(result);
+    }
+
+    pub async fn select_persistent_room_by_room_id(
+        &mut self,
+        room_id: &i32,
+    ) -> Result<Vec<Row>, Error> {
+        let query = select_queries::SELECT_PERSISTENT_ROOM_BY_ROOM_ID;
+        let result: Vec<Row> = self.client.query(query, &[room_id]).await?;
+        return Ok(result);
+    }
+
+    pub async fn select_all_room_instances(&mut self) -> Result<Vec<Row>, Error> {
+        let query = select_queries::SELECT_ALL_ROOM_INSTANCES_QUERY;
+        let result: Vec<Row> = self.client.query(query, &[]).await?;
+        return Ok(result);
+    }
+
+    pub async fn select_room_instance_by_room_id(
+        &mut self,
+        room_id: &i32,
+    ) -> Result<Vec<Row>, Error> {
+        let query = select_queries::SELECT_ROOM_INSTANCE_BY_ROOM_ID;
+        let result: Vec<Row> = self.client.query(query, &[room_id]).await?;
+        return Ok(result);
+    }
+
+    pub async fn select_expired_room_instances(
+        &mut self,
+        instance_id: &String,
+        expired_before: &i64,
+    ) -> Result<Vec<Row>, Error> {
+        let query = select_queries::SELECT_EXPIRED_ROOM_INSTANCES_QUERY;
+        let result: Vec<Row> = self
+            .client
+            .query(query, &[instance_id, expired_before])
+            .await?;
+        return Ok(result);
+    }
+
+    pub async fn select_audit_events_for_room(
+        &mut self,
+        room_id: &i32,
+        limit: &i64,
+    ) -> Result<Vec<Row>, Error> {
+        let query = select_queries::SELECT_AUDIT_EVENTS_FOR_ROOM_QUERY;
+        let result: Vec<Row> = self.client.query(query, &[room_id, limit]).await?;
+        return Ok(result);
+    }
+
+    pub async fn select_audit_events_for_room_by_action(
+        &mut self,
+        room_id: &i32,
+        action: &String,
+    ) -> Result<Vec<Row>, Error> {
+        let query = select_queries::SELECT_AUDIT_EVENTS_FOR_ROOM_BY_ACTION_QUERY;
+        let result: Vec<Row> = self.client.query(query, &[room_id, action]).await?;
+        return Ok(result);
+    }
+
+    pub async fn select_iot_board(
+        &mut self,
+        room_id: &i32,
+        external_server_id: &String,
+    ) -> Result<Vec<Row>, Error> {
+        let query = select_queries::SELECT_IOT_BOARD_QUERY;
+        let result: Vec<Row> = self
+            .client
+            .query(query, &[room_id, external_server_id])
+            .await?;
+        return Ok(result);
+    }
+
+    pub async fn select_iot_boards_for_room(&mut self, room_id: &i32) -> Result<Vec<Row>, Error> {
+        let query = select_queries::SELECT_IOT_BOARDS_FOR_ROOM_QUERY;
+        let result: Vec<Row> = self.client.query(query, &[room_id]).await?;
+        return Ok(result);
+    }
+
+    pub async fn select_iot_board_permission(
+        &mut self,
+        permission: &DBIoTBoardPermission,
+    ) -> Result<Vec<Row>, Error> {
+        let query = select_queries::SELECT_IOT_BOARD_PERMISSION_QUERY;
+        let result: Vec<Row> = self
+            .client
+            .query(
+                query,
+                &[
+                    &permission.room_id,
+                    &permission.external_server_id,
+                    &permission.user_id,
+                ],
+            )
+            .await?;
+        return Ok(result);
+    }
+
+    pub async fn select_iot_board_permissions_for_room(
+        &mut self,
+        room_id: &i32,
+    ) -> Result<Vec<Row>, Error> {
+        let query = select_queries::SELECT_IOT_BOARD_PERMISSIONS_FOR_ROOM_QUERY;
+        let result: Vec<Row> = self.client.query(query, &[room_id]).await?;
+        return Ok(result);
+    }
+
+    pub async fn select_iot_passive_data_in_range(
+        &mut self,
+        room_id: &i32,
+        external_server_id: &String,
+        from: &i64,
+        to: &i64,
+        bucket_ms: &i64,
+        limit: &i64,
+    ) -> Result<Vec<Row>, Error> {
+        let query = select_queries::SELECT_IOT_PASSIVE_DATA_IN_RANGE_QUERY;
+        let result: Vec<Row> = self
+            .client
+            .query(
+                query,
+                &[room_id, external_server_id, from, to, bucket_ms, limit],
+            )
+            .await?;
+        return Ok(result);
+    }
+
+    pub async fn select_iot_actions_for_board(
+        &mut self,
+        room_id: &i32,
+        external_server_id: &String,
+        limit: &i64,
+    ) -> Result<Vec<Row>, Error> {
+        let query = select_queries::SELECT_IOT_ACTIONS_FOR_BOARD_QUERY;
+        let result: Vec<Row> = self
+            .client
+            .query(query, &[room_id, external_server_id, limit])
+            .await?;
+        return Ok(result);
+    }
+
+    pub async fn select_room_recordings_for_room(
+        &mut self,
+        room_id: &i32,
+    ) -> Result<Vec<Row>, Error> {
+        let query = select_queries::SELECT_ROOM_RECORDINGS_FOR_ROOM_QUERY;
+        let result: Vec<Row> = self.client.query(query, &[room_id]).await?;
+        return Ok(result);
+    }
+
     pub async fn select_scheduled_room_by_id(&mut self, room_id: &i32) -> Result<Vec<Row>, Error> {
         let query = select_queries::SELECT_SCHEDULED_ROOM_BY_ID;
         let result: Vec<Row> = self.client.query(query, &[room_id]).await?;
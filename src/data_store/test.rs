@@ -8,6 +8,15 @@ pub async fn test() {
     test_room(&mut execution_handler).await;
     test_follower(&mut execution_handler).await;
     test_blocks(&mut execution_handler).await;
+    test_board(&mut execution_handler).await;
+}
+
+async fn test_board(execution_handler: &mut ExecutionHandler) {
+    tests::board::test_iot_board_insert_update_and_gather(execution_handler).await;
+    tests::board::test_iot_board_permission_insert_gather_and_delete(execution_handler).await;
+    tests::board::test_iot_passive_data_history(execution_handler).await;
+    tests::board::test_iot_action_insert_result_and_gather(execution_handler).await;
+    tests::board::test_delete_iot_board(execution_handler).await;
 }
 
 async fn test_blocks(execution_handler: &mut ExecutionHandler) {
@@ -18,6 +27,7 @@ async fn test_blocks(execution_handler: &mut ExecutionHandler) {
     tests::blocks::test_single_room_block_gather(execution_handler).await;
     tests::blocks::test_remove_user_block(execution_handler).await;
     tests::blocks::test_remove_room_block(execution_handler).await;
+    tests::blocks::test_expired_room_blocks(execution_handler).await;
 }
 
 async fn test_follower(execution_handler: &mut ExecutionHandler) {
@@ -33,6 +43,16 @@ async fn test_room(execution_handler: &mut ExecutionHandler) {
     //live-non scheduled
     tests::room::test_update_room_owner(execution_handler, room_id.clone()).await;
     tests::room::test_delete_room(execution_handler, room_id.clone()).await;
+    //persistent
+    tests::room::test_persistent_room_insert_and_gather(execution_handler).await;
+    tests::room::test_update_persistent_room(execution_handler).await;
+    tests::room::test_delete_persistent_room(execution_handler).await;
+    //clustered mode
+    tests::room::test_room_instance_insert_gather_and_delete(execution_handler).await;
+    //audit log
+    tests::room::test_audit_event_insert_and_gather(execution_handler).await;
+    //recordings
+    tests::room::test_room_recording_insert_stop_and_gather(execution_handler).await;
     //scheduled
     tests::room::test_update_scheduled_room_num_attending(execution_handler, sch_room_id.clone())
         .await;
@@ -47,6 +67,7 @@ async fn test_room(execution_handler: &mut ExecutionHandler) {
     tests::room::test_room_permission_insert_and_gather(execution_handler).await;
     tests::room::test_update_room_permission_for_user(execution_handler).await;
     tests::room::test_delete_room_permissions(execution_handler).await;
+    tests::room::test_delete_orphaned_room_permissions(execution_handler).await;
 }
 
 async fn test_users(execution_handler: &mut ExecutionHandler) {
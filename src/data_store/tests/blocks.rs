@@ -92,6 +92,26 @@ pub async fn test_remove_room_block(execution_handler: &mut ExecutionHandler) {
     assert_eq!(rows_affected, 1);
 }
 
+pub async fn test_expired_room_blocks(execution_handler: &mut ExecutionHandler) {
+    println!("testing expired room blocks");
+    let mut room_block: DBRoomBlock = gather_room_block();
+    room_block.owner_room_id = 23;
+    room_block.expires_at = Some(1000);
+    room_block.reason = Some("spam".to_owned());
+    room_block.blocked_by = Some(22);
+    execution_handler
+        .insert_room_block(&room_block)
+        .await
+        .unwrap();
+    //expired blocks are ignored when gathering
+    let gather_result = execution_handler
+        .select_all_blocked_users_for_room(&room_block.owner_room_id)
+        .await;
+    assert_eq!(gather_result.unwrap().len(), 0);
+    let remove_result = execution_handler.delete_expired_room_blocks(&2000).await;
+    assert!(remove_result.unwrap() >= 1);
+}
+
 fn gather_user_block() -> DBUserBlock {
     return DBUserBlock {
         id: 0,
@@ -105,5 +125,8 @@ fn gather_room_block() -> DBRoomBlock {
         id: 0,
         owner_room_id: 22,
         blocked_user_id: 33,
+        expires_at: None,
+        reason: None,
+        blocked_by: None,
     };
 }
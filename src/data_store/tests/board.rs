@@ -0,0 +1,269 @@
+use crate::data_store::db_models::{
+    DBIoTAction, DBIoTBoard, DBIoTBoardPermission, DBIoTPassiveData,
+};
+use crate::data_store::sql_execution_handler::ExecutionHandler;
+
+pub async fn test_iot_board_insert_update_and_gather(execution_handler: &mut ExecutionHandler) {
+    println!("testing iot board insert, update and gather");
+    let mut board = gather_db_board();
+    execution_handler.insert_iot_board(&board).await.unwrap();
+    board.owner_id = 33;
+    board.provider = "mqtt".to_owned();
+    let num_modified = execution_handler.update_iot_board(&board).await.unwrap();
+    assert_eq!(num_modified, 1);
+    let selected_rows = execution_handler
+        .select_iot_boards_for_room(&board.room_id)
+        .await
+        .unwrap();
+    assert_eq!(selected_rows.len(), 1);
+    let external_server_id: String = selected_rows[0].get(2);
+    let owner_id: i32 = selected_rows[0].get(3);
+    let provider: String = selected_rows[0].get(5);
+    assert_eq!(external_server_id, board.external_server_id);
+    assert_eq!(owner_id, 33);
+    assert_eq!(provider, "mqtt");
+    let num_modified = execution_handler
+        .update_iot_board_credentials(
+            &board.room_id,
+            &board.external_server_id,
+            &Some("encrypted".to_owned()),
+        )
+        .await
+        .unwrap();
+    assert_eq!(num_modified, 1);
+    let selected_rows = execution_handler
+        .select_iot_board(&board.room_id, &board.external_server_id)
+        .await
+        .unwrap();
+    let credentials: Option<String> = selected_rows[0].get(6);
+    assert_eq!(credentials, Some("encrypted".to_owned()));
+    let num_modified = execution_handler
+        .update_iot_board_automations(
+            &board.room_id,
+            &board.external_server_id,
+            &"{\"schedules\":[],\"rules\":[]}".to_owned(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(num_modified, 1);
+    let selected_rows = execution_handler
+        .select_iot_board(&board.room_id, &board.external_server_id)
+        .await
+        .unwrap();
+    let automations: Option<String> = selected_rows[0].get(7);
+    assert_eq!(
+        automations,
+        Some("{\"schedules\":[],\"rules\":[]}".to_owned())
+    );
+}
+
+pub async fn test_iot_board_permission_insert_gather_and_delete(
+    execution_handler: &mut ExecutionHandler,
+) {
+    println!("testing iot board permission insert, gather and delete");
+    let board = gather_db_board();
+    for user_id in [44, 55] {
+        let permission = gather_db_board_permission(&board, user_id);
+        execution_handler
+            .insert_iot_board_permission(&permission)
+            .await
+            .unwrap();
+    }
+    // scope 55 to a single bot
+    let mut permission = gather_db_board_permission(&board, 55);
+    permission.bots = Some("[\"lamp\"]".to_owned());
+    let num_modified = execution_handler
+        .update_iot_board_permission(&permission)
+        .await
+        .unwrap();
+    assert_eq!(num_modified, 1);
+    let selected_rows = execution_handler
+        .select_iot_board_permission(&permission)
+        .await
+        .unwrap();
+    let bots: Option<String> = selected_rows[0].get(4);
+    let actions: Option<String> = selected_rows[0].get(5);
+    assert_eq!(bots, permission.bots);
+    assert_eq!(actions, None);
+    let selected_rows = execution_handler
+        .select_iot_board_permissions_for_room(&board.room_id)
+        .await
+        .unwrap();
+    assert_eq!(selected_rows.len(), 2);
+    let num_modified = execution_handler
+        .delete_iot_board_permission(&board.room_id, &board.external_server_id, &44)
+        .await
+        .unwrap();
+    assert_eq!(num_modified, 1);
+    let num_modified = execution_handler
+        .delete_iot_board_permissions(&board.room_id, &board.external_server_id)
+        .await
+        .unwrap();
+    assert_eq!(num_modified, 1);
+}
+
+pub async fn test_delete_iot_board(execution_handler: &mut ExecutionHandler) {
+    println!("testing iot board delete");
+    let board = gather_db_board();
+    let num_modified = execution_handler
+        .delete_iot_board(&board.room_id, &board.external_server_id)
+        .await
+        .unwrap();
+    assert_eq!(num_modified, 1);
+    let selected_rows = execution_handler
+        .select_iot_board(&board.room_id, &board.external_server_id)
+        .await
+        .unwrap();
+    assert_eq!(selected_rows.len(), 0);
+}
+
+pub async fn test_iot_passive_data_history(execution_handler: &mut ExecutionHandler) {
+    println!("testing iot passive data history insert, downsample and expiry");
+    let board = gather_db_board();
+    for (captured_at, temperature) in [(1000, 20), (1500, 21), (2500, 22)] {
+        execution_handler
+            .insert_iot_passive_data(&DBIoTPassiveData {
+                id: -1,
+                room_id: board.room_id.clone(),
+                external_server_id: board.external_server_id.clone(),
+                data: format!("{{\"temperature\":{}}}", temperature),
+                captured_at: captured_at,
+            })
+            .await
+            .unwrap();
+    }
+    let selected_rows = execution_handler
+        .select_iot_passive_data_in_range(
+            &board.room_id,
+            &board.external_server_id,
+            &0,
+            &3000,
+            &1,
+            &100,
+        )
+        .await
+        .unwrap();
+    assert_eq!(selected_rows.len(), 3);
+    // 1000 and 1500 share a bucket, the latest is kept
+    let selected_rows = execution_handler
+        .select_iot_passive_data_in_range(
+            &board.room_id,
+            &board.external_server_id,
+            &1000,
+            &3000,
+            &1000,
+            &100,
+        )
+        .await
+        .unwrap();
+    assert_eq!(selected_rows.len(), 2);
+    // newest first
+    let captured_at: i64 = selected_rows[1].get(4);
+    assert_eq!(captured_at, 1500);
+    // the limit drops the oldest samples
+    let selected_rows = execution_handler
+        .select_iot_passive_data_in_range(
+            &board.room_id,
+            &board.external_server_id,
+            &0,
+            &3000,
+            &1,
+            &1,
+        )
+        .await
+        .unwrap();
+    assert_eq!(selected_rows.len(), 1);
+    let captured_at: i64 = selected_rows[0].get(4);
+    assert_eq!(captured_at, 2500);
+    let num_modified = execution_handler
+        .delete_expired_iot_passive_data(&2000)
+        .await
+        .unwrap();
+    assert_eq!(num_modified, 2);
+    let num_modified = execution_handler
+        .delete_iot_passive_data(&board.room_id, &board.external_server_id)
+        .await
+        .unwrap();
+    assert_eq!(num_modified, 1);
+}
+
+pub async fn test_iot_action_insert_result_and_gather(execution_handler: &mut ExecutionHandler) {
+    println!("testing iot action insert, result and gather");
+    let board = gather_db_board();
+    for (action_id, requested_at) in [("test_action_1", 1000), ("test_action_2", 2000)] {
+        execution_handler
+            .insert_iot_action(&DBIoTAction {
+                id: -1,
+                action_id: action_id.to_owned(),
+                room_id: board.room_id.clone(),
+                external_server_id: board.external_server_id.clone(),
+                requester_id: Some(22),
+                bot_name: "lamp".to_owned(),
+                action: "on".to_owned(),
+                status: "pending".to_owned(),
+                response: None,
+                requested_at: requested_at,
+                responded_at: None,
+            })
+            .await
+            .unwrap();
+    }
+    let num_modified = execution_handler
+        .update_iot_action_result(
+            &"test_action_1".to_owned(),
+            &"responded".to_owned(),
+            &Some("{}".to_owned()),
+            &1500,
+        )
+        .await
+        .unwrap();
+    assert_eq!(num_modified, 1);
+    let selected_rows = execution_handler
+        .select_iot_actions_for_board(&board.room_id, &board.external_server_id, &1)
+        .await
+        .unwrap();
+    // newest first
+    assert_eq!(selected_rows.len(), 1);
+    let action_id: String = selected_rows[0].get(1);
+    assert_eq!(action_id, "test_action_2");
+    let selected_rows = execution_handler
+        .select_iot_actions_for_board(&board.room_id, &board.external_server_id, &10)
+        .await
+        .unwrap();
+    let status: String = selected_rows[1].get(7);
+    let responded_at: Option<i64> = selected_rows[1].get(10);
+    assert_eq!(status, "responded");
+    assert_eq!(responded_at, Some(1500));
+    let num_modified = execution_handler
+        .delete_expired_iot_actions(&1500)
+        .await
+        .unwrap();
+    assert_eq!(num_modified, 1);
+    let num_modified = execution_handler
+        .delete_iot_actions(&board.room_id, &board.external_server_id)
+        .await
+        .unwrap();
+    assert_eq!(num_modified, 1);
+}
+
+fn gather_db_board_permission(board: &DBIoTBoard, user_id: i32) -> DBIoTBoardPermission {
+    return DBIoTBoardPermission {
+        id: -1,
+        room_id: board.room_id.clone(),
+        external_server_id: board.external_server_id.clone(),
+        user_id: user_id,
+        bots: None,
+        actions: None,
+    };
+}
+
+fn gather_db_board() -> DBIoTBoard {
+    return DBIoTBoard {
+        id: -1,
+        room_id: 4452,
+        external_server_id: "test_external_server".to_owned(),
+        owner_id: 22,
+        outside_name: "test_board".to_owned(),
+        provider: "house_of_iot".to_owned(),
+    };
+}
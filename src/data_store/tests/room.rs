@@ -1,5 +1,6 @@
 use crate::data_store::db_models::{
-    DBRoom, DBRoomPermissions, DBScheduledRoom, DBScheduledRoomAttendance,
+    DBAuditEvent, DBPersistentRoom, DBRoom, DBRoomPermissions, DBRoomRecording, DBScheduledRoom,
+    DBScheduledRoomAttendance,
 };
 use crate::data_store::sql_execution_handler::ExecutionHandler;
 use tokio_postgres::{row::Row, Error};
@@ -50,6 +51,200 @@ pub async fn test_delete_room(execution_handler: &mut ExecutionHandler, room_id:
     assert_eq!(selected_rows.len(), 0);
 }
 
+//#persistent rooms
+
+pub async fn test_persistent_room_insert_and_gather(execution_handler: &mut ExecutionHandler) {
+    println!("testing persistent room insert and gather");
+    let new_persistent_room = gather_persistent_db_room();
+    execution_handler
+        .insert_persistent_room(&new_persistent_room)
+        .await
+        .unwrap();
+    //check inserted data
+    let gather_room_result = execution_handler
+        .select_persistent_room_by_room_id(&new_persistent_room.room_id)
+        .await;
+    let selected_rows = gather_room_result.unwrap();
+    let name: &str = selected_rows[0].get(2);
+    let desc: &str = selected_rows[0].get(3);
+    let public: bool = selected_rows[0].get(4);
+    let auto_speaker: bool = selected_rows[0].get(5);
+    let chat_throttle: i32 = selected_rows[0].get(6);
+    assert_eq!(name, "bench");
+    assert_eq!(desc, "lab bench");
+    assert_eq!(public, true);
+    assert_eq!(auto_speaker, false);
+    assert_eq!(chat_throttle, 1000);
+}
+
+pub async fn test_update_persistent_room(execution_handler: &mut ExecutionHandler) {
+    println!("testing updating persistent room");
+    let mut persistent_room = gather_persistent_db_room();
+    persistent_room.name = "bench 2".to_owned();
+    persistent_room.chat_throttle = 2000;
+    persistent_room.speaker_cap = Some(4);
+    let update_result = execution_handler
+        .update_persistent_room(&persistent_room)
+        .await;
+    assert_eq!(update_result.unwrap(), 1);
+    //check updated data
+    let gather_room_result = execution_handler
+        .select_persistent_room_by_room_id(&persistent_room.room_id)
+        .await;
+    let selected_rows = gather_room_result.unwrap();
+    let name: &str = selected_rows[0].get(2);
+    let chat_throttle: i32 = selected_rows[0].get(6);
+    let speaker_cap: Option<i32> = selected_rows[0].get(8);
+    assert_eq!(name, "bench 2");
+    assert_eq!(chat_throttle, 2000);
+    assert_eq!(speaker_cap, Some(4));
+}
+
+pub async fn test_delete_persistent_room(execution_handler: &mut ExecutionHandler) {
+    println!("testing deleting persistent room");
+    let room_id = gather_persistent_db_room().room_id;
+    let delete_rows_result = execution_handler.delete_persistent_room(&room_id).await;
+    assert_eq!(delete_rows_result.unwrap(), 1);
+    //check if it exist
+    let gather_room_result = execution_handler
+        .select_persistent_room_by_room_id(&room_id)
+        .await;
+    assert_eq!(gather_room_result.unwrap().len(), 0);
+}
+
+pub async fn test_room_instance_insert_gather_and_delete(execution_handler: &mut ExecutionHandler) {
+    println!("testing room instance insert, gather and delete");
+    let room_id = 4449;
+    let instance_id = "test-instance".to_owned();
+    execution_handler
+        .insert_room_instance(&room_id, &instance_id)
+        .await
+        .unwrap();
+    let gather_result = execution_handler
+        .select_room_instance_by_room_id(&room_id)
+        .await
+        .unwrap();
+    assert_eq!(gather_result.len(), 1);
+    let gathered_instance_id: String = gather_result[0].get(2);
+    assert_eq!(gathered_instance_id, instance_id);
+    //instances that are still sending heartbeats keep their rooms
+    let other_instance_id = "test-instance-2".to_owned();
+    execution_handler
+        .upsert_instance_heartbeat(&instance_id, &1000)
+        .await
+        .unwrap();
+    execution_handler
+        .upsert_instance_heartbeat(&instance_id, &5000)
+        .await
+        .unwrap();
+    let expired = execution_handler
+        .select_expired_room_instances(&other_instance_id, &4000)
+        .await
+        .unwrap();
+    assert!(!expired
+        .iter()
+        .any(|row| row.get::<usize, i32>(0) == room_id));
+    //dead instances lose them, but only to the first claim
+    let expired = execution_handler
+        .select_expired_room_instances(&other_instance_id, &6000)
+        .await
+        .unwrap();
+    assert!(expired
+        .iter()
+        .any(|row| row.get::<usize, i32>(0) == room_id));
+    let claim_result = execution_handler
+        .update_room_instance_owner(&room_id, &other_instance_id, &instance_id)
+        .await;
+    assert_eq!(claim_result.unwrap(), 1);
+    let claim_result = execution_handler
+        .update_room_instance_owner(&room_id, &"test-instance-3".to_owned(), &instance_id)
+        .await;
+    assert_eq!(claim_result.unwrap(), 0);
+    let gather_result = execution_handler
+        .select_room_instance_by_room_id(&room_id)
+        .await
+        .unwrap();
+    let gathered_instance_id: String = gather_result[0].get(2);
+    assert_eq!(gathered_instance_id, other_instance_id);
+    let delete_rows_result = execution_handler.delete_room_instance(&room_id).await;
+    assert_eq!(delete_rows_result.unwrap(), 1);
+    let gather_result = execution_handler
+        .select_room_instance_by_room_id(&room_id)
+        .await;
+    assert_eq!(gather_result.unwrap().len(), 0);
+}
+
+pub async fn test_audit_event_insert_and_gather(execution_handler: &mut ExecutionHandler) {
+    println!("testing audit event insert and gather");
+    let room_id = 4450;
+    for action in ["create_room", "kick_user"] {
+        let audit_event = DBAuditEvent {
+            id: -1,
+            room_id: room_id,
+            actor_id: 22,
+            target_id: Some(33),
+            action: action.to_owned(),
+            payload: None,
+            created_at: 1000,
+        };
+        execution_handler
+            .insert_audit_event(&audit_event)
+            .await
+            .unwrap();
+    }
+    let gather_result = execution_handler
+        .select_audit_events_for_room(&room_id, &1)
+        .await;
+    assert_eq!(gather_result.unwrap().len(), 1);
+    let gather_result = execution_handler
+        .select_audit_events_for_room_by_action(&room_id, &"kick_user".to_owned())
+        .await
+        .unwrap();
+    assert_eq!(gather_result.len(), 1);
+    let actor_id: i32 = gather_result[0].get(2);
+    let target_id: Option<i32> = gather_result[0].get(3);
+    assert_eq!(actor_id, 22);
+    assert_eq!(target_id, Some(33));
+}
+
+pub async fn test_room_recording_insert_stop_and_gather(execution_handler: &mut ExecutionHandler) {
+    println!("testing room recording insert, stop and gather");
+    let room_id = 4451;
+    let recording = DBRoomRecording {
+        id: -1,
+        room_id: room_id,
+        started_by: 22,
+        started_at: 1000,
+        stopped_at: None,
+    };
+    let recording_id = execution_handler
+        .insert_room_recording(&recording)
+        .await
+        .unwrap();
+    let num_modified = execution_handler
+        .update_open_room_recordings_stopped_at(&room_id, &2000)
+        .await
+        .unwrap();
+    assert_eq!(num_modified, 1);
+    // already stopped
+    let num_modified = execution_handler
+        .update_open_room_recordings_stopped_at(&room_id, &3000)
+        .await
+        .unwrap();
+    assert_eq!(num_modified, 0);
+    let gather_result = execution_handler
+        .select_room_recordings_for_room(&room_id)
+        .await
+        .unwrap();
+    assert_eq!(gather_result.len(), 1);
+    let id: i32 = gather_result[0].get(0);
+    let started_by: i32 = gather_result[0].get(2);
+    let stopped_at: Option<i64> = gather_result[0].get(4);
+    assert_eq!(id, recording_id);
+    assert_eq!(started_by, 22);
+    assert_eq!(stopped_at, Some(2000));
+}
+
 //#scheduled rooms
 
 pub async fn test_scheduled_room_insert_and_gather(
@@ -298,6 +493,24 @@ fn assert_permissions(new_permissions: &DBRoomPermissions, gather_result: Result
     assert_eq!(asked_to_speak, new_permissions.asked_to_speak);
 }
 
+pub async fn test_delete_orphaned_room_permissions(execution_handler: &mut ExecutionHandler) {
+    println!("testing deleting orphaned room permissions");
+    //no room exists with this id, so the
+    //permissions are orphaned
+    let mut orphaned_permissions: DBRoomPermissions = gather_permissions();
+    orphaned_permissions.room_id = 777777;
+    execution_handler
+        .insert_room_permission(&orphaned_permissions)
+        .await
+        .unwrap();
+    let delete_result = execution_handler.delete_orphaned_room_permissions().await;
+    assert!(delete_result.unwrap() >= 1);
+    let gather_result = execution_handler
+        .select_all_room_permissions_for_room(&orphaned_permissions.room_id)
+        .await;
+    assert_eq!(gather_result.unwrap().len(), 0);
+}
+
 fn gather_permissions() -> DBRoomPermissions {
     return DBRoomPermissions {
         id: 0,
@@ -326,6 +539,20 @@ fn gather_db_room() -> DBRoom {
     };
 }
 
+fn gather_persistent_db_room() -> DBPersistentRoom {
+    return DBPersistentRoom {
+        id: 0,
+        room_id: 4448,
+        name: "bench".to_string(),
+        desc: "lab bench".to_string(),
+        public: true,
+        auto_speaker: false,
+        chat_throttle: 1000,
+        created_at: "test".to_string(),
+        speaker_cap: None,
+    };
+}
+
 fn gather_sch_db_room() -> DBScheduledRoom {
     return DBScheduledRoom {
         id: 0,
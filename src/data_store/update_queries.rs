@@ -5,6 +5,39 @@ SET ownerId = $1
 WHERE Id = $2;
 ";
 
+pub const UPDATE_OPEN_ROOM_RECORDINGS_STOPPED_AT_QUERY: &str = "
+UPDATE room_recording
+SET stoppedAt = $1
+WHERE roomId = $2 AND stoppedAt IS NULL;
+";
+
+pub const UPDATE_IOT_BOARD_QUERY: &str = "
+UPDATE iot_board
+SET ownerId = $1,
+    outsideName = $2,
+    provider = $3
+WHERE roomId = $4 AND externalServerId = $5;
+";
+
+pub const UPDATE_IOT_BOARD_CREDENTIALS_QUERY: &str = "
+UPDATE iot_board
+SET credentials = $1
+WHERE roomId = $2 AND externalServerId = $3;
+";
+
+pub const UPDATE_IOT_BOARD_AUTOMATIONS_QUERY: &str = "
+UPDATE iot_board
+SET automations = $1
+WHERE roomId = $2 AND externalServerId = $3;
+";
+
+pub const UPDATE_IOT_BOARD_PERMISSION_QUERY: &str = "
+UPDATE iot_board_permission
+SET bots = $1,
+    actions = $2
+WHERE roomId = $3 AND externalServerId = $4 AND userId = $5;
+";
+
 pub const UPDATE_ROOM_MOD_STATUS_QUERY: &str = "
 UPDATE room_permission
 SET isMod = $1
@@ -19,6 +52,17 @@ SET isMod = $1,
 WHERE roomId = $4 AND userId = $5;
 ";
 
+pub const UPDATE_PERSISTENT_ROOM_QUERY: &str = "
+UPDATE persistent_room
+SET name = $1,
+    description = $2,
+    public = $3,
+    autoSpeaker = $4,
+    chatThrottle = $5,
+    speakerCap = $6
+WHERE roomId = $7;
+";
+
 pub const UPDATE_USER_AVATAR_QUERY: &str = "
 UPDATE users
 SET avatarUrl = $1
@@ -122,3 +166,17 @@ SET displayName = $1,
     bannerUrl = $6
 WHERE Id = $7;
 ";
+
+pub const UPDATE_IOT_ACTION_RESULT_QUERY: &str = "
+UPDATE iot_action
+SET status = $1, response = $2, respondedAt = $3
+WHERE actionId = $4;
+";
+
+//only claims the room if the dead instance still owns it,
+//so two instances can't both take it over.
+pub const UPDATE_ROOM_INSTANCE_OWNER_QUERY: &str = "
+UPDATE room_instance
+SET instanceId = $1
+WHERE roomId = $2 AND instanceId = $3;
+";
@@ -0,0 +1,116 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::Value;
+
+use super::providers::ALL_PROVIDERS;
+
+// Integration credentials are encrypted(AES-256-GCM) with:
+//  - MERLIN_INTEGRATION_CREDENTIALS_KEY before they're put on the
+//    integration queue, the integration server holds the same key
+//  - MERLIN_CREDENTIALS_STORAGE_KEY when owners ask Merlin to
+//    remember them, only Merlin ever needs this one
+// Both keys are 32 bytes, base64 encoded. Encrypted values are
+// base64(nonce + ciphertext). Credentials are only put on the
+// queue unencrypted if MERLIN_INTEGRATION_ALLOW_PLAINTEXT_CREDENTIALS
+// is true, for integration servers that don't have the key yet.
+
+pub const QUEUE_KEY_VAR: &str = "MERLIN_INTEGRATION_CREDENTIALS_KEY";
+pub const STORAGE_KEY_VAR: &str = "MERLIN_CREDENTIALS_STORAGE_KEY";
+pub const ALLOW_PLAINTEXT_VAR: &str = "MERLIN_INTEGRATION_ALLOW_PLAINTEXT_CREDENTIALS";
+pub const REDACTED: &str = "[redacted]";
+
+const NONCE_LEN: usize = 12;
+
+pub fn queue_key() -> Option<[u8; 32]> {
+    return key_from_env(QUEUE_KEY_VAR);
+}
+
+pub fn storage_key() -> Option<[u8; 32]> {
+    return key_from_env(STORAGE_KEY_VAR);
+}
+
+pub fn plaintext_allowed() -> bool {
+    return std::env::var(ALLOW_PLAINTEXT_VAR).map_or(false, |value| value == "true");
+}
+
+pub fn encrypt(key: &[u8; 32], plaintext: &str) -> String {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    // only fails for plaintexts far bigger than credentials
+    let ciphertext = cipher.encrypt(&nonce, plaintext.as_bytes()).unwrap();
+    let mut encrypted = nonce.to_vec();
+    encrypted.extend(ciphertext);
+    return STANDARD.encode(encrypted);
+}
+
+/// None if it wasn't encrypted with this key(or was tampered with)
+pub fn decrypt(key: &[u8; 32], encrypted: &str) -> Option<String> {
+    let encrypted = STANDARD.decode(encrypted).ok()?;
+    if encrypted.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = encrypted.split_at(NONCE_LEN);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let plaintext = cipher.decrypt(Nonce::from_slice(nonce), ciphertext).ok()?;
+    return String::from_utf8(plaintext).ok();
+}
+
+/// Replaces every provider's secret fields, json inside
+/// of strings(like request_containing_data) included.
+pub fn redact(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                if is_secret_field(name) {
+                    *field = Value::String(REDACTED.to_owned());
+                } else {
+                    redact(field);
+                }
+            }
+        }
+        Value::Array(values) => values.iter_mut().for_each(redact),
+        Value::String(data) => {
+            if let Ok(mut nested @ Value::Object(_)) = serde_json::from_str::<Value>(data) {
+                redact(&mut nested);
+                *data = nested.to_string();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// For logging raw messages, anything that
+/// isn't json is hidden entirely.
+pub fn redact_str(data: &str) -> String {
+    return match serde_json::from_str::<Value>(data) {
+        Ok(mut value) => {
+            redact(&mut value);
+            value.to_string()
+        }
+        Err(_) => REDACTED.to_owned(),
+    };
+}
+
+/// serde errors quote the values they choke on,
+/// which could be a credential.
+pub fn redact_error(error: &serde_json::Error) -> String {
+    return format!(
+        "{:?} error at line {} column {}",
+        error.classify(),
+        error.line(),
+        error.column()
+    );
+}
+
+fn is_secret_field(name: &str) -> bool {
+    return ALL_PROVIDERS
+        .iter()
+        .any(|provider| provider.secret_credential_fields().contains(&name));
+}
+
+fn key_from_env(var: &str) -> Option<[u8; 32]> {
+    let encoded = std::env::var(var).ok()?;
+    let key = STANDARD.decode(encoded.trim()).ok()?;
+    return key.try_into().ok();
+}
@@ -0,0 +1,179 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use super::types::{HOIActionDataOutgoing, IntegrationProviderDescription};
+
+// Device backends the integration server can bridge a board to.
+// Every provider gets its own connect/disconnect/action categories
+// on the integration queue, declares the credentials it needs to
+// connect and how actions are shaped for it. Everything else
+// (passive data, responses, disconnects) comes back the same way
+// no matter the provider, so the Board model doesn't change.
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum IntegrationProvider {
+    #[default]
+    #[serde(rename = "house_of_iot")]
+    HouseOfIoT,
+    #[serde(rename = "mqtt")]
+    Mqtt,
+    #[serde(rename = "home_assistant")]
+    HomeAssistant,
+}
+
+pub const ALL_PROVIDERS: [IntegrationProvider; 3] = [
+    IntegrationProvider::HouseOfIoT,
+    IntegrationProvider::Mqtt,
+    IntegrationProvider::HomeAssistant,
+];
+
+impl IntegrationProvider {
+    /// What the provider is stored as
+    pub fn name(&self) -> &'static str {
+        return match self {
+            IntegrationProvider::HouseOfIoT => "house_of_iot",
+            IntegrationProvider::Mqtt => "mqtt",
+            IntegrationProvider::HomeAssistant => "home_assistant",
+        };
+    }
+
+    pub fn from_name(name: &str) -> Option<IntegrationProvider> {
+        return ALL_PROVIDERS
+            .iter()
+            .find(|provider| provider.name() == name)
+            .copied();
+    }
+
+    /// Fields the credentials object must have(as non empty strings)
+    pub fn credential_fields(&self) -> &'static [&'static str] {
+        return match self {
+            IntegrationProvider::HouseOfIoT => &[
+                "connection_str",
+                "name_and_type",
+                "password",
+                "admin_password",
+            ],
+            IntegrationProvider::Mqtt => &["broker_url", "username", "password", "topic_prefix"],
+            IntegrationProvider::HomeAssistant => &["base_url", "access_token"],
+        };
+    }
+
+    /// Credential fields that are never logged, see integration::credentials
+    pub fn secret_credential_fields(&self) -> &'static [&'static str] {
+        return match self {
+            IntegrationProvider::HouseOfIoT => &["password", "admin_password"],
+            IntegrationProvider::Mqtt => &["password"],
+            IntegrationProvider::HomeAssistant => &["access_token"],
+        };
+    }
+
+    /// Fields of the action sent to the integration server,
+    /// besides the action_id every provider gets.
+    pub fn action_fields(&self) -> &'static [&'static str] {
+        return match self {
+            IntegrationProvider::HouseOfIoT => &["bot_name", "action"],
+            IntegrationProvider::Mqtt => &["topic", "payload"],
+            IntegrationProvider::HomeAssistant => &["entity_id", "service"],
+        };
+    }
+
+    pub fn validate_credentials(&self, credentials: &Value) -> bool {
+        return self.credential_fields().iter().all(|field| {
+            credentials[*field]
+                .as_str()
+                .map_or(false, |value| !value.is_empty())
+        });
+    }
+
+    /// Boards always deal in bots and actions, this maps
+    /// them onto whatever the provider calls them.
+    pub fn format_action(&self, action_id: &String, bot_name: &String, action: &String) -> String {
+        let formatted = match self {
+            IntegrationProvider::HouseOfIoT => {
+                return serde_json::to_string(&HOIActionDataOutgoing {
+                    action_id: action_id.clone(),
+                    bot_name: bot_name.clone(),
+                    action: action.clone(),
+                })
+                .unwrap();
+            }
+            IntegrationProvider::Mqtt => serde_json::json!({
+                "action_id": action_id,
+                "topic": format!("{}/set", bot_name),
+                "payload": action,
+            }),
+            IntegrationProvider::HomeAssistant => serde_json::json!({
+                "action_id": action_id,
+                "entity_id": bot_name,
+                "service": action,
+            }),
+        };
+        return formatted.to_string();
+    }
+
+    /// What the integration server gets asked to connect with,
+    /// house of iot keeps its original flat credentials. The
+    /// request id is echoed back with the auth result.
+    pub fn connect_payload(
+        &self,
+        credentials: &Value,
+        outside_name: &String,
+        user_id: i32,
+        request_id: &String,
+    ) -> String {
+        let payload = match self {
+            IntegrationProvider::HouseOfIoT => {
+                let mut payload = credentials.clone();
+                payload["outside_name"] = Value::String(outside_name.clone());
+                payload["user_id"] = Value::from(user_id);
+                payload["request_id"] = Value::String(request_id.clone());
+                payload
+            }
+            _ => serde_json::json!({
+                "provider": self,
+                "credentials": credentials,
+                "outside_name": outside_name,
+                "user_id": user_id,
+                "request_id": request_id,
+            }),
+        };
+        return payload.to_string();
+    }
+
+    pub fn connect_category(&self) -> String {
+        return format!("connect_{}", self.category_suffix());
+    }
+
+    pub fn disconnect_category(&self) -> String {
+        return format!("disconnect_{}", self.category_suffix());
+    }
+
+    pub fn action_category(&self) -> String {
+        return format!("action_{}", self.category_suffix());
+    }
+
+    pub fn describe(&self) -> IntegrationProviderDescription {
+        return IntegrationProviderDescription {
+            provider: *self,
+            credential_fields: self
+                .credential_fields()
+                .iter()
+                .map(|field| field.to_string())
+                .collect(),
+            action_fields: self
+                .action_fields()
+                .iter()
+                .map(|field| field.to_string())
+                .collect(),
+        };
+    }
+
+    // house of iot kept its original categories
+    fn category_suffix(&self) -> &'static str {
+        return match self {
+            IntegrationProvider::HouseOfIoT => "hoi",
+            IntegrationProvider::Mqtt => "mqtt",
+            IntegrationProvider::HomeAssistant => "home_assistant",
+        };
+    }
+}
@@ -1,161 +1,331 @@
-use std::collections::HashSet;
+use std::collections::HashMap;
+use std::sync::Arc;
 
+use chrono::Utc;
+use futures::lock::Mutex;
 use serde_json::Value;
 
 use crate::{
-    communication::types::{BasicResponse, NewIoTServer, PassiveData},
-    state::state::ServerState,
+    board::{self, types::BoardStatus},
+    communication::types::{BasicResponse, NewIoTServer},
+    data_store::sql_execution_handler::ExecutionHandler,
+    integration::credentials,
+    integration::types::{
+        ActionResponseMessage, AuthResult, CategorizedMessage, DisconnectedMessage,
+        IntegrationMessage, PassiveDataMessage,
+    },
+    logging,
+    state::{
+        state::ServerState,
+        types::{Board, PassiveFanOut, PendingIntegrationConnect},
+    },
     ws_fan,
 };
 
-pub async fn route_msg(msg_data: String, state: &mut ServerState) {
-    let msg: serde_json::Value = serde_json::from_str(&msg_data).unwrap();
-    if msg["passed_auth"] != Value::Null {
-        check_auth_and_insert(msg, state).await;
-    } else if msg["category"] != Value::Null {
-        // using .to_string includes the trailing quotes, so your string be like
-        // "example" instead of just -> example
-        let category = msg["category"].to_string();
-        let category_corrected = category[1..category.len() - 1].to_string();
+pub async fn route_msg(
+    msg_data: String,
+    state: &mut ServerState,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+) {
+    let msg = match decode_msg(&msg_data, state) {
+        Some(msg) => msg,
+        None => return,
+    };
+    match msg {
+        IntegrationMessage::AuthResult(result) => {
+            check_auth_and_insert(result, state, execution_handler).await;
+        }
+        IntegrationMessage::Categorized(CategorizedMessage::PassiveData(message)) => {
+            handle_passive_data(message, state, execution_handler).await;
+        }
+        IntegrationMessage::Categorized(CategorizedMessage::Disconnected(message)) => {
+            handle_disconnected(message, state).await;
+        }
+        IntegrationMessage::Categorized(CategorizedMessage::ActionResponse(message)) => {
+            handle_action_response(message, msg_data, state, execution_handler).await;
+        }
+        // dropped(and counted) by decode_msg
+        IntegrationMessage::Categorized(CategorizedMessage::Unknown) => {}
+    }
+}
 
-        if category_corrected == "passive_data" {
-            // Let the room know there is a new IoT Server
-            let external_id = msg["server_id"].to_string();
-            let external_id = external_id[1..external_id.len() - 1].to_string();
+/// Malformed messages and unknown categories are counted
+/// and dropped rather than taking down the consumer.
+pub fn decode_msg(msg_data: &str, state: &mut ServerState) -> Option<IntegrationMessage> {
+    let counters = &mut state.integration_protocol_counters;
+    match serde_json::from_str(msg_data) {
+        Ok(IntegrationMessage::Categorized(CategorizedMessage::Unknown)) => {
+            counters.unknown_categories += 1;
+            logging::console::log_failure(&format!(
+                "Dropping integration message with an unknown category({} so far):{}",
+                counters.unknown_categories,
+                credentials::redact_str(msg_data)
+            ));
+            return None;
+        }
+        Ok(msg) => return Some(msg),
+        Err(error) => {
+            counters.decode_errors += 1;
+            logging::console::log_failure(&format!(
+                "Couldn't decode integration message({} so far):{}, error:{}",
+                counters.decode_errors,
+                credentials::redact_str(msg_data),
+                credentials::redact_error(&error)
+            ));
+            return None;
+        }
+    }
+}
 
-            let actual_passive_data = msg["data"].to_string();
-            //construct and notify everyone in that room of the new
-            //passive data snapshot from the server.
-            let passive_data = serde_json::to_string(&BasicResponse {
-                response_op_code: "passive_data".to_owned(),
-                response_containing_data: serde_json::to_string(&PassiveData {
-                    external_id: external_id.clone(),
-                    passive_data: actual_passive_data.clone(),
-                })
-                .unwrap(),
-            })
-            .unwrap();
+async fn handle_passive_data(
+    message: PassiveDataMessage,
+    state: &mut ServerState,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+) {
+    let external_id = message.server_id;
+    let actual_passive_data = message.data.to_string();
+    board::liveness::mark_seen(state, &external_id, Utc::now().timestamp_millis());
 
-            //should always be Some, but just extra safety
-            if let Some(room_id) = state.external_servers.get(&external_id) {
-                let cloned_room_id = room_id.clone();
-                insert_new_passive_snapshot(
-                    state,
-                    actual_passive_data,
-                    cloned_room_id,
-                    external_id,
-                );
-                ws_fan::fan::broadcast_message_to_room(passive_data, state, cloned_room_id).await;
-            }
-        } else if category_corrected == "disconnected" {
-            let external_id = msg["server_id"].to_string();
-            if let Some(room_id) = state.external_servers.get(&external_id) {
-                if let Some(room) = state.rooms.get_mut(room_id) {
-                    let cloned_room_id = room_id.clone();
-                    drop(room_id);
-                    room.iot_server_connections.remove(&external_id);
-                    let basic_response = BasicResponse {
-                        response_op_code: "hoi_server_disconnected".to_owned(),
-                        response_containing_data: external_id.clone(),
-                    };
-                    // Let the room know a server was disconnected
-                    ws_fan::fan::broadcast_message_to_room(
-                        serde_json::to_string(&basic_response).unwrap(),
-                        state,
-                        cloned_room_id,
-                    )
-                    .await;
-                    state.external_servers.remove(&external_id);
-                }
-            }
-        } else if category_corrected == "action_response" {
-            let external_id = msg["server_id"].to_string();
-            let external_id = external_id[1..external_id.len() - 1].to_string();
+    //should always be Some, but just extra safety
+    if let Some(room_id) = state.external_servers.get(&external_id) {
+        let cloned_room_id = room_id.clone();
+        //notify everyone in that room of the new passive data
+        //snapshot(throttled, see board::passive), repeated
+        //snapshots aren't kept in the history either.
+        if insert_new_passive_snapshot(
+            state,
+            actual_passive_data.clone(),
+            cloned_room_id,
+            external_id.clone(),
+        ) {
+            let mut handler = execution_handler.lock().await;
+            board::history::persist_passive_data(
+                &mut handler,
+                &cloned_room_id,
+                &external_id,
+                &actual_passive_data,
+            )
+            .await;
+            drop(handler);
+            board::passive::fan_out_if_due(state, &cloned_room_id, &external_id).await;
+        }
+    }
+}
 
+pub async fn handle_disconnected(message: DisconnectedMessage, state: &mut ServerState) {
+    let external_id = message.server_id;
+    if let Some(room_id) = state.external_servers.remove(&external_id) {
+        if let Some(room) = state.rooms.get_mut(&room_id) {
+            room.iot_server_connections.remove(&external_id);
             let basic_response = BasicResponse {
-                response_op_code: "action_response_iot".to_owned(),
-                response_containing_data: msg_data,
+                response_op_code: "hoi_server_disconnected".to_owned(),
+                response_containing_data: external_id,
             };
+            // Let the room know a server was disconnected
+            ws_fan::fan::broadcast_message_to_room(
+                serde_json::to_string(&basic_response).unwrap(),
+                state,
+                room_id,
+            )
+            .await;
+        }
+    }
+}
 
-            if let Some(room_id) = state.external_servers.get(&external_id) {
-                let cloned_room_id = room_id.clone();
-                drop(room_id);
-                // Let the room know a server was disconnected
-                ws_fan::fan::broadcast_message_to_room(
-                    serde_json::to_string(&basic_response).unwrap(),
-                    state,
-                    cloned_room_id,
-                )
+async fn handle_action_response(
+    message: ActionResponseMessage,
+    msg_data: String,
+    state: &mut ServerState,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+) {
+    let external_id = message.server_id;
+    board::liveness::mark_seen(state, &external_id, Utc::now().timestamp_millis());
+    if let Some(room_id) = state.external_servers.get(&external_id) {
+        let cloned_room_id = room_id.clone();
+        // link the response back to the action it's for
+        let mut response: Value = serde_json::from_str(&msg_data).unwrap_or_default();
+        if let Some((action_id, pending)) =
+            board::actions::take_responded_action(state, &external_id, message.action_id)
+        {
+            let mut handler = execution_handler.lock().await;
+            board::actions::record_result(&mut handler, &action_id, "responded", Some(msg_data))
                 .await;
-            }
+            response["action_id"] = serde_json::json!(action_id);
+            response["requester_id"] = serde_json::json!(pending.requester_id);
         }
+        let basic_response = BasicResponse {
+            response_op_code: "action_response_iot".to_owned(),
+            response_containing_data: response.to_string(),
+        };
+        // Let the room know how the action went
+        ws_fan::fan::broadcast_message_to_room(
+            serde_json::to_string(&basic_response).unwrap(),
+            state,
+            cloned_room_id,
+        )
+        .await;
     }
 }
 
+/// Returns false if the board doesn't exist
+/// or the snapshot didn't change.
 pub fn insert_new_passive_snapshot(
     state: &mut ServerState,
     passive_data: String,
     room_id: i32,
     external_id: String,
-) {
+) -> bool {
     if let Some(room) = state.rooms.get_mut(&room_id) {
         if let Some(board) = room.iot_server_connections.get_mut(&external_id) {
-            board.passive_data_snapshot = Some(passive_data);
+            if board::passive::update_snapshot(board, passive_data) {
+                // rule actions are sent by the automation task
+                let passive_data = board.passive_data_snapshot.clone().unwrap();
+                let triggered = board::automation::evaluate_rules(board, &passive_data);
+                state.pending_board_actions.extend(triggered);
+                return true;
+            }
         }
     }
+    return false;
 }
 
-pub async fn check_auth_and_insert(msg: serde_json::Value, state: &mut ServerState) {
-    let passed_auth = msg["passed_auth"].as_bool();
-    if let Some(passed) = passed_auth {
-        // If we passed auth insert our new server connection
-        if passed {
-            let external_server_id = msg["server_id"].to_string();
-            let external_server_id =
-                external_server_id[1..external_server_id.len() - 1].to_string();
+/// How long the integration server has to send a connect's auth result
+pub const CONNECT_TIMEOUT_MS: i64 = 30000;
 
-            let user_id: Result<i32, _> = msg["user_id"].to_string().parse();
-            if let Ok(user_id) = user_id {
-                if let Some(user) = state.active_users.get(&user_id) {
-                    let room_id = user.current_room_id.clone();
-                    if let Some(room) = state.rooms.get_mut(&room_id) {
-                        //Insert this iot server for this room
-                        room.iot_server_connections.insert(
-                            external_server_id.clone(),
-                            crate::state::types::Board {
-                                room_id: room_id,
-                                owner_user_id: user_id.clone(),
-                                users_with_permission: HashSet::new(),
-                                external_server_id: external_server_id.clone(),
-                                passive_data_snapshot: None,
-                                outside_name: msg["outside_name"].to_string(),
-                            },
-                        );
-                        // We need to know what room links to what external server ID, since
-                        // that data isn't flowing per request. We only use the external server
-                        // ID to communicate normally.
-                        state
-                            .external_servers
-                            .insert(external_server_id.clone(), room.room_id.clone());
-                        let basic_response = BasicResponse {
-                            response_op_code: "new_iot_server".to_owned(),
-                            response_containing_data: serde_json::to_string(&NewIoTServer {
-                                external_id: external_server_id,
-                                owner_id: user_id,
-                                outside_name: msg["outside_name"].to_string(),
-                            })
-                            .unwrap(),
-                        };
-                        // Let the room know there is a new IoT Server
-                        ws_fan::fan::broadcast_message_to_room(
-                            serde_json::to_string(&basic_response).unwrap(),
-                            state,
-                            room_id,
-                        )
-                        .await;
-                    }
-                }
+/// Stops tracking the connect an auth result is for, results
+/// without a request id can only be matched when the user
+/// has a single pending connect.
+pub fn take_pending_connect(
+    state: &mut ServerState,
+    request_id: Option<String>,
+    user_id: Option<i32>,
+) -> Option<PendingIntegrationConnect> {
+    let request_id = match request_id {
+        Some(request_id) => request_id,
+        None => {
+            let user_request_ids: Vec<&String> = state
+                .pending_integration_connects
+                .iter()
+                .filter(|(_, pending)| Some(pending.user_id) == user_id)
+                .map(|(request_id, _)| request_id)
+                .collect();
+            if user_request_ids.len() != 1 {
+                return None;
             }
+            user_request_ids[0].clone()
         }
+    };
+    match state.pending_integration_connects.get(&request_id) {
+        Some(pending) if user_id.map_or(true, |user_id| user_id == pending.user_id) => {}
+        _ => return None,
+    }
+    return state.pending_integration_connects.remove(&request_id);
+}
+
+/// Forgets the connects the integration server never
+/// answered, returns how many were forgotten.
+pub fn prune_expired_connects(state: &mut ServerState, now: i64) -> usize {
+    let num_pending = state.pending_integration_connects.len();
+    state
+        .pending_integration_connects
+        .retain(|_, pending| now - pending.requested_at <= CONNECT_TIMEOUT_MS);
+    return num_pending - state.pending_integration_connects.len();
+}
+
+/// Results are only accepted for connects we are waiting on,
+/// the board goes to the room the connect was requested in.
+pub async fn check_auth_and_insert(
+    result: AuthResult,
+    state: &mut ServerState,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+) {
+    let pending = match take_pending_connect(state, result.request_id, result.user_id) {
+        Some(pending) => pending,
+        None => {
+            logging::console::log_failure(&format!(
+                "Auth result for user({:?}) doesn't match a pending connect",
+                result.user_id
+            ));
+            return;
+        }
+    };
+    // If we passed auth insert our new server connection
+    if !result.passed_auth {
+        return;
+    }
+    let external_server_id = match result.server_id {
+        Some(external_server_id) => external_server_id,
+        None => return,
+    };
+    // trust what was asked for over what was echoed back
+    let provider = pending.provider;
+    let user_id = pending.user_id;
+    let room_id = pending.room_id;
+    let still_in_room = state
+        .active_users
+        .get(&user_id)
+        .map_or(false, |user| user.current_room_id == room_id);
+    if !still_in_room || !state.rooms.contains_key(&room_id) {
+        logging::console::log_failure(&format!(
+            "user({}) left room({}) before their board connected",
+            user_id, room_id
+        ));
+        return;
+    }
+    let mut new_board = Board {
+        room_id: room_id,
+        owner_user_id: user_id.clone(),
+        users_with_permission: HashMap::new(),
+        external_server_id: external_server_id.clone(),
+        passive_data_snapshot: None,
+        passive_fan_out: PassiveFanOut::default(),
+        outside_name: result.outside_name.clone(),
+        provider: provider,
+        last_seen: Utc::now().timestamp_millis(),
+        status: BoardStatus::Online,
+        integration_server_id: result.integration_server_id.clone(),
+        schedules: Vec::new(),
+        rules: Vec::new(),
+    };
+    let mut handler = execution_handler.lock().await;
+    board::handler::persist_and_restore_board(&mut handler, &mut new_board).await;
+    if let Some(credentials) = pending.credentials {
+        board::handler::remember_credentials(
+            &mut handler,
+            &room_id,
+            &external_server_id,
+            credentials,
+        )
+        .await;
     }
+    drop(handler);
+    let controllers_of_room: Vec<i32> = new_board.users_with_permission.keys().cloned().collect();
+    let room = state.rooms.get_mut(&room_id).unwrap();
+    //Insert this iot server for this room
+    room.iot_server_connections
+        .insert(external_server_id.clone(), new_board);
+    // We need to know what room links to what external server ID, since
+    // that data isn't flowing per request. We only use the external server
+    // ID to communicate normally.
+    state
+        .external_servers
+        .insert(external_server_id.clone(), room.room_id.clone());
+    let basic_response = BasicResponse {
+        response_op_code: "new_iot_server".to_owned(),
+        response_containing_data: serde_json::to_string(&NewIoTServer {
+            external_id: external_server_id,
+            owner_id: user_id,
+            outside_name: result.outside_name,
+            provider: provider,
+            controllers_of_room: controllers_of_room,
+        })
+        .unwrap(),
+    };
+    // Let the room know there is a new IoT Server
+    ws_fan::fan::broadcast_message_to_room(
+        serde_json::to_string(&basic_response).unwrap(),
+        state,
+        room_id,
+    )
+    .await;
 }
@@ -0,0 +1,274 @@
+use crate::integration::credentials;
+use crate::integration::providers::{IntegrationProvider, ALL_PROVIDERS};
+use crate::integration::router;
+use crate::integration::types::{CategorizedMessage, DisconnectedMessage, IntegrationMessage};
+use crate::state::state::ServerState;
+use crate::state::tests::{mock_board, mock_room};
+use crate::state::types::PendingIntegrationConnect;
+use tokio::sync::mpsc;
+
+pub async fn test() {
+    test_decoding();
+    test_disconnected().await;
+    test_providers();
+    test_credentials();
+    test_pending_connects();
+}
+
+fn test_decoding() {
+    let mut state = ServerState::new();
+    let auth = router::decode_msg(
+        r#"{"passed_auth":true,"server_id":"server","user_id":22,"outside_name":"board"}"#,
+        &mut state,
+    );
+    match auth {
+        Some(IntegrationMessage::AuthResult(result)) => {
+            assert!(result.passed_auth);
+            assert_eq!(result.server_id, Some("server".to_owned()));
+            assert_eq!(result.user_id, Some(22));
+            // no surrounding quotes
+            assert_eq!(result.outside_name, "board");
+        }
+        _ => panic!("auth result wasn't decoded"),
+    }
+    // failed auth results only carry the flag
+    match router::decode_msg(r#"{"passed_auth":false}"#, &mut state) {
+        Some(IntegrationMessage::AuthResult(result)) => assert!(!result.passed_auth),
+        _ => panic!("failed auth result wasn't decoded"),
+    }
+    match router::decode_msg(
+        r#"{"category":"passive_data","server_id":"server","data":{"temp":20}}"#,
+        &mut state,
+    ) {
+        Some(IntegrationMessage::Categorized(CategorizedMessage::PassiveData(message))) => {
+            assert_eq!(message.server_id, "server");
+            assert_eq!(message.data["temp"], 20);
+        }
+        _ => panic!("passive data wasn't decoded"),
+    }
+    match router::decode_msg(
+        r#"{"category":"disconnected","server_id":"server"}"#,
+        &mut state,
+    ) {
+        Some(IntegrationMessage::Categorized(CategorizedMessage::Disconnected(message))) => {
+            assert_eq!(message.server_id, "server");
+        }
+        _ => panic!("disconnect wasn't decoded"),
+    }
+    match router::decode_msg(
+        r#"{"category":"action_response","server_id":"server","action_id":"abc","status":"ok"}"#,
+        &mut state,
+    ) {
+        Some(IntegrationMessage::Categorized(CategorizedMessage::ActionResponse(message))) => {
+            assert_eq!(message.action_id, Some("abc".to_owned()));
+        }
+        _ => panic!("action response wasn't decoded"),
+    }
+    assert_eq!(state.integration_protocol_counters.decode_errors, 0);
+    // malformed messages are counted instead of panicking
+    assert!(router::decode_msg("not json", &mut state).is_none());
+    assert!(router::decode_msg(r#"{"category":"passive_data"}"#, &mut state).is_none());
+    assert!(router::decode_msg(r#"{"server_id":"server"}"#, &mut state).is_none());
+    assert_eq!(state.integration_protocol_counters.decode_errors, 3);
+    // so are categories we don't know about
+    assert!(router::decode_msg(
+        r#"{"category":"something_new","server_id":"server"}"#,
+        &mut state
+    )
+    .is_none());
+    assert_eq!(state.integration_protocol_counters.unknown_categories, 1);
+}
+
+async fn test_disconnected() {
+    let mut state = ServerState::new();
+    let mut room = mock_room(3, "0");
+    room.user_ids.insert(22);
+    room.iot_server_connections
+        .insert("server".to_owned(), mock_board(3, "server"));
+    state.rooms.insert(3, room);
+    state.external_servers.insert("server".to_owned(), 3);
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    state.peer_map.insert(22, tx);
+    router::handle_disconnected(
+        DisconnectedMessage {
+            server_id: "server".to_owned(),
+        },
+        &mut state,
+    )
+    .await;
+    // the board is actually removed now that the id isn't quoted
+    assert!(state
+        .rooms
+        .get(&3)
+        .unwrap()
+        .iot_server_connections
+        .is_empty());
+    assert!(state.external_servers.is_empty());
+    let message = rx.recv().await.unwrap();
+    assert!(message
+        .to_str()
+        .unwrap()
+        .contains("hoi_server_disconnected"));
+}
+
+fn test_providers() {
+    let mut state = ServerState::new();
+    // auth results from older integration servers are house of iot
+    match router::decode_msg(
+        r#"{"passed_auth":true,"server_id":"server","user_id":22,"outside_name":"board"}"#,
+        &mut state,
+    ) {
+        Some(IntegrationMessage::AuthResult(result)) => {
+            assert_eq!(result.provider, IntegrationProvider::HouseOfIoT);
+        }
+        _ => panic!("auth result wasn't decoded"),
+    }
+    match router::decode_msg(
+        r#"{"passed_auth":true,"server_id":"server","user_id":22,"outside_name":"board","provider":"mqtt"}"#,
+        &mut state,
+    ) {
+        Some(IntegrationMessage::AuthResult(result)) => {
+            assert_eq!(result.provider, IntegrationProvider::Mqtt);
+        }
+        _ => panic!("auth result wasn't decoded"),
+    }
+    for provider in ALL_PROVIDERS {
+        assert_eq!(
+            IntegrationProvider::from_name(provider.name()),
+            Some(provider)
+        );
+    }
+    assert_eq!(IntegrationProvider::from_name("unknown"), None);
+    // house of iot keeps its original categories and action format
+    let house_of_iot = IntegrationProvider::HouseOfIoT;
+    assert_eq!(house_of_iot.connect_category(), "connect_hoi");
+    assert_eq!(house_of_iot.action_category(), "action_hoi");
+    let action: serde_json::Value = serde_json::from_str(&house_of_iot.format_action(
+        &"abc".to_owned(),
+        &"lamp".to_owned(),
+        &"on".to_owned(),
+    ))
+    .unwrap();
+    assert_eq!(
+        action,
+        serde_json::json!({"action_id": "abc", "bot_name": "lamp", "action": "on"})
+    );
+    let mqtt = IntegrationProvider::Mqtt;
+    assert_eq!(mqtt.disconnect_category(), "disconnect_mqtt");
+    let action: serde_json::Value = serde_json::from_str(&mqtt.format_action(
+        &"abc".to_owned(),
+        &"lamp".to_owned(),
+        &"on".to_owned(),
+    ))
+    .unwrap();
+    assert_eq!(action["topic"], "lamp/set");
+    assert_eq!(action["payload"], "on");
+    // every declared credential has to be filled in
+    let home_assistant = IntegrationProvider::HomeAssistant;
+    assert!(home_assistant.validate_credentials(
+        &serde_json::json!({"base_url": "http://localhost:8123", "access_token": "token"})
+    ));
+    assert!(!home_assistant.validate_credentials(
+        &serde_json::json!({"base_url": "http://localhost:8123", "access_token": ""})
+    ));
+    assert!(!home_assistant.validate_credentials(&serde_json::json!({"base_url": "x"})));
+    assert!(!mqtt.validate_credentials(&serde_json::json!("not an object")));
+}
+
+fn test_credentials() {
+    let key = [7u8; 32];
+    let encrypted = credentials::encrypt(&key, "secret");
+    assert!(!encrypted.contains("secret"));
+    assert_eq!(
+        credentials::decrypt(&key, &encrypted),
+        Some("secret".to_owned())
+    );
+    // same plaintext, different nonce
+    assert_ne!(credentials::encrypt(&key, "secret"), encrypted);
+    assert_eq!(credentials::decrypt(&[8u8; 32], &encrypted), None);
+    let mut tampered = encrypted.into_bytes();
+    let last = tampered.len() - 3;
+    tampered[last] = if tampered[last] == b'A' { b'B' } else { b'A' };
+    assert_eq!(
+        credentials::decrypt(&key, &String::from_utf8(tampered).unwrap()),
+        None
+    );
+    assert_eq!(credentials::decrypt(&key, "not base64!"), None);
+    // house of iot keeps its flat credentials
+    let payload: serde_json::Value =
+        serde_json::from_str(&IntegrationProvider::HouseOfIoT.connect_payload(
+            &serde_json::json!({"connection_str": "localhost", "password": "pass"}),
+            &"board".to_owned(),
+            22,
+            &"request".to_owned(),
+        ))
+        .unwrap();
+    assert_eq!(payload["connection_str"], "localhost");
+    assert_eq!(payload["outside_name"], "board");
+    assert_eq!(payload["user_id"], 22);
+    assert_eq!(payload["request_id"], "request");
+    // secrets are redacted, even inside of nested json strings
+    let request = serde_json::json!({
+        "request_op_code": "connect_hoi",
+        "request_containing_data": serde_json::json!({
+            "connection_str": "localhost",
+            "password": "pass",
+            "admin_password": "admin",
+        })
+        .to_string(),
+    });
+    let redacted: serde_json::Value =
+        serde_json::from_str(&credentials::redact_str(&request.to_string())).unwrap();
+    let data: serde_json::Value =
+        serde_json::from_str(redacted["request_containing_data"].as_str().unwrap()).unwrap();
+    assert_eq!(data["password"], credentials::REDACTED);
+    assert_eq!(data["admin_password"], credentials::REDACTED);
+    assert_eq!(data["connection_str"], "localhost");
+    let mut value =
+        serde_json::json!({"credentials": {"access_token": "token", "base_url": "url"}});
+    credentials::redact(&mut value);
+    assert_eq!(value["credentials"]["access_token"], credentials::REDACTED);
+    assert_eq!(value["credentials"]["base_url"], "url");
+    assert_eq!(
+        credentials::redact_str("password=pass"),
+        credentials::REDACTED
+    );
+    let error = serde_json::from_str::<i32>("\"pass\"").unwrap_err();
+    assert!(!credentials::redact_error(&error).contains("pass"));
+}
+
+fn test_pending_connects() {
+    let mut state = ServerState::new();
+    for (request_id, user_id, requested_at) in [("first", 22, 1000), ("second", 22, 2000)] {
+        state.pending_integration_connects.insert(
+            request_id.to_owned(),
+            PendingIntegrationConnect {
+                user_id: user_id,
+                room_id: 3,
+                provider: IntegrationProvider::Mqtt,
+                credentials: None,
+                requested_at: requested_at,
+            },
+        );
+    }
+    // another user's auth result can't take the connect
+    assert!(router::take_pending_connect(&mut state, Some("first".to_owned()), Some(33)).is_none());
+    // without a request id there's no telling which of the two it's for
+    assert!(router::take_pending_connect(&mut state, None, Some(22)).is_none());
+    let pending =
+        router::take_pending_connect(&mut state, Some("first".to_owned()), Some(22)).unwrap();
+    // what was asked for, even if the result echoes house of iot
+    assert_eq!(pending.provider, IntegrationProvider::Mqtt);
+    // the board goes to the room it was requested in
+    assert_eq!(pending.room_id, 3);
+    // unanswered connects are forgotten
+    assert_eq!(
+        router::prune_expired_connects(&mut state, 2000 + router::CONNECT_TIMEOUT_MS),
+        0
+    );
+    assert_eq!(
+        router::prune_expired_connects(&mut state, 2001 + router::CONNECT_TIMEOUT_MS),
+        1
+    );
+    assert!(state.pending_integration_connects.is_empty());
+}
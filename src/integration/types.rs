@@ -1,10 +1,81 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Deserialize, Serialize)]
-pub struct AuthResponse {
-    pub outside_name: String,
+use super::providers::IntegrationProvider;
+
+/// Everything the integration server sends us,
+/// auth results are the only messages without a category.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum IntegrationMessage {
+    AuthResult(AuthResult),
+    Categorized(CategorizedMessage),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "category")]
+pub enum CategorizedMessage {
+    #[serde(rename = "passive_data")]
+    PassiveData(PassiveDataMessage),
+    #[serde(rename = "disconnected")]
+    Disconnected(DisconnectedMessage),
+    #[serde(rename = "action_response")]
+    ActionResponse(ActionResponseMessage),
+    /// categories we don't know about are counted and dropped
+    #[serde(other)]
+    Unknown,
+}
+
+/// The result of connecting a user's IoT server,
+/// only passed results have the rest filled in.
+#[derive(Deserialize)]
+pub struct AuthResult {
     pub passed_auth: bool,
+    #[serde(default)]
     pub server_id: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<i32>,
+    #[serde(default)]
+    pub outside_name: String,
+    /// echoed back from the connect request,
+    /// older integration servers only know house of iot
+    #[serde(default)]
+    pub provider: IntegrationProvider,
+    /// echoed back from the connect request,
+    /// older integration servers leave it out
+    #[serde(default)]
+    pub request_id: Option<String>,
+    /// the integration server holding the connection,
+    /// same as in its heartbeats(see board::liveness)
+    #[serde(default)]
+    pub integration_server_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PassiveDataMessage {
+    pub server_id: String,
+    #[serde(default)]
+    pub data: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+pub struct DisconnectedMessage {
+    pub server_id: String,
+}
+
+/// The rest of the response is passed to the room untouched
+#[derive(Deserialize)]
+pub struct ActionResponseMessage {
+    pub server_id: String,
+    /// missing when the integration server doesn't echo it
+    #[serde(default)]
+    pub action_id: Option<String>,
+}
+
+/// Integration servers publish these to a fanout exchange,
+/// see board::liveness.
+#[derive(Deserialize, Serialize)]
+pub struct IntegrationServerHeartbeat {
+    pub integration_server_id: String,
 }
 
 /// Communication with the integration server
@@ -13,6 +84,10 @@ pub struct GeneralMessage {
     pub category: String,
     pub data: String,
     pub server_id: String,
+    /// data was encrypted with the queue key,
+    /// see integration::credentials
+    #[serde(default)]
+    pub encrypted: bool,
 }
 
 #[derive(Deserialize, Serialize, Clone)]
@@ -25,6 +100,40 @@ pub struct HouseOfIoTCredentials {
     pub admin_password: String,
     pub outside_name: String,
     pub user_id: i32,
+    /// store them(encrypted) so the owner
+    /// can reconnect the board later
+    #[serde(default, skip_serializing)]
+    pub remember_credentials: bool,
+}
+
+/// Connects a board through any provider,
+/// see integration::providers for the credentials each needs.
+#[derive(Deserialize, Serialize)]
+pub struct ConnectIntegration {
+    pub provider: IntegrationProvider,
+    pub credentials: serde_json::Value,
+    pub outside_name: String,
+    pub user_id: i32,
+    #[serde(default)]
+    pub remember_credentials: bool,
+}
+
+/// What gets encrypted and stored for boards
+/// whose owners asked Merlin to remember them.
+#[derive(Deserialize, Serialize)]
+pub struct StoredCredentials {
+    pub provider: IntegrationProvider,
+    pub credentials: serde_json::Value,
+    pub outside_name: String,
+}
+
+/// What clients need to build a connect form
+/// and actions for a provider.
+#[derive(Deserialize, Serialize)]
+pub struct IntegrationProviderDescription {
+    pub provider: IntegrationProvider,
+    pub credential_fields: Vec<String>,
+    pub action_fields: Vec<String>,
 }
 
 #[derive(Deserialize, Serialize)]
@@ -41,6 +150,8 @@ pub struct HOIActionDataIncoming {
 
 #[derive(Deserialize, Serialize, Clone)]
 pub struct HOIActionDataOutgoing {
+    /// echoed back in the action_response
+    pub action_id: String,
     pub bot_name: String,
     pub action: String,
 }
@@ -12,8 +12,25 @@ pub mod state {
     pub mod types;
 }
 
+pub mod board {
+    pub mod actions;
+    pub mod automation;
+    pub mod handler;
+    pub mod history;
+    pub mod liveness;
+    pub mod passive;
+    pub mod types;
+}
+
+pub mod cluster {
+    pub mod handler;
+    pub mod router;
+    pub mod types;
+}
+
 pub mod common {
     pub mod response_logic;
+    pub mod throttle;
 }
 
 pub mod data_store {
@@ -27,6 +44,7 @@ pub mod data_store {
     pub mod update_queries;
     pub mod tests {
         pub mod blocks;
+        pub mod board;
         pub mod follower;
         pub mod room;
         pub mod user;
@@ -34,7 +52,10 @@ pub mod data_store {
 }
 
 pub mod integration {
+    pub mod credentials;
+    pub mod providers;
     pub mod router;
+    pub mod tests;
     pub mod types;
 }
 
@@ -73,13 +94,21 @@ pub mod rabbitmq {
     pub mod test;
 }
 pub mod rooms {
+    pub mod audit;
     pub mod handler;
     pub mod permission_configs;
+    pub mod speaking;
 }
 
 pub mod vs_response {
     pub mod handler;
     pub mod router;
+    pub mod tests;
+    pub mod types;
+}
+
+pub mod voice_servers {
+    pub mod handler;
     pub mod types;
 }
 
@@ -0,0 +1,34 @@
+use crate::communication::data_capturer;
+use crate::data_store::db_models::DBAuditEvent;
+use crate::data_store::sql_execution_handler::ExecutionHandler;
+use crate::logging;
+use chrono::Utc;
+
+/// Records a privileged action(mod/owner action) taken in a room,
+/// the payload is any extra json describing the action.
+pub async fn record(
+    execution_handler: &mut ExecutionHandler,
+    room_id: &i32,
+    actor_id: &i32,
+    target_id: Option<i32>,
+    action: &str,
+    payload: Option<String>,
+) {
+    let audit_event = DBAuditEvent {
+        id: -1,
+        room_id: room_id.clone(),
+        actor_id: actor_id.clone(),
+        target_id: target_id,
+        action: action.to_owned(),
+        payload: payload,
+        created_at: Utc::now().timestamp_millis(),
+    };
+    let capture_result =
+        data_capturer::capture_new_audit_event(execution_handler, &audit_event).await;
+    if capture_result.encountered_error {
+        logging::console::log_failure(&format!(
+            "Issue recording audit event({}) for room({})",
+            action, room_id
+        ));
+    }
+}
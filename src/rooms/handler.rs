@@ -1,31 +1,48 @@
-use super::permission_configs;
+use super::{audit, permission_configs};
 use crate::common::response_logic::send_to_requester_channel;
 use crate::communication::data_capturer::CaptureResult;
 use crate::communication::types::{
-    BasicResponse, GenericRoomIdAndPeerId, RoomPermissions, RoomUpdate, SpecialLeaveRoomOnDestroy,
-    VoiceServerClosePeer, VoiceServerCreateRoom, VoiceServerDestroyRoom, VoiceServerRequest,
+    BasicResponse, BlockUserFromRoom, DeafAndMuteStatusUpdate, GenericRoomIdAndPeerId,
+    KickUserFromRoom, ModMuteOrDeafenUser, RecordingState, RoomPermissions, RoomUpdate,
+    SpecialLeaveRoomOnDestroy, UserKicked, VoiceReconnectRequired, VoiceRequestTimedOut,
+    VoiceServerClosePeer, VoiceServerCreateRoom, VoiceServerDestroyRoom,
+    VoiceServerListRoomsAndPeers, VoiceServerRecording,
 };
 use crate::communication::{self, data_capturer, data_fetcher};
-use crate::data_store::db_models::{DBRoom, DBRoomBlock, DBRoomPermissions};
+use crate::data_store::db_models::{
+    DBPersistentRoom, DBRoom, DBRoomBlock, DBRoomPermissions, DBRoomRecording,
+};
 use crate::data_store::sql_execution_handler::ExecutionHandler;
 use crate::logging;
 use crate::rabbitmq::rabbit;
 use crate::state::owner_queue::OwnerQueue;
 use crate::state::state::ServerState;
-use crate::state::types::Room;
+use crate::state::types::{
+    ActiveRecording, PendingReconciliation, PendingVoiceRequest, Room, SpeakingState,
+    VoiceRequestRollback,
+};
+use crate::voice_servers::types::{RoomVoiceServerChanged, VoiceServerDrift};
+use crate::vs_response::types::{VoiceServerRequest, VoiceServerRequestOp};
 use crate::ws_fan::{self, fan};
+use crate::{board, cluster, voice_servers};
 use chrono::Utc;
 use futures::lock::Mutex;
 use lapin::Channel;
-use serde::Serialize;
 use std::collections::{HashMap, HashSet, LinkedList};
 use std::mem::drop;
-use std::sync::Arc;
+use std::sync::{Arc, OnceLock};
+use uuid::Uuid;
 pub type EncounteredError = bool;
 pub type AllPermissionsResult = (EncounteredError, HashMap<i32, RoomPermissions>);
 pub type ListenerOrSpeaker = String;
 pub type RoomOwnerAndSettings = (bool, i32, String);
 
+static KICK_COOLDOWN_MS: OnceLock<i64> = OnceLock::new();
+
+/// MERLIN_KICK_COOLDOWN_SECS is clamped to these
+pub const MIN_KICK_COOLDOWN_SECS: i64 = 1;
+pub const MAX_KICK_COOLDOWN_SECS: i64 = 7 * 24 * 60 * 60;
+
 // Managing rooms happens in a pub-sub fashion:
 //  - The client waits on the response from this server.
 //  - This server waits on the response from the voice server.
@@ -39,14 +56,38 @@ pub type RoomOwnerAndSettings = (bool, i32, String);
 //       the user is removed from the state of the server
 //       and this update is fanned/brodcasted across all users in the room.
 
+/// How long a voice server has to respond
+/// to a tracked request before it is rolled back.
+const VOICE_REQUEST_TIMEOUT_MS: i64 = 10000;
+
+/// Blocks can be permanent or expire after
+/// the requested duration.
 pub async fn block_user_from_room(
-    user_id: i32,
-    room_id: i32,
+    request_data: BlockUserFromRoom,
     requester_id: i32,
     server_state: &mut ServerState,
     execution_handler: &Arc<Mutex<ExecutionHandler>>,
     publish_channel: &Arc<Mutex<lapin::Channel>>,
 ) {
+    let room_id = request_data.room_id;
+    let user_id = request_data.user_id;
+    let expires_at =
+        match block_expires_at(request_data.duration_secs, Utc::now().timestamp_millis()) {
+            Some(expires_at) => expires_at,
+            None => {
+                logging::console::log_failure(&format!(
+                    "Invalid block duration from user({})",
+                    requester_id
+                ));
+                send_to_requester_channel(
+                    user_id.to_string(),
+                    requester_id,
+                    server_state,
+                    "issue_blocking_user".to_string(),
+                );
+                return;
+            }
+        };
     let mut handler = execution_handler.lock().await;
     let owner_gather: (bool, i32, String) =
         data_fetcher::get_room_owner_and_settings(&mut handler, &room_id).await;
@@ -64,8 +105,28 @@ pub async fn block_user_from_room(
             id: -1,
             owner_room_id: room_id.clone(),
             blocked_user_id: user_id.clone(),
+            expires_at: expires_at,
+            reason: request_data.reason,
+            blocked_by: Some(requester_id.clone()),
         };
         let capture_result = data_capturer::capture_new_room_block(&mut handler, &new_block).await;
+        if !capture_result.encountered_error {
+            audit::record(
+                &mut handler,
+                &room_id,
+                &requester_id,
+                Some(user_id.clone()),
+                "block_user",
+                Some(
+                    serde_json::json!({
+                        "reason": new_block.reason,
+                        "expires_at": new_block.expires_at,
+                    })
+                    .to_string(),
+                ),
+            )
+            .await;
+        }
         drop(handler);
         handle_user_block_capture_result(
             capture_result,
@@ -86,6 +147,312 @@ pub async fn block_user_from_room(
     );
 }
 
+/// Some(None) for permanent blocks, None if the duration
+/// isn't positive or the expiry doesn't fit in an i64.
+pub fn block_expires_at(duration_secs: Option<i64>, now: i64) -> Option<Option<i64>> {
+    let duration_secs = match duration_secs {
+        Some(duration_secs) => duration_secs,
+        None => return Some(None),
+    };
+    if duration_secs <= 0 {
+        return None;
+    }
+    let expires_at = duration_secs
+        .checked_mul(1000)
+        .and_then(|duration_ms| now.checked_add(duration_ms))?;
+    return Some(Some(expires_at));
+}
+
+/// Kicking removes the user from the room without
+/// a permanent block, they can rejoin once their
+/// cooldown(MERLIN_KICK_COOLDOWN_SECS, 60 by default) is over.
+/// Who can kick who follows the same rules as blocking.
+pub async fn kick_user_from_room(
+    request_data: KickUserFromRoom,
+    requester_id: i32,
+    server_state: &mut ServerState,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+) {
+    let room_id = request_data.room_id;
+    let user_id = request_data.user_id;
+    let reason = request_data.reason;
+    let mut handler = execution_handler.lock().await;
+    let owner_gather: (bool, i32, String) =
+        data_fetcher::get_room_owner_and_settings(&mut handler, &room_id).await;
+    let all_room_permissions =
+        data_fetcher::get_room_permissions_for_users(&room_id, &mut handler).await;
+    drop(handler);
+
+    if owner_gather.0
+        || all_room_permissions.0
+        || !can_block_this_user_from_room(
+            all_room_permissions.1,
+            owner_gather.1,
+            requester_id,
+            user_id,
+        )
+    {
+        send_to_requester_channel(
+            user_id.to_string(),
+            requester_id,
+            server_state,
+            "issue_kicking_user".to_string(),
+        );
+        return;
+    }
+    server_state.kick_cooldowns.insert(
+        (room_id.clone(), user_id.clone()),
+        Utc::now()
+            .timestamp_millis()
+            .saturating_add(kick_cooldown_millis()),
+    );
+    // broadcast before removal, so the kicked user
+    // knows why they were removed.
+    let response = BasicResponse {
+        response_op_code: "user_kicked".to_owned(),
+        response_containing_data: serde_json::to_string(&UserKicked {
+            user_id: user_id.clone(),
+            room_id: room_id.clone(),
+            kicked_by: requester_id.clone(),
+            reason: reason.clone(),
+        })
+        .unwrap(),
+    };
+    fan::broadcast_message_to_room(
+        serde_json::to_string(&response).unwrap(),
+        server_state,
+        room_id.clone(),
+    )
+    .await;
+    let request = VoiceServerClosePeer {
+        roomId: room_id.to_string(),
+        peerId: user_id.to_string(),
+        kicked: true,
+    };
+    remove_user_from_room_basic(request, server_state, publish_channel).await;
+    let mut handler = execution_handler.lock().await;
+    audit::record(
+        &mut handler,
+        &room_id,
+        &requester_id,
+        Some(user_id.clone()),
+        "kick_user",
+        Some(serde_json::json!({ "reason": reason }).to_string()),
+    )
+    .await;
+    drop(handler);
+    logging::console::log_success(&format!(
+        "user({}) kicked user({}) from room({})",
+        requester_id, user_id, room_id
+    ));
+}
+
+/// What a mod imposes on(or lifts from) a user
+#[derive(Clone, Copy, PartialEq)]
+pub enum ModVoiceAction {
+    Mute,
+    Unmute,
+    Deafen,
+    Undeafen,
+}
+
+impl ModVoiceAction {
+    pub fn from_op_code(op_code: &str) -> Option<Self> {
+        match op_code {
+            "mod_mute_user" => Some(Self::Mute),
+            "mod_unmute_user" => Some(Self::Unmute),
+            "mod_deafen_user" => Some(Self::Deafen),
+            "mod_undeafen_user" => Some(Self::Undeafen),
+            _ => None,
+        }
+    }
+
+    fn audit_action(&self) -> &'static str {
+        match self {
+            Self::Mute => "mod_mute",
+            Self::Unmute => "mod_unmute",
+            Self::Deafen => "mod_deafen",
+            Self::Undeafen => "mod_undeafen",
+        }
+    }
+}
+
+/// - Muting pauses the user's producer, deafening their consumers
+/// - The user can't lift it themselves, see update_mute_and_deaf_status
+/// - Lifting it only resumes what the user hasn't muted/deafened themselves
+/// - Same rules as blocking, mods can't do this to other mods/the owner
+pub async fn mod_mute_or_deafen_user(
+    request_data: ModMuteOrDeafenUser,
+    requester_id: i32,
+    action: ModVoiceAction,
+    server_state: &mut ServerState,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+) {
+    let room_id = request_data.room_id;
+    let user_id = request_data.user_id;
+    let mut handler = execution_handler.lock().await;
+    let owner_gather: (bool, i32, String) =
+        data_fetcher::get_room_owner_and_settings(&mut handler, &room_id).await;
+    let all_room_permissions =
+        data_fetcher::get_room_permissions_for_users(&room_id, &mut handler).await;
+    drop(handler);
+
+    if owner_gather.0
+        || all_room_permissions.0
+        || !can_block_this_user_from_room(
+            all_room_permissions.1,
+            owner_gather.1,
+            requester_id,
+            user_id,
+        )
+    {
+        send_to_requester_channel(
+            user_id.to_string(),
+            requester_id,
+            server_state,
+            "issue_muting_or_deafening_user".to_string(),
+        );
+        return;
+    }
+    let room = server_state.rooms.get_mut(&room_id).unwrap();
+    match action {
+        ModVoiceAction::Mute => room.muted.insert(user_id.clone()),
+        ModVoiceAction::Unmute => room.muted.remove(&user_id),
+        ModVoiceAction::Deafen => room.deaf.insert(user_id.clone()),
+        ModVoiceAction::Undeafen => room.deaf.remove(&user_id),
+    };
+    // the user's own flags are left alone, someone
+    // who muted themselves stays muted after the lift
+    let (self_muted, self_deafened) = server_state
+        .active_users
+        .get(&user_id)
+        .map_or((false, false), |user| (user.muted, user.deaf));
+    let data = GenericRoomIdAndPeerId {
+        roomId: room_id.clone(),
+        peerId: user_id.clone(),
+    };
+    let request_op = match action {
+        ModVoiceAction::Mute => Some(VoiceServerRequestOp::PauseProducer(data)),
+        ModVoiceAction::Unmute if !self_muted => Some(VoiceServerRequestOp::ResumeProducer(data)),
+        ModVoiceAction::Deafen => Some(VoiceServerRequestOp::PauseConsumers(data)),
+        ModVoiceAction::Undeafen if !self_deafened => {
+            Some(VoiceServerRequestOp::ResumeConsumers(data))
+        }
+        _ => None,
+    };
+    if let Some(request_op) = request_op {
+        let request_str = create_voice_server_request(&user_id.to_string(), request_op);
+        let channel = publish_channel.lock().await;
+        rabbit::publish_voice_message(
+            &channel,
+            &voice_servers::handler::consume_queue_for_room(server_state, &room_id),
+            request_str,
+        )
+        .await
+        .unwrap_or_default();
+        drop(channel);
+    }
+    broadcast_deaf_and_mute_update(server_state, &room_id, &user_id).await;
+    let mut handler = execution_handler.lock().await;
+    audit::record(
+        &mut handler,
+        &room_id,
+        &requester_id,
+        Some(user_id.clone()),
+        action.audit_action(),
+        None,
+    )
+    .await;
+    drop(handler);
+    logging::console::log_success(&format!(
+        "user({}) used {} on user({}) in room({})",
+        requester_id,
+        action.audit_action(),
+        user_id,
+        room_id
+    ));
+}
+
+/// (mod muted, mod deafened) in the user's current room
+pub fn mod_mute_and_deafen_state(server_state: &ServerState, user_id: &i32) -> (bool, bool) {
+    let room = server_state
+        .active_users
+        .get(user_id)
+        .and_then(|user| server_state.rooms.get(&user.current_room_id));
+    return match room {
+        Some(room) => (room.muted.contains(user_id), room.deaf.contains(user_id)),
+        None => (false, false),
+    };
+}
+
+async fn broadcast_deaf_and_mute_update(
+    server_state: &mut ServerState,
+    room_id: &i32,
+    user_id: &i32,
+) {
+    let (muted, deaf) = server_state
+        .active_users
+        .get(user_id)
+        .map_or((false, false), |user| (user.muted, user.deaf));
+    let (mod_muted, mod_deafened) = mod_mute_and_deafen_state(server_state, user_id);
+    let deaf_mute_response = DeafAndMuteStatusUpdate {
+        deaf: deaf,
+        muted: muted,
+        user_id: user_id.clone(),
+        mod_muted: mod_muted,
+        mod_deafened: mod_deafened,
+    };
+    let response = BasicResponse {
+        response_op_code: "user_mute_and_deaf_update".to_owned(),
+        response_containing_data: serde_json::to_string(&deaf_mute_response).unwrap(),
+    };
+    fan::broadcast_message_to_room(
+        serde_json::to_string(&response).unwrap(),
+        server_state,
+        room_id.clone(),
+    )
+    .await;
+}
+
+pub fn user_is_on_kick_cooldown(server_state: &ServerState, room_id: &i32, user_id: &i32) -> bool {
+    if let Some(rejoin_at) = server_state
+        .kick_cooldowns
+        .get(&(room_id.clone(), user_id.clone()))
+    {
+        return rejoin_at > &Utc::now().timestamp_millis();
+    }
+    return false;
+}
+
+/// Removes cooldowns that are over, so they
+/// don't pile up in memory.
+pub fn cleanup_kick_cooldowns(server_state: &mut ServerState) {
+    let now = Utc::now().timestamp_millis();
+    server_state
+        .kick_cooldowns
+        .retain(|_, rejoin_at| *rejoin_at > now);
+}
+
+/// Read once, see kick_cooldown_secs_to_millis
+fn kick_cooldown_millis() -> i64 {
+    return *KICK_COOLDOWN_MS.get_or_init(|| {
+        let seconds: i64 = std::env::var("MERLIN_KICK_COOLDOWN_SECS")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(60);
+        kick_cooldown_secs_to_millis(seconds)
+    });
+}
+
+pub fn kick_cooldown_secs_to_millis(seconds: i64) -> i64 {
+    return seconds
+        .clamp(MIN_KICK_COOLDOWN_SECS, MAX_KICK_COOLDOWN_SECS)
+        .checked_mul(1000)
+        .unwrap_or(i64::MAX);
+}
+
 pub async fn create_room(
     server_state: &mut ServerState,
     publish_channel: &Arc<Mutex<lapin::Channel>>,
@@ -94,6 +461,7 @@ pub async fn create_room(
     name: String,
     desc: String,
     public: bool,
+    persistent: bool,
 ) {
     let mut handler = execution_handler.lock().await;
     let db_room = DBRoom {
@@ -111,14 +479,49 @@ pub async fn create_room(
         );
         logging::console::log_failure(&format!("user({}) create room failure", requester_id));
     } else {
+        // claim the room, so the other instances
+        // know where to send users trying to join it.
+        data_capturer::capture_new_room_instance(
+            &mut handler,
+            &room_id,
+            &cluster::handler::config().instance_id,
+        )
+        .await;
+        // the creator can still read the audit log
+        // after the room is destroyed.
+        audit::record(
+            &mut handler,
+            &room_id,
+            &requester_id,
+            None,
+            "create_room",
+            None,
+        )
+        .await;
+        let mut new_room_state: Room =
+            construct_basic_room_for_state(room_id.clone(), public, name, desc, persistent);
+        if persistent {
+            let capture_result = data_capturer::capture_new_persistent_room(
+                &mut handler,
+                &construct_persistent_room_for_db(&new_room_state),
+            )
+            .await;
+            // fallback to a regular room, so our state
+            // matches what is in the database.
+            if capture_result.encountered_error {
+                new_room_state.persistent = false;
+                logging::console::log_failure(&format!(
+                    "room({}) could not be made persistent",
+                    room_id
+                ));
+            }
+        }
+        drop(handler);
         let channel = publish_channel.lock().await;
         continue_with_successful_room_creation(
-            room_id,
+            new_room_state,
             &channel,
-            public,
             server_state,
-            name,
-            desc,
             requester_id,
         )
         .await;
@@ -141,21 +544,493 @@ pub async fn destroy_room(
     drop(handler);
 
     // remove from state
+    let voice_server_queue = voice_servers::handler::consume_queue_for_room(server_state, room_id);
     server_state.rooms.remove(room_id);
     server_state.owner_queues.remove(room_id);
     // remove from voice server
     let request_to_voice_server = VoiceServerDestroyRoom {
         roomId: room_id.to_string(),
     };
-    let request_str =
-        create_voice_server_request("destroy-room", &"-1".to_owned(), request_to_voice_server);
+    let request_str = create_voice_server_request(
+        &"-1".to_owned(),
+        VoiceServerRequestOp::DestroyRoom(request_to_voice_server),
+    );
     let channel = publish_channel.lock().await;
-    rabbit::publish_voice_message(&channel, request_str)
+    rabbit::publish_voice_message(&channel, &voice_server_queue, request_str)
         .await
         .unwrap_or_default();
     logging::console::log_event(&format!("Destroyed room:{}", room_id));
 }
 
+/// - Only the owner can start or stop recording
+/// - Everyone in the room is told, users joining
+///     while recording have to consent first
+pub async fn start_or_stop_recording(
+    room_id: i32,
+    requester_id: i32,
+    start: bool,
+    server_state: &mut ServerState,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+) {
+    let mut handler = execution_handler.lock().await;
+    let is_owner = user_is_owner_of_room(requester_id, &mut handler, &room_id).await;
+    let current_recording_id = server_state
+        .rooms
+        .get(&room_id)
+        .and_then(|room| room.recording.as_ref())
+        .map(|recording| recording.recording_id.clone());
+    if !is_owner || current_recording_id.is_some() == start {
+        drop(handler);
+        send_to_requester_channel(
+            room_id.to_string(),
+            requester_id,
+            server_state,
+            "issue_updating_recording".to_string(),
+        );
+        return;
+    }
+    let now = Utc::now().timestamp_millis();
+    let recording_id = match current_recording_id {
+        Some(recording_id) => {
+            let capture_result =
+                data_capturer::capture_room_recording_stop(&mut handler, &room_id, &now).await;
+            if capture_result.encountered_error {
+                -1
+            } else {
+                recording_id
+            }
+        }
+        None => {
+            let recording = DBRoomRecording {
+                id: -1,
+                room_id: room_id.clone(),
+                started_by: requester_id.clone(),
+                started_at: now.clone(),
+                stopped_at: None,
+            };
+            data_capturer::capture_new_room_recording(&mut handler, &recording).await
+        }
+    };
+    if recording_id == -1 {
+        drop(handler);
+        send_to_requester_channel(
+            room_id.to_string(),
+            requester_id,
+            server_state,
+            "issue_updating_recording".to_string(),
+        );
+        return;
+    }
+    let audit_action = if start {
+        "start_recording"
+    } else {
+        "stop_recording"
+    };
+    audit::record(
+        &mut handler,
+        &room_id,
+        &requester_id,
+        None,
+        audit_action,
+        None,
+    )
+    .await;
+    drop(handler);
+
+    let room = server_state.rooms.get_mut(&room_id).unwrap();
+    // starting the recording counts as the requester's consent
+    room.recording = if start {
+        Some(ActiveRecording {
+            recording_id: recording_id.clone(),
+            started_by: requester_id.clone(),
+            started_at: now,
+            consents: HashSet::from([requester_id.clone()]),
+        })
+    } else {
+        None
+    };
+    let user_ids: Vec<i32> = room.user_ids.iter().cloned().collect();
+    let data = VoiceServerRecording {
+        roomId: room_id.to_string(),
+        recordingId: recording_id.to_string(),
+    };
+    let request_op = if start {
+        VoiceServerRequestOp::StartRecording(data)
+    } else {
+        VoiceServerRequestOp::StopRecording(data)
+    };
+    let request_str = create_voice_server_request(&requester_id.to_string(), request_op);
+    let channel = publish_channel.lock().await;
+    rabbit::publish_voice_message(
+        &channel,
+        &voice_servers::handler::consume_queue_for_room(server_state, &room_id),
+        request_str,
+    )
+    .await
+    .unwrap_or_default();
+    drop(channel);
+    let response = BasicResponse {
+        response_op_code: "recording_state".to_owned(),
+        response_containing_data: serde_json::to_string(&recording_state(
+            server_state.rooms.get(&room_id).unwrap(),
+        ))
+        .unwrap(),
+    };
+    fan::broadcast_message_to_room(
+        serde_json::to_string(&response).unwrap(),
+        server_state,
+        room_id.clone(),
+    )
+    .await;
+    // everyone already in the room is asked the same
+    // way joiners are, see accept_recording_consent
+    if start {
+        ask_for_recording_consent(server_state, &room_id, user_ids);
+    }
+    logging::console::log_success(&format!(
+        "user({}) used {} in room({})",
+        requester_id, audit_action, room_id
+    ));
+}
+
+/// Sends recording_consent_required to the users that
+/// haven't consented to the room's recording yet.
+pub fn ask_for_recording_consent(
+    server_state: &mut ServerState,
+    room_id: &i32,
+    user_ids: Vec<i32>,
+) {
+    let state = match server_state.rooms.get(room_id) {
+        Some(room) if room.recording.is_some() => recording_state(room),
+        _ => return,
+    };
+    let data = serde_json::to_string(&state).unwrap();
+    for user_id in user_ids {
+        if needs_recording_consent(server_state, room_id, &user_id) {
+            send_to_requester_channel(
+                data.clone(),
+                user_id,
+                server_state,
+                "recording_consent_required".to_string(),
+            );
+        }
+    }
+}
+
+pub fn recording_state(room: &Room) -> RecordingState {
+    return RecordingState {
+        room_id: room.room_id.clone(),
+        recording: room.recording.is_some(),
+        started_by: room
+            .recording
+            .as_ref()
+            .map(|recording| recording.started_by.clone()),
+        started_at: room
+            .recording
+            .as_ref()
+            .map(|recording| recording.started_at.clone()),
+    };
+}
+
+fn recording_id_for_room(room: &Room) -> Option<i32> {
+    return room
+        .recording
+        .as_ref()
+        .map(|recording| recording.recording_id.clone());
+}
+
+/// Users that were never asked(or declined) can't
+/// join a room that is being recorded.
+pub fn needs_recording_consent(server_state: &ServerState, room_id: &i32, user_id: &i32) -> bool {
+    return match server_state
+        .rooms
+        .get(room_id)
+        .and_then(|room| room.recording.as_ref())
+    {
+        Some(recording) => !recording.consents.contains(user_id),
+        None => false,
+    };
+}
+
+/// Returns false if the room isn't being recorded
+pub fn accept_recording_consent(
+    server_state: &mut ServerState,
+    room_id: &i32,
+    user_id: &i32,
+) -> bool {
+    return match server_state
+        .rooms
+        .get_mut(room_id)
+        .and_then(|room| room.recording.as_mut())
+    {
+        Some(recording) => {
+            recording.consents.insert(user_id.clone());
+            true
+        }
+        None => false,
+    };
+}
+
+/// Closes the recordings of persistent rooms
+/// that were deactivated while recording.
+pub async fn capture_pending_recording_stops(
+    server_state: &mut ServerState,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+) {
+    if server_state.pending_recording_stops.len() == 0 {
+        return;
+    }
+    let mut handler = execution_handler.lock().await;
+    for (room_id, stopped_at) in server_state.pending_recording_stops.drain(..) {
+        let capture_result =
+            data_capturer::capture_room_recording_stop(&mut handler, &room_id, &stopped_at).await;
+        if capture_result.encountered_error {
+            logging::console::log_failure(&format!(
+                "{} for room({})",
+                capture_result.desc, room_id
+            ));
+        }
+    }
+}
+
+/// Executed once on startup before any user can connect.
+/// The database still holds the rooms that existed before
+/// a restart(or crash), while our state starts empty. For
+/// the rooms this instance owns(or reclaims from dead instances):
+/// - persistent rooms are loaded back into state as inactive rooms.
+/// - every other room is purged along with its permissions/blocks.
+pub async fn reconcile_rooms_on_startup(
+    server_state: &mut ServerState,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+) {
+    let mut handler = execution_handler.lock().await;
+    let room_ids = data_fetcher::get_all_room_ids(&mut handler).await;
+    let persistent_rooms = data_fetcher::get_persistent_rooms(&mut handler).await;
+    let room_instances = data_fetcher::get_room_instances(&mut handler).await;
+    if room_ids.0 || persistent_rooms.0 || room_instances.0 {
+        logging::console::log_failure("Issue gathering rooms for startup reconciliation");
+        return;
+    }
+    let mut persistent_rooms_by_id: HashMap<i32, DBPersistentRoom> = persistent_rooms
+        .1
+        .into_iter()
+        .map(|persistent_room| (persistent_room.room_id.clone(), persistent_room))
+        .collect();
+    let clustered = cluster::handler::config().enabled;
+    let reclaimed = if clustered {
+        claim_rooms_from_dead_instances(&mut handler).await
+    } else {
+        Vec::new()
+    };
+    let mut num_rehydrated = 0;
+    let mut num_boards_restored = 0;
+    let mut num_purged = 0;
+    for room_id in room_ids.1 {
+        // rooms owned by live instances are theirs to handle,
+        // unclaimed rooms are from before clustered mode so only
+        // a standalone instance can be sure they're its own.
+        let owned = match room_instances.1.get(&room_id) {
+            Some(instance_id) => {
+                instance_id == &cluster::handler::config().instance_id
+                    || reclaimed.contains(&room_id)
+            }
+            None => !clustered,
+        };
+        if !owned {
+            continue;
+        }
+        let persistent_room = persistent_rooms_by_id.remove(&room_id);
+        match take_over_room(server_state, &mut handler, &room_id, persistent_room).await {
+            Some(boards_restored) => {
+                num_boards_restored += boards_restored;
+                num_rehydrated += 1;
+            }
+            None => num_purged += 1,
+        }
+    }
+    let orphaned = data_capturer::capture_orphaned_room_data_removal(&mut handler).await;
+    logging::console::log_event(&format!(
+        "Startup reconciliation: rehydrated {} persistent rooms({} boards), purged {} rooms, {} orphaned permissions and {} orphaned blocks",
+        num_rehydrated, num_boards_restored, num_purged, orphaned.0, orphaned.1
+    ));
+}
+
+/// Clustered mode only, rooms owned by instances that stopped
+/// sending heartbeats are taken over by whichever live
+/// instance claims them first.
+pub async fn reclaim_rooms_from_dead_instances(
+    server_state: &mut ServerState,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+) {
+    let mut handler = execution_handler.lock().await;
+    let reclaimed = claim_rooms_from_dead_instances(&mut handler).await;
+    if reclaimed.is_empty() {
+        return;
+    }
+    let persistent_rooms = data_fetcher::get_persistent_rooms(&mut handler).await;
+    if persistent_rooms.0 {
+        logging::console::log_failure("Issue gathering persistent rooms for reclaimed rooms");
+        return;
+    }
+    let mut persistent_rooms_by_id: HashMap<i32, DBPersistentRoom> = persistent_rooms
+        .1
+        .into_iter()
+        .map(|persistent_room| (persistent_room.room_id.clone(), persistent_room))
+        .collect();
+    for room_id in reclaimed {
+        let persistent_room = persistent_rooms_by_id.remove(&room_id);
+        take_over_room(server_state, &mut handler, &room_id, persistent_room).await;
+    }
+}
+
+/// Claims every room owned by a dead instance,
+/// returning the rooms we won.
+async fn claim_rooms_from_dead_instances(handler: &mut ExecutionHandler) -> Vec<i32> {
+    let instance_id = &cluster::handler::config().instance_id;
+    let expired_before = Utc::now().timestamp_millis() - cluster::handler::INSTANCE_EXPIRY_MS;
+    let expired =
+        data_fetcher::get_expired_room_instances(handler, instance_id, &expired_before).await;
+    let mut claimed = Vec::new();
+    for (room_id, dead_instance_id) in expired.1 {
+        let capture_result = data_capturer::capture_room_instance_claim(
+            handler,
+            &room_id,
+            instance_id,
+            &dead_instance_id,
+        )
+        .await;
+        if capture_result.encountered_error {
+            continue;
+        }
+        logging::console::log_event(&format!(
+            "Reclaimed room({}) from dead instance({})",
+            room_id, dead_instance_id
+        ));
+        claimed.push(room_id);
+    }
+    return claimed;
+}
+
+/// Persistent rooms this instance now owns are loaded back
+/// into state(returning the boards restored), every
+/// other room is purged(returning None).
+async fn take_over_room(
+    server_state: &mut ServerState,
+    handler: &mut ExecutionHandler,
+    room_id: &i32,
+    persistent_room: Option<DBPersistentRoom>,
+) -> Option<usize> {
+    if let Some(persistent_room) = persistent_room {
+        server_state.rooms.insert(
+            room_id.clone(),
+            construct_room_from_persistent_room(persistent_room),
+        );
+        // the voice server lost any recording with the room,
+        // the owner has to start a new one
+        handler
+            .update_open_room_recordings_stopped_at(room_id, &Utc::now().timestamp_millis())
+            .await
+            .unwrap_or_default();
+        return Some(board::handler::restore_boards_for_room(server_state, handler, room_id).await);
+    }
+    data_capturer::capture_room_removal(handler, room_id).await;
+    return None;
+}
+
+/// Persistent rooms are never destroyed when they
+/// become empty, instead only the voice server side
+/// of the room is removed. Everything else(database, state)
+/// is kept so the room can be reactivated when
+/// someone joins.
+pub async fn deactivate_room(
+    server_state: &mut ServerState,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+    room_id: &i32,
+) {
+    if let Some(room) = server_state.rooms.get_mut(room_id) {
+        room.active = false;
+        // the voice server drops the recording with the room
+        if room.recording.take().is_some() {
+            server_state
+                .pending_recording_stops
+                .push((room_id.clone(), Utc::now().timestamp_millis()));
+        }
+    }
+    let request_to_voice_server = VoiceServerDestroyRoom {
+        roomId: room_id.to_string(),
+    };
+    let request_str = create_voice_server_request(
+        &"-1".to_owned(),
+        VoiceServerRequestOp::DestroyRoom(request_to_voice_server),
+    );
+    let channel = publish_channel.lock().await;
+    rabbit::publish_voice_message(
+        &channel,
+        &voice_servers::handler::consume_queue_for_room(server_state, room_id),
+        request_str,
+    )
+    .await
+    .unwrap_or_default();
+    logging::console::log_event(&format!("Deactivated persistent room:{}", room_id));
+}
+
+/// Moves every room on a dead voice server to a live one,
+/// active rooms are recreated and everyone in them
+/// rejoins with the same permissions they had.
+pub async fn migrate_rooms_from_voice_server(
+    server_state: &mut ServerState,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    dead_voice_server_id: &String,
+) {
+    let room_ids: Vec<i32> = server_state
+        .rooms
+        .values()
+        .filter(|room| &room.voice_server_id == dead_voice_server_id)
+        .map(|room| room.room_id.clone())
+        .collect();
+    for room_id in room_ids {
+        let voice_server_id = voice_servers::handler::place_room(server_state);
+        let room = server_state.rooms.get_mut(&room_id).unwrap();
+        room.voice_server_id = voice_server_id.clone();
+        if !room.active {
+            continue;
+        }
+        let user_ids: Vec<i32> = room.user_ids.iter().cloned().collect();
+        let recording_id = recording_id_for_room(room);
+        let voice_server_queue =
+            voice_servers::handler::consume_queue_for_voice_server(server_state, &voice_server_id);
+        recreate_room_on_voice_server(
+            &room_id,
+            user_ids,
+            recording_id,
+            &voice_server_queue,
+            publish_channel,
+            execution_handler,
+        )
+        .await;
+        let response = BasicResponse {
+            response_op_code: "room_voice_server_changed".to_owned(),
+            response_containing_data: serde_json::to_string(&RoomVoiceServerChanged {
+                room_id: room_id.clone(),
+                voice_server_id: voice_server_id.clone(),
+            })
+            .unwrap(),
+        };
+        fan::broadcast_message_to_room(
+            serde_json::to_string(&response).unwrap(),
+            server_state,
+            room_id.clone(),
+        )
+        .await;
+        logging::console::log_event(&format!(
+            "Moved room({}) from voice server({}) to voice server({})",
+            room_id, dead_voice_server_id, voice_server_id
+        ));
+    }
+}
+
 pub async fn remove_user_from_room_basic(
     request_to_voice_server: VoiceServerClosePeer,
     server_state: &mut ServerState,
@@ -176,13 +1051,20 @@ pub async fn remove_user_from_room_basic(
     {
         user.current_room_id = -1;
     }
+    cluster::handler::announce_presence(
+        server_state,
+        &request_to_voice_server.peerId.parse().unwrap(),
+    );
+    let voice_server_queue = voice_servers::handler::consume_queue_for_room(
+        server_state,
+        &request_to_voice_server.roomId.parse().unwrap(),
+    );
     let request_str: String = create_voice_server_request(
-        "close-peer",
         &request_to_voice_server.peerId.clone(),
-        request_to_voice_server,
+        VoiceServerRequestOp::ClosePeer(request_to_voice_server),
     );
     let channel = publish_channel.lock().await;
-    rabbit::publish_voice_message(&channel, request_str)
+    rabbit::publish_voice_message(&channel, &voice_server_queue, request_str)
         .await
         .unwrap_or_default();
 }
@@ -224,15 +1106,22 @@ pub async fn join_room(
     // if the user has this permission
     if result == false {
         let channel = publish_channel.lock().await;
-        let request_str = create_voice_server_request(
-            type_of_join,
-            &request_to_voice_server.peerId.to_string(),
-            request_to_voice_server,
+        reactivate_room_if_needed(&room_id, &user_id, server_state, &channel).await;
+        let request_str = create_tracked_voice_server_request(
+            server_state,
+            &request_to_voice_server.peerId.clone(),
+            &room_id,
+            VoiceServerRequestOp::join(type_of_join == "join-as-speaker", request_to_voice_server),
+            VoiceRequestRollback::RemoveUserFromRoom,
         );
         add_user_to_room_state(&room_id, user_id, server_state);
-        rabbit::publish_voice_message(&channel, request_str)
-            .await
-            .unwrap_or_default();
+        rabbit::publish_voice_message(
+            &channel,
+            &voice_servers::handler::consume_queue_for_room(server_state, &room_id),
+            request_str,
+        )
+        .await
+        .unwrap_or_default();
 
         //make sure this user is now reflected in our queue
         //for next-in-line ownership
@@ -260,26 +1149,95 @@ pub async fn leave_room(
     server_state: &mut ServerState,
     requester_id: &i32,
     room_id: &i32,
+    transfer_boards_to: Option<i32>,
     voice_server_publish_channel: &Arc<Mutex<lapin::Channel>>,
     integration_publish_channel: &Arc<Mutex<lapin::Channel>>,
     execution_handler: &Arc<Mutex<ExecutionHandler>>,
 ) {
-    remove_all_owned_iot_servers(
-        server_state,
-        integration_publish_channel,
-        requester_id,
-        room_id.clone(),
-    )
-    .await;
+    let room_is_persistent = server_state
+        .rooms
+        .get(room_id)
+        .map_or(false, |room| room.persistent);
+    // the room owner takes over the boards instead of them being
+    // disconnected, unless the user picked someone else.
+    let owns_boards =
+        gather_owned_servers(server_state, room_id.clone(), requester_id.clone()).len() > 0;
+    // nothing to hand over if the user doesn't own any boards
+    let mut new_board_owner = if owns_boards {
+        transfer_boards_to
+    } else {
+        None
+    };
+    if owns_boards && new_board_owner.is_none() && !room_is_persistent {
+        let mut handler = execution_handler.lock().await;
+        let owner_gather = data_fetcher::get_room_owner_and_settings(&mut handler, room_id).await;
+        drop(handler);
+        if !owner_gather.0 {
+            new_board_owner = Some(owner_gather.1);
+        }
+    }
+    // handing the boards over means there is nothing left to disconnect,
+    // room owners leaving without picking someone still disconnect theirs.
+    if let Some(new_board_owner_id) = new_board_owner {
+        let new_owner_in_room = server_state
+            .rooms
+            .get(room_id)
+            .map_or(false, |room| room.user_ids.contains(&new_board_owner_id));
+        if new_owner_in_room && &new_board_owner_id != requester_id {
+            let mut handler = execution_handler.lock().await;
+            let transferred = board::handler::transfer_owned_boards(
+                server_state,
+                &mut handler,
+                room_id,
+                requester_id,
+                &new_board_owner_id,
+            )
+            .await;
+            for external_id in transferred.iter() {
+                audit::record(
+                    &mut handler,
+                    room_id,
+                    requester_id,
+                    Some(new_board_owner_id.clone()),
+                    "transfer_board_ownership",
+                    Some(serde_json::json!({ "external_id": external_id }).to_string()),
+                )
+                .await;
+            }
+            drop(handler);
+            if transferred.len() > 0 {
+                logging::console::log_success(&format!(
+                    "user({}) handed {} boards to user({}) in room({})",
+                    requester_id,
+                    transferred.len(),
+                    new_board_owner_id,
+                    room_id
+                ));
+            }
+        }
+    }
+    // persistent rooms keep their boards around
+    // while their owners are away.
+    if !room_is_persistent {
+        remove_all_owned_iot_servers(
+            server_state,
+            integration_publish_channel,
+            requester_id,
+            room_id.clone(),
+        )
+        .await;
+    }
     if let Some(user) = server_state.active_users.get_mut(&requester_id) {
         user.current_room_id = -1;
         let mut room = server_state.rooms.get_mut(room_id).unwrap();
         room.amount_of_users -= 1;
         room.user_ids.remove(&requester_id);
+        let room_is_empty = room.amount_of_users == 0;
+        cluster::handler::announce_presence(server_state, &requester_id);
         //Always cleanup empty rooms
         //this is handled during unexpected
         //disconnects too.
-        if room.amount_of_users == 0 {
+        if room_is_empty {
             let response = BasicResponse {
                 response_op_code: "you_left_room".to_owned(),
                 response_containing_data: serde_json::to_string(&SpecialLeaveRoomOnDestroy {
@@ -293,6 +1251,14 @@ pub async fn leave_room(
                 requester_id,
             )
             .await;
+            if room_is_persistent {
+                logging::console::log_success(&format!(
+                    "user({}) left room({}) which resulted in deactivation",
+                    requester_id, room_id
+                ));
+                deactivate_room(server_state, voice_server_publish_channel, room_id).await;
+                return;
+            }
             logging::console::log_success(&format!(
                 "user({}) left room({}) which resulted in destruction",
                 requester_id, room_id
@@ -312,8 +1278,13 @@ pub async fn leave_room(
                 room_id.clone(),
             )
             .await;
-            send_close_peer_request_not_kicked(room_id, requester_id, voice_server_publish_channel)
-                .await;
+            send_close_peer_request_not_kicked(
+                room_id,
+                requester_id,
+                server_state,
+                voice_server_publish_channel,
+            )
+            .await;
         }
     }
 }
@@ -363,16 +1334,27 @@ pub async fn add_speaker(
                 &mut handler,
             )
             .await;
+            audit::record(
+                &mut handler,
+                &room_id,
+                requester_id,
+                Some(user_id.clone()),
+                "add_speaker",
+                None,
+            )
+            .await;
             drop(handler);
-            let request_str = create_voice_server_request(
-                "add-speaker",
-                &user_id.to_string(),
+            let rollback = VoiceRequestRollback::RevertToListener {
+                asked_to_speak: requestee_permissions.asked_to_speak,
+                is_mod: requestee_permissions.is_mod,
+            };
+            send_add_speaker_request(
                 request_to_voice_server,
-            );
-            let channel = publish_channel.lock().await;
-            rabbit::publish_voice_message(&channel, request_str)
-                .await
-                .unwrap_or_default();
+                rollback,
+                server_state,
+                publish_channel,
+            )
+            .await;
             logging::console::log_success(&format!(
                 "user({}) added user({}) as to speakers",
                 requester_id, user_id
@@ -425,16 +1407,28 @@ pub async fn remove_speaker(
             );
             data_capturer::capture_new_room_permissions_update(&new_permissions, &mut handler)
                 .await;
+            audit::record(
+                &mut handler,
+                &room_id,
+                requester_id,
+                Some(user_id.clone()),
+                "remove_speaker",
+                None,
+            )
+            .await;
             drop(handler);
             let request_str = create_voice_server_request(
-                "remove-speaker",
                 &user_id.to_string(),
-                request_to_voice_server,
+                VoiceServerRequestOp::RemoveSpeaker(request_to_voice_server),
             );
             let channel = publish_channel.lock().await;
-            rabbit::publish_voice_message(&channel, request_str)
-                .await
-                .unwrap_or_default();
+            rabbit::publish_voice_message(
+                &channel,
+                &voice_servers::handler::consume_queue_for_room(server_state, &room_id),
+                request_str,
+            )
+            .await
+            .unwrap_or_default();
 
             //notify the users in the room.
             let basic_response = BasicResponse {
@@ -477,25 +1471,44 @@ pub async fn remove_speaker(
 ///  being transfered to the voice server.
 pub async fn handle_web_rtc_specific_requests(
     request_to_voice_server: serde_json::Value,
+    server_state: &ServerState,
     publish_channel: &Arc<Mutex<lapin::Channel>>,
     op_code: &str,
 ) {
     let user_id = request_to_voice_server["peerId"].to_string();
-    let request_str = create_voice_server_request(op_code, &user_id, request_to_voice_server);
+    // the room id is validated before we get here
+    let room_id: i32 = request_to_voice_server["roomId"]
+        .to_string()
+        .parse()
+        .unwrap();
+    let request_op = match VoiceServerRequestOp::web_rtc(op_code, request_to_voice_server) {
+        Some(request_op) => request_op,
+        None => return,
+    };
+    let request_str = create_voice_server_request(&user_id, request_op);
     let channel = publish_channel.lock().await;
-    rabbit::publish_voice_message(&channel, request_str)
-        .await
-        .unwrap_or_default();
+    rabbit::publish_voice_message(
+        &channel,
+        &voice_servers::handler::consume_queue_for_room(server_state, &room_id),
+        request_str,
+    )
+    .await
+    .unwrap_or_default();
 }
 
 // A user can only raise a hand if:
 // They aren't a speaker. If they do, it is a
 // clear illegal request, no need to
 // respond.
+//
+// Auto speaker rooms skip the asking stage
+// and promote the user right away, as long as
+// the room's speaker cap isn't reached.
 pub async fn raise_hand(
     server_state: &mut ServerState,
     room_id: &i32,
     requester_id: &i32,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
     execution_handler: &Arc<Mutex<ExecutionHandler>>,
 ) {
     let mut handler = execution_handler.lock().await;
@@ -510,6 +1523,40 @@ pub async fn raise_hand(
     if current_user_permissions.is_speaker {
         return;
     }
+    if room_can_auto_promote_speaker(server_state, room_id, &all_room_permissions.1) {
+        let new_db_permissions = permission_configs::create_non_preset(
+            room_id.clone(),
+            requester_id.clone(),
+            false,
+            true,
+            current_user_permissions.is_mod,
+        );
+        data_capturer::capture_new_room_permissions_update(&new_db_permissions, &mut handler).await;
+        drop(handler);
+        // the voice server responds with "you-are-now-a-speaker"
+        // which is broadcasted to the room, the same way
+        // a mod accepting the request is.
+        let request_to_voice_server = GenericRoomIdAndPeerId {
+            roomId: room_id.clone(),
+            peerId: requester_id.clone(),
+        };
+        let rollback = VoiceRequestRollback::RevertToListener {
+            asked_to_speak: current_user_permissions.asked_to_speak,
+            is_mod: current_user_permissions.is_mod,
+        };
+        send_add_speaker_request(
+            request_to_voice_server,
+            rollback,
+            server_state,
+            publish_channel,
+        )
+        .await;
+        logging::console::log_success(&format!(
+            "user({}) was auto promoted to speaker in room({})",
+            requester_id, room_id
+        ));
+        return;
+    }
     //no one should ever ask to speak if they are mods, because
     //the frontend will make the add speaker request on
     //the mod's behalf automatically which will be accepted
@@ -607,6 +1654,20 @@ pub async fn update_room_meta_data(
     execution_handler: &Arc<Mutex<ExecutionHandler>>,
     request_data: RoomUpdate,
 ) {
+    //a cap below one would keep everyone off stage
+    if request_data
+        .speaker_cap
+        .map_or(false, |speaker_cap| speaker_cap < 1)
+    {
+        logging::console::log_failure(&format!("Invalid speaker cap from user({})", requester_id));
+        send_to_requester_channel(
+            "issue with request".to_owned(),
+            requester_id.clone(),
+            server_state,
+            "invalid_request".to_owned(),
+        );
+        return;
+    }
     let mut handler = execution_handler.lock().await;
     let all_room_permissions: (bool, HashMap<i32, RoomPermissions>) =
         data_fetcher::get_room_permissions_for_users(room_id, &mut handler).await;
@@ -623,6 +1684,29 @@ pub async fn update_room_meta_data(
         room.public = request_data.public.clone();
         room.desc = request_data.description.clone();
         room.name = request_data.name.clone();
+        room.speaker_cap = request_data.speaker_cap.clone();
+        let was_persistent = room.persistent;
+        if let Some(persistent) = request_data.persistent {
+            room.persistent = persistent;
+        }
+        let now_persistent = room.persistent;
+        let persistent_room = construct_persistent_room_for_db(room);
+        capture_persistent_room_meta_data(
+            &mut handler,
+            was_persistent,
+            &persistent_room,
+            now_persistent,
+        )
+        .await;
+        audit::record(
+            &mut handler,
+            room_id,
+            &requester_id,
+            None,
+            "update_room_meta",
+            Some(serde_json::to_string(&request_data).unwrap()),
+        )
+        .await;
         //let the users know about the update
         let basic_response = BasicResponse {
             response_op_code: "room_meta_update".to_owned(),
@@ -683,9 +1767,16 @@ fn add_user_to_room_state(room_id: &i32, user_id: i32, state: &mut ServerState)
         .get_mut(&user_id)
         .unwrap()
         .current_room_id = room_id.clone();
+    cluster::handler::announce_presence(state, &user_id);
 }
 
-fn construct_basic_room_for_state(room_id: i32, public: bool, name: String, desc: String) -> Room {
+fn construct_basic_room_for_state(
+    room_id: i32,
+    public: bool,
+    name: String,
+    desc: String,
+    persistent: bool,
+) -> Room {
     return Room {
         room_id: room_id,
         muted: HashSet::new(),
@@ -695,29 +1786,137 @@ fn construct_basic_room_for_state(room_id: i32, public: bool, name: String, desc
         user_ids: HashSet::new(),
         public: public,
         auto_speaker: true,
+        speaker_cap: None,
+        persistent: persistent,
+        active: true,
         amount_of_users: 0,
         name: name,
         desc: desc,
         chat_throttle: 1000,
         created_at: Utc::now().to_string(),
         iot_server_connections: HashMap::new(),
+        speaking: SpeakingState::default(),
+        recording: None,
+    };
+}
+
+fn construct_persistent_room_for_db(room: &Room) -> DBPersistentRoom {
+    return DBPersistentRoom {
+        id: -1,
+        room_id: room.room_id.clone(),
+        name: room.name.clone(),
+        desc: room.desc.clone(),
+        public: room.public,
+        auto_speaker: room.auto_speaker,
+        chat_throttle: room.chat_throttle.clone(),
+        created_at: room.created_at.clone(),
+        speaker_cap: room.speaker_cap,
     };
 }
 
+/// Rehydrated rooms start inactive, the voice server
+/// room is recreated once someone joins.
+fn construct_room_from_persistent_room(persistent_room: DBPersistentRoom) -> Room {
+    let mut room = construct_basic_room_for_state(
+        persistent_room.room_id,
+        persistent_room.public,
+        persistent_room.name,
+        persistent_room.desc,
+        true,
+    );
+    room.auto_speaker = persistent_room.auto_speaker;
+    room.chat_throttle = persistent_room.chat_throttle;
+    room.speaker_cap = persistent_room.speaker_cap;
+    room.created_at = persistent_room.created_at;
+    room.active = false;
+    return room;
+}
+
+/// Keeps the persisted room metadata in line with
+/// the room's current persistence setting.
+async fn capture_persistent_room_meta_data(
+    handler: &mut ExecutionHandler,
+    was_persistent: bool,
+    persistent_room: &DBPersistentRoom,
+    now_persistent: bool,
+) {
+    let capture_result = match (was_persistent, now_persistent) {
+        (true, true) => {
+            data_capturer::capture_persistent_room_update(handler, persistent_room).await
+        }
+        (false, true) => data_capturer::capture_new_persistent_room(handler, persistent_room).await,
+        (true, false) => {
+            data_capturer::capture_persistent_room_removal(handler, &persistent_room.room_id).await
+        }
+        (false, false) => return,
+    };
+    if capture_result.encountered_error {
+        logging::console::log_failure(&capture_result.desc);
+    }
+}
+
+/// Persistent rooms that were emptied out no longer
+/// exist on the voice server, so the room needs to be
+/// recreated before anyone can join it.
+async fn reactivate_room_if_needed(
+    room_id: &i32,
+    user_id: &i32,
+    server_state: &mut ServerState,
+    channel: &Channel,
+) {
+    if let Some(room) = server_state.rooms.get(room_id) {
+        if room.active {
+            return;
+        }
+    }
+    // the voice server the room was on may be gone,
+    // so the room is placed again.
+    let voice_server_id = voice_servers::handler::place_room(server_state);
+    if let Some(room) = server_state.rooms.get_mut(room_id) {
+        room.active = true;
+        room.voice_server_id = voice_server_id;
+    }
+    if !server_state.owner_queues.contains_key(room_id) {
+        server_state.owner_queues.insert(
+            room_id.clone(),
+            OwnerQueue {
+                user_queue: LinkedList::new(),
+                room_id: room_id.clone(),
+            },
+        );
+    }
+    let request_to_voice_server = VoiceServerCreateRoom {
+        roomId: room_id.to_string(),
+    };
+    let request_str = create_voice_server_request(
+        &user_id.to_string(),
+        VoiceServerRequestOp::CreateRoom(request_to_voice_server),
+    );
+    rabbit::publish_voice_message(
+        channel,
+        &voice_servers::handler::consume_queue_for_room(server_state, room_id),
+        request_str,
+    )
+    .await
+    .unwrap_or_default();
+    logging::console::log_success(&format!(
+        "user({}) reactivated persistent room({})",
+        user_id, room_id
+    ));
+}
+
 /// executed after database insertion is proven to be successful.
 async fn continue_with_successful_room_creation(
-    room_id: i32,
+    mut new_room_state: Room,
     channel: &Channel,
-    public: bool,
     server_state: &mut ServerState,
-    name: String,
-    desc: String,
     user_id: i32,
 ) {
+    let room_id = new_room_state.room_id.clone();
     let request_to_voice_server = VoiceServerCreateRoom {
         roomId: room_id.clone().to_string(),
     };
-    let new_room_state: Room = construct_basic_room_for_state(room_id.clone(), public, name, desc);
+    new_room_state.voice_server_id = voice_servers::handler::place_room(server_state);
     server_state.rooms.insert(room_id, new_room_state);
     server_state.owner_queues.insert(
         room_id,
@@ -726,11 +1925,20 @@ async fn continue_with_successful_room_creation(
             room_id: room_id,
         },
     );
-    let request_str =
-        create_voice_server_request("create-room", &user_id.to_string(), request_to_voice_server);
-    rabbit::publish_voice_message(channel, request_str)
-        .await
-        .unwrap_or_default();
+    let request_str = create_tracked_voice_server_request(
+        server_state,
+        &user_id,
+        &room_id,
+        VoiceServerRequestOp::CreateRoom(request_to_voice_server),
+        VoiceRequestRollback::Nothing,
+    );
+    rabbit::publish_voice_message(
+        channel,
+        &voice_servers::handler::consume_queue_for_room(server_state, &room_id),
+        request_str,
+    )
+    .await
+    .unwrap_or_default();
     logging::console::log_success(&format!(
         "user({}) successfully created room({})",
         user_id, room_id
@@ -748,6 +1956,20 @@ async fn check_or_insert_initial_permissions(
         // if the user already has permissions
         if permissions.1.contains_key(requester_id) {
             let current_user_permissions = permissions.1.get(&requester_id).unwrap();
+            // the owner re-entering their empty persistent
+            // room gets mod back on their existing row
+            if room.persistent
+                && room.user_ids.len() == 0
+                && !current_user_permissions.is_mod
+                && user_is_owner_of_room(requester_id.clone(), handler, &room.room_id).await
+            {
+                let owner_permissions =
+                    permission_configs::modded_speaker(room.room_id.clone(), requester_id.clone());
+                let result =
+                    data_capturer::capture_new_room_permissions_update(&owner_permissions, handler)
+                        .await;
+                return result.encountered_error;
+            }
             // If the user is requesting to join as speaker:
             // - But isn't a speaker in the database and the room is auto speaker
             //    we accept this request because the user could have been a peer
@@ -786,8 +2008,12 @@ async fn create_initial_user_permissions(
 ) -> EncounteredError {
     //this is the first person in the room
     //aka the owner so they must have mod
-    //permissions
-    if room.user_ids.len() == 0 {
+    //permissions, persistent rooms can be
+    //empty so we have to check the owner.
+    if room.user_ids.len() == 0
+        && (room.persistent == false
+            || user_is_owner_of_room(requester_id.clone(), handler, &room.room_id).await)
+    {
         let init_permissions =
             permission_configs::modded_speaker(room.room_id.clone(), requester_id.clone());
         let result = data_capturer::capture_new_room_permissions(&init_permissions, handler).await;
@@ -814,15 +2040,398 @@ async fn create_initial_user_permissions(
     }
 }
 
-fn create_voice_server_request<T: Serialize>(op_code: &str, uid: &String, data: T) -> String {
+fn create_voice_server_request(uid: &String, op: VoiceServerRequestOp) -> String {
+    return serialize_voice_server_request(uid, op, Uuid::new_v4().to_string());
+}
+
+/// Same as create_voice_server_request, but the request
+/// is remembered until the voice server responds to it,
+/// if it never does the rollback is applied.
+/// Voice servers that don't echo request ids are never
+/// tracked, every request to them would be rolled back.
+fn create_tracked_voice_server_request(
+    server_state: &mut ServerState,
+    user_id: &i32,
+    room_id: &i32,
+    op: VoiceServerRequestOp,
+    rollback: VoiceRequestRollback,
+) -> String {
+    let request_id = Uuid::new_v4().to_string();
+    if !voice_servers::handler::echoes_request_id(server_state, room_id) {
+        return serialize_voice_server_request(&user_id.to_string(), op, request_id);
+    }
+    server_state.pending_voice_requests.insert(
+        request_id.clone(),
+        PendingVoiceRequest {
+            op: op.op_code().to_owned(),
+            user_id: user_id.clone(),
+            room_id: room_id.clone(),
+            sent_at: Utc::now().timestamp_millis(),
+            rollback: rollback,
+        },
+    );
+    return serialize_voice_server_request(&user_id.to_string(), op, request_id);
+}
+
+fn serialize_voice_server_request(
+    uid: &String,
+    op: VoiceServerRequestOp,
+    request_id: String,
+) -> String {
     let voice_server_req = VoiceServerRequest {
-        op: op_code.to_owned(),
+        op: op,
         uid: uid.to_owned(),
-        d: data,
+        request_id: request_id,
     };
     return serde_json::to_string(&voice_server_req).unwrap();
 }
 
+/// Recreates a room on a voice server, everyone
+/// in it rejoins with the same permissions they had.
+/// The recording(if any) is started again on the new room
+/// with the same id, so it continues the same room_recording row.
+async fn recreate_room_on_voice_server(
+    room_id: &i32,
+    user_ids: Vec<i32>,
+    recording_id: Option<i32>,
+    voice_server_queue: &String,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+) {
+    let request_str = create_voice_server_request(
+        &"-1".to_owned(),
+        VoiceServerRequestOp::CreateRoom(VoiceServerCreateRoom {
+            roomId: room_id.to_string(),
+        }),
+    );
+    let channel = publish_channel.lock().await;
+    rabbit::publish_voice_message(&channel, voice_server_queue, request_str)
+        .await
+        .unwrap_or_default();
+    drop(channel);
+    rejoin_voice_server(
+        room_id,
+        user_ids,
+        voice_server_queue,
+        publish_channel,
+        execution_handler,
+    )
+    .await;
+    if let Some(recording_id) = recording_id {
+        let request_str = create_voice_server_request(
+            &"-1".to_owned(),
+            VoiceServerRequestOp::StartRecording(VoiceServerRecording {
+                roomId: room_id.to_string(),
+                recordingId: recording_id.to_string(),
+            }),
+        );
+        let channel = publish_channel.lock().await;
+        rabbit::publish_voice_message(&channel, voice_server_queue, request_str)
+            .await
+            .unwrap_or_default();
+    }
+}
+
+async fn rejoin_voice_server(
+    room_id: &i32,
+    user_ids: Vec<i32>,
+    voice_server_queue: &String,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+) {
+    let mut handler = execution_handler.lock().await;
+    let all_room_permissions: (bool, HashMap<i32, RoomPermissions>) =
+        data_fetcher::get_room_permissions_for_users(room_id, &mut handler).await;
+    drop(handler);
+    let channel = publish_channel.lock().await;
+    for user_id in user_ids {
+        let is_speaker = all_room_permissions
+            .1
+            .get(&user_id)
+            .map_or(false, |permissions| permissions.is_speaker);
+        let request_str = create_voice_server_request(
+            &user_id.to_string(),
+            VoiceServerRequestOp::join(
+                is_speaker,
+                GenericRoomIdAndPeerId {
+                    roomId: room_id.clone(),
+                    peerId: user_id.clone(),
+                },
+            ),
+        );
+        rabbit::publish_voice_message(&channel, voice_server_queue, request_str)
+            .await
+            .unwrap_or_default();
+    }
+}
+
+/// Asks every voice server in use for its rooms and peers,
+/// the reports are handled by reconcile_voice_server.
+pub async fn request_voice_server_states(
+    server_state: &mut ServerState,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+) {
+    let channel = publish_channel.lock().await;
+    for voice_server_id in voice_servers::handler::voice_server_ids_in_use(server_state) {
+        let request_id = Uuid::new_v4().to_string();
+        server_state.pending_reconciliations.insert(
+            request_id.clone(),
+            PendingReconciliation {
+                voice_server_id: voice_server_id.clone(),
+                sent_at: Utc::now().timestamp_millis(),
+                expected: voice_servers::handler::expected_rooms_and_peers(
+                    server_state,
+                    &voice_server_id,
+                ),
+                reported: None,
+            },
+        );
+        let request_str = serialize_voice_server_request(
+            &"-1".to_owned(),
+            VoiceServerRequestOp::ListRoomsAndPeers(VoiceServerListRoomsAndPeers {}),
+            request_id,
+        );
+        rabbit::publish_voice_message(
+            &channel,
+            &voice_servers::handler::consume_queue_for_voice_server(server_state, &voice_server_id),
+            request_str,
+        )
+        .await
+        .unwrap_or_default();
+    }
+}
+
+/// Fixes the drift between a voice server and our state:
+/// - missing rooms are recreated with everyone rejoining
+/// - missing peers rejoin their room
+/// - unknown peers are closed
+/// - unknown rooms are destroyed
+/// Users who rejoined need to reconnect their transports.
+pub async fn reconcile_voice_server(
+    server_state: &mut ServerState,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    voice_server_id: &String,
+    drift: VoiceServerDrift,
+) {
+    if drift == VoiceServerDrift::default() {
+        return;
+    }
+    logging::console::log_failure(&format!(
+        "Voice server({}) drifted: {:?}",
+        voice_server_id, drift
+    ));
+    let voice_server_queue =
+        voice_servers::handler::consume_queue_for_voice_server(server_state, voice_server_id);
+    let mut to_reconnect: Vec<(i32, i32)> = Vec::new();
+    for room_id in drift.missing_rooms {
+        let user_ids: Vec<i32> = server_state
+            .rooms
+            .get(&room_id)
+            .map_or(Vec::new(), |room| room.user_ids.iter().cloned().collect());
+        let recording_id = server_state
+            .rooms
+            .get(&room_id)
+            .and_then(recording_id_for_room);
+        recreate_room_on_voice_server(
+            &room_id,
+            user_ids.clone(),
+            recording_id,
+            &voice_server_queue,
+            publish_channel,
+            execution_handler,
+        )
+        .await;
+        for user_id in user_ids {
+            to_reconnect.push((room_id.clone(), user_id));
+        }
+    }
+    for (room_id, user_id) in drift.missing_peers {
+        rejoin_voice_server(
+            &room_id,
+            vec![user_id.clone()],
+            &voice_server_queue,
+            publish_channel,
+            execution_handler,
+        )
+        .await;
+        to_reconnect.push((room_id, user_id));
+    }
+    let channel = publish_channel.lock().await;
+    for (room_id, user_id) in drift.unknown_peers {
+        let request_str = create_voice_server_request(
+            &user_id.to_string(),
+            VoiceServerRequestOp::ClosePeer(VoiceServerClosePeer {
+                roomId: room_id.to_string(),
+                peerId: user_id.to_string(),
+                kicked: false,
+            }),
+        );
+        rabbit::publish_voice_message(&channel, &voice_server_queue, request_str)
+            .await
+            .unwrap_or_default();
+    }
+    for room_id in drift.unknown_rooms {
+        let request_str = create_voice_server_request(
+            &"-1".to_owned(),
+            VoiceServerRequestOp::DestroyRoom(VoiceServerDestroyRoom {
+                roomId: room_id.to_string(),
+            }),
+        );
+        rabbit::publish_voice_message(&channel, &voice_server_queue, request_str)
+            .await
+            .unwrap_or_default();
+    }
+    drop(channel);
+    for (room_id, user_id) in to_reconnect {
+        let response = BasicResponse {
+            response_op_code: "voice_reconnect_required".to_owned(),
+            response_containing_data: serde_json::to_string(&VoiceReconnectRequired {
+                room_id: room_id,
+            })
+            .unwrap(),
+        };
+        fan::broadcast_message_to_single_user(
+            serde_json::to_string(&response).unwrap(),
+            server_state,
+            &user_id,
+        )
+        .await;
+    }
+}
+
+/// Removes and returns the requests the voice
+/// servers didn't respond to in time.
+pub fn take_timed_out_voice_requests(server_state: &mut ServerState) -> Vec<PendingVoiceRequest> {
+    let now = Utc::now().timestamp_millis();
+    let timed_out_ids: Vec<String> = server_state
+        .pending_voice_requests
+        .iter()
+        .filter(|(_, pending)| now - pending.sent_at > VOICE_REQUEST_TIMEOUT_MS)
+        .map(|(request_id, _)| request_id.clone())
+        .collect();
+    return timed_out_ids
+        .iter()
+        .filter_map(|request_id| server_state.pending_voice_requests.remove(request_id))
+        .collect();
+}
+
+/// Undoes the optimistic changes made for a
+/// request that timed out and lets the requester know.
+pub async fn rollback_timed_out_voice_request(
+    server_state: &mut ServerState,
+    voice_server_publish_channel: &Arc<Mutex<lapin::Channel>>,
+    integration_publish_channel: &Arc<Mutex<lapin::Channel>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+    pending: PendingVoiceRequest,
+) {
+    match pending.rollback {
+        // leaving like any other user, so the owner queue
+        // and empty room cleanup still happen.
+        VoiceRequestRollback::RemoveUserFromRoom => {
+            let user_is_in_room = server_state
+                .rooms
+                .get(&pending.room_id)
+                .map_or(false, |room| room.user_ids.contains(&pending.user_id));
+            if user_is_in_room {
+                leave_room(
+                    server_state,
+                    &pending.user_id,
+                    &pending.room_id,
+                    None,
+                    voice_server_publish_channel,
+                    integration_publish_channel,
+                    execution_handler,
+                )
+                .await;
+            }
+        }
+        VoiceRequestRollback::RevertToListener {
+            asked_to_speak,
+            is_mod,
+        } => {
+            let previous_permissions = permission_configs::create_non_preset(
+                pending.room_id.clone(),
+                pending.user_id.clone(),
+                asked_to_speak,
+                false,
+                is_mod,
+            );
+            let mut handler = execution_handler.lock().await;
+            data_capturer::capture_new_room_permissions_update(&previous_permissions, &mut handler)
+                .await;
+        }
+        VoiceRequestRollback::Nothing => {}
+    }
+    logging::console::log_failure(&format!(
+        "voice server never responded to {} for user({}) in room({})",
+        pending.op, pending.user_id, pending.room_id
+    ));
+    send_to_requester_channel(
+        serde_json::to_string(&VoiceRequestTimedOut {
+            op: pending.op,
+            room_id: pending.room_id,
+        })
+        .unwrap(),
+        pending.user_id,
+        server_state,
+        "voice_request_timed_out".to_owned(),
+    );
+}
+
+async fn send_add_speaker_request(
+    request_to_voice_server: GenericRoomIdAndPeerId,
+    rollback: VoiceRequestRollback,
+    server_state: &mut ServerState,
+    publish_channel: &Arc<Mutex<lapin::Channel>>,
+) {
+    let voice_server_queue = voice_servers::handler::consume_queue_for_room(
+        server_state,
+        &request_to_voice_server.roomId,
+    );
+    let request_str = create_tracked_voice_server_request(
+        server_state,
+        &request_to_voice_server.peerId.clone(),
+        &request_to_voice_server.roomId.clone(),
+        VoiceServerRequestOp::AddSpeaker(request_to_voice_server),
+        rollback,
+    );
+    let channel = publish_channel.lock().await;
+    rabbit::publish_voice_message(&channel, &voice_server_queue, request_str)
+        .await
+        .unwrap_or_default();
+}
+
+/// A room can only auto promote a user if:
+/// - auto speaker is turned on
+/// - the amount of speakers currently in the room
+///   is under the speaker cap(if there is one)
+fn room_can_auto_promote_speaker(
+    server_state: &ServerState,
+    room_id: &i32,
+    permissions: &HashMap<i32, RoomPermissions>,
+) -> bool {
+    if let Some(room) = server_state.rooms.get(room_id) {
+        if room.auto_speaker == false {
+            return false;
+        }
+        if let Some(speaker_cap) = room.speaker_cap {
+            let current_speakers = room
+                .user_ids
+                .iter()
+                .filter(|user_id| {
+                    permissions
+                        .get(user_id)
+                        .map_or(false, |user_permissions| user_permissions.is_speaker)
+                })
+                .count() as i32;
+            return current_speakers < speaker_cap;
+        }
+        return true;
+    }
+    return false;
+}
+
 /// When Users can only be removed from speaker:
 /// - If the owner requests(doesn't matter if the user is a mod)
 /// - If the person being removed is not a mod and the requester is a mod
@@ -1046,6 +2655,7 @@ pub fn can_block_this_user_from_room(
 async fn send_close_peer_request_not_kicked(
     room_id: &i32,
     requester_id: &i32,
+    server_state: &ServerState,
     voice_server_publish_channel: &Arc<Mutex<lapin::Channel>>,
 ) {
     let request_to_voice_server = VoiceServerClosePeer {
@@ -1054,13 +2664,16 @@ async fn send_close_peer_request_not_kicked(
         kicked: false,
     };
     let request_str: String = create_voice_server_request(
-        "close-peer",
         &requester_id.to_string(),
-        request_to_voice_server,
+        VoiceServerRequestOp::ClosePeer(request_to_voice_server),
     );
     let channel = voice_server_publish_channel.lock().await;
-    rabbit::publish_voice_message(&channel, request_str)
-        .await
-        .unwrap_or_default();
+    rabbit::publish_voice_message(
+        &channel,
+        &voice_servers::handler::consume_queue_for_room(server_state, room_id),
+        request_str,
+    )
+    .await
+    .unwrap_or_default();
     logging::console::log_success(&format!("user({}) left room({})", requester_id, room_id));
 }
@@ -0,0 +1,91 @@
+use crate::common::throttle;
+use crate::communication::types::{BasicResponse, RoomSpeakingUpdate};
+use crate::state::state::ServerState;
+use crate::state::types::Room;
+use crate::ws_fan::fan;
+use chrono::Utc;
+use std::collections::HashMap;
+
+/// Speaking updates are fanned out at most
+/// once per window for each room.
+pub const SPEAKING_FAN_OUT_THROTTLE_MS: i64 = 300;
+
+/// Only users in the room can be the active speaker.
+pub fn set_active_speaker(room: &mut Room, user_id: Option<i32>) {
+    let user_id = user_id.filter(|user_id| room.user_ids.contains(user_id));
+    if room.speaking.active_speaker != user_id {
+        room.speaking.active_speaker = user_id;
+        room.speaking.throttle.dirty = true;
+    }
+}
+
+/// The voice server only reports the users above its
+/// volume threshold, everyone else is silent.
+pub fn set_audio_levels(room: &mut Room, levels: Vec<(i32, f64)>) {
+    let audio_levels: HashMap<i32, f64> = levels
+        .into_iter()
+        .filter(|(user_id, _)| room.user_ids.contains(user_id))
+        .collect();
+    if room.speaking.audio_levels != audio_levels {
+        room.speaking.audio_levels = audio_levels;
+        room.speaking.throttle.dirty = true;
+    }
+}
+
+pub fn currently_speaking(room: &Room) -> Vec<i32> {
+    let mut user_ids: Vec<i32> = room
+        .speaking
+        .audio_levels
+        .keys()
+        .filter(|user_id| room.user_ids.contains(user_id))
+        .cloned()
+        .collect();
+    user_ids.sort();
+    return user_ids;
+}
+
+/// Fans out the room's speaking state if it changed and
+/// the throttle window passed, otherwise the change waits
+/// for the next update or the flush task.
+pub async fn fan_out_if_due(state: &mut ServerState, room_id: &i32) {
+    let now = Utc::now().timestamp_millis();
+    let room = match state.rooms.get_mut(room_id) {
+        Some(room) => room,
+        None => return,
+    };
+    if !throttle::take_if_due(
+        &mut room.speaking.throttle,
+        now,
+        SPEAKING_FAN_OUT_THROTTLE_MS,
+    ) {
+        return;
+    }
+    let update = RoomSpeakingUpdate {
+        room_id: room_id.clone(),
+        active_speaker: room.speaking.active_speaker,
+        currently_speaking: currently_speaking(room),
+        audio_levels: room.speaking.audio_levels.clone(),
+    };
+    let response = BasicResponse {
+        response_op_code: "room_speaking_update".to_owned(),
+        response_containing_data: serde_json::to_string(&update).unwrap(),
+    };
+    fan::broadcast_message_to_room(
+        serde_json::to_string(&response).unwrap(),
+        state,
+        room_id.clone(),
+    )
+    .await;
+}
+
+pub async fn flush_throttled_updates(state: &mut ServerState) {
+    let room_ids: Vec<i32> = state
+        .rooms
+        .values()
+        .filter(|room| room.speaking.throttle.dirty)
+        .map(|room| room.room_id.clone())
+        .collect();
+    for room_id in room_ids {
+        fan_out_if_due(state, &room_id).await;
+    }
+}
@@ -3,14 +3,15 @@ use crate::auth::authentication_handler::CodeParams;
 use crate::auth::oauth_locations;
 use crate::auth::ws_auth_handler::UserIdAndNewAuthCredentials;
 use crate::auth::{authentication_handler, ws_auth_handler};
-use crate::communication::router;
 use crate::communication::types::{AuthCredentials, AuthResponse, BasicResponse};
+use crate::communication::{data_capturer, data_fetcher, router};
 use crate::data_store::sql_execution_handler::ExecutionHandler;
+use crate::integration::credentials;
 use crate::rabbitmq::rabbit;
 use crate::state::state::ServerState;
 use crate::state::types::User;
 use crate::warp::http::Uri;
-use crate::{logging, rooms};
+use crate::{board, cluster, integration, logging, rooms, voice_servers};
 use futures::lock::Mutex;
 use futures_util::stream::SplitStream;
 use futures_util::{stream::SplitSink, SinkExt, StreamExt, TryFutureExt};
@@ -33,6 +34,7 @@ pub async fn start_server<T: Into<SocketAddr>>(addr: T, tls_or_not: String) {
     let server_state: Arc<RwLock<ServerState>> = Arc::new(RwLock::new(ServerState::new()));
     let execution_handler: Arc<Mutex<ExecutionHandler>> =
         Arc::new(Mutex::new(setup_execution_handler().await.unwrap()));
+    reconcile_state_with_database(&server_state, &execution_handler).await;
     let rabbit_connection: Connection = rabbit::setup_rabbit_connection().await.unwrap();
     let voice_publish_channel: Arc<Mutex<lapin::Channel>> = Arc::new(Mutex::new(
         rabbit::setup_voice_publish_channel(&rabbit_connection)
@@ -50,10 +52,51 @@ pub async fn start_server<T: Into<SocketAddr>>(addr: T, tls_or_not: String) {
         execution_handler.clone(),
     );
     setup_room_queue_cleanup_task(server_state.clone());
-    rabbit::setup_integration_consume_task(&rabbit_connection, server_state.clone())
+    setup_instance_heartbeat_task(server_state.clone(), execution_handler.clone());
+    setup_room_block_expiry_task(execution_handler.clone());
+    setup_passive_history_retention_task(execution_handler.clone());
+    setup_voice_request_timeout_task(
+        server_state.clone(),
+        voice_publish_channel.clone(),
+        integration_publish_channel.clone(),
+        execution_handler.clone(),
+    );
+    setup_voice_server_cleanup_task(
+        server_state.clone(),
+        voice_publish_channel.clone(),
+        execution_handler.clone(),
+    );
+    setup_voice_reconciliation_task(
+        server_state.clone(),
+        voice_publish_channel.clone(),
+        execution_handler.clone(),
+    );
+    setup_speaking_flush_task(server_state.clone());
+    setup_passive_data_flush_task(server_state.clone());
+    setup_board_automation_task(
+        server_state.clone(),
+        execution_handler.clone(),
+        integration_publish_channel.clone(),
+    );
+    setup_iot_action_timeout_task(server_state.clone(), execution_handler.clone());
+    setup_board_liveness_task(server_state.clone());
+    rabbit::setup_integration_consume_task(
+        &rabbit_connection,
+        server_state.clone(),
+        execution_handler.clone(),
+    )
+    .await
+    .unwrap();
+    rabbit::setup_voice_consume_task(&rabbit_connection, server_state.clone())
         .await
         .unwrap();
-    rabbit::setup_voice_consume_task(&rabbit_connection, server_state.clone())
+    rabbit::setup_voice_heartbeat_task(&rabbit_connection, server_state.clone())
+        .await
+        .unwrap();
+    rabbit::setup_integration_heartbeat_task(&rabbit_connection, server_state.clone())
+        .await
+        .unwrap();
+    rabbit::setup_cluster_tasks(&rabbit_connection, server_state.clone())
         .await
         .unwrap();
     setup_routes_and_serve(
@@ -153,7 +196,7 @@ async fn user_message(
         execution_handler,
     )
     .await
-    .unwrap_or_else(|e| eprintln!("issue routing msg:{}", e));
+    .unwrap_or_else(|e| eprintln!("issue routing msg:{}", credentials::redact_error(&e)));
 }
 
 async fn user_disconnected(
@@ -184,6 +227,7 @@ async fn user_disconnected(
             &mut write_state,
             current_user_id,
             &current_room_id,
+            None,
             voice_publish_channel,
             integration_publish_channel,
             execution_handler,
@@ -192,6 +236,7 @@ async fn user_disconnected(
     }
     write_state.peer_map.remove(current_user_id);
     write_state.active_users.remove(current_user_id);
+    cluster::handler::announce_presence(&write_state, current_user_id);
     logging::console::log_event(&format!(
         "User({}) disconnected from the server",
         current_user_id
@@ -300,15 +345,18 @@ async fn insert_new_peer(
         .await
         .peer_map
         .insert(current_user_id, tx);
-    server_state.write().await.active_users.insert(
+    let mut write_state = server_state.write().await;
+    write_state.active_users.insert(
         current_user_id,
         User {
             ip: "-1".to_owned(),
             current_room_id: -1,
             muted: false,
             deaf: false,
+            passive_data_diffs: false,
         },
     );
+    cluster::handler::announce_presence(&write_state, &current_user_id);
 }
 
 pub async fn setup_execution_handler() -> Result<ExecutionHandler, Error> {
@@ -330,9 +378,51 @@ pub async fn setup_execution_handler() -> Result<ExecutionHandler, Error> {
     return Ok(handler);
 }
 
+/// Brings the database and our fresh state in line
+/// with each other before we start accepting users.
+async fn reconcile_state_with_database(
+    server_state: &Arc<RwLock<ServerState>>,
+    execution_handler: &Arc<Mutex<ExecutionHandler>>,
+) {
+    // claim our rooms before the other instances
+    // think we're dead and reclaim them.
+    if cluster::handler::config().enabled {
+        let mut handler = execution_handler.lock().await;
+        cluster::handler::record_heartbeat(&mut handler).await;
+    }
+    let mut write_state = server_state.write().await;
+    rooms::handler::reconcile_rooms_on_startup(&mut write_state, execution_handler).await;
+}
+
+/// Clustered mode only, keeps this instance's rooms
+/// claimed and takes over rooms from dead instances.
+fn setup_instance_heartbeat_task(
+    state: Arc<RwLock<ServerState>>,
+    execution_handler: Arc<Mutex<ExecutionHandler>>,
+) {
+    if !cluster::handler::config().enabled {
+        return;
+    }
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_millis(
+                cluster::handler::INSTANCE_HEARTBEAT_INTERVAL_MS,
+            ))
+            .await;
+            let mut handler = execution_handler.lock().await;
+            cluster::handler::record_heartbeat(&mut handler).await;
+            drop(handler);
+            let mut write_state = state.write().await;
+            rooms::handler::reclaim_rooms_from_dead_instances(&mut write_state, &execution_handler)
+                .await;
+        }
+    });
+}
+
 /// Make sure the rooms are being cleaned up
 /// in the case that someone creates a room but
-/// don't join the room within 10 seconds
+/// don't join the room within 10 seconds,
+/// persistent rooms are allowed to stay empty.
 fn setup_room_cleanup_task(
     state: Arc<RwLock<ServerState>>,
     publish_channel: Arc<Mutex<lapin::Channel>>,
@@ -345,7 +435,7 @@ fn setup_room_cleanup_task(
             let mut write_state = state.write().await;
             for id in write_state.rooms.keys() {
                 if let Some(room) = write_state.rooms.get(&id) {
-                    if room.amount_of_users == 0 {
+                    if room.amount_of_users == 0 && room.persistent == false {
                         to_delete.push(id.clone());
                     }
                 }
@@ -357,6 +447,67 @@ fn setup_room_cleanup_task(
                 &execution_handler,
             )
             .await;
+            rooms::handler::capture_pending_recording_stops(&mut write_state, &execution_handler)
+                .await;
+        }
+    });
+}
+
+/// Board passive data history and actions are only kept
+/// for MERLIN_PASSIVE_HISTORY_RETENTION_DAYS(7 by default).
+fn setup_passive_history_retention_task(execution_handler: Arc<Mutex<ExecutionHandler>>) {
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_millis(60000)).await;
+            let mut handler = execution_handler.lock().await;
+            let (num_samples_removed, num_actions_removed) =
+                board::history::prune_expired_history(&mut handler).await;
+            if num_samples_removed > 0 || num_actions_removed > 0 {
+                logging::console::log_event(&format!(
+                    "Removed {} expired passive data samples and {} expired actions",
+                    num_samples_removed, num_actions_removed
+                ));
+            }
+        }
+    });
+}
+
+/// Sends the board actions that schedules
+/// and rules triggered, see board::automation.
+fn setup_board_automation_task(
+    state: Arc<RwLock<ServerState>>,
+    execution_handler: Arc<Mutex<ExecutionHandler>>,
+    integration_publish_channel: Arc<Mutex<lapin::Channel>>,
+) {
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_millis(1000)).await;
+            let mut write_state = state.write().await;
+            board::automation::run_due_actions(
+                &mut write_state,
+                &execution_handler,
+                &integration_publish_channel,
+            )
+            .await;
+        }
+    });
+}
+
+/// Times out the IoT actions the integration
+/// server never sent an action_response for.
+fn setup_iot_action_timeout_task(
+    state: Arc<RwLock<ServerState>>,
+    execution_handler: Arc<Mutex<ExecutionHandler>>,
+) {
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_millis(2000)).await;
+            let mut write_state = state.write().await;
+            if write_state.pending_iot_actions.len() == 0 {
+                continue;
+            }
+            let mut handler = execution_handler.lock().await;
+            board::actions::time_out_actions(&mut write_state, &mut handler).await;
         }
     });
 }
@@ -364,12 +515,179 @@ fn setup_room_cleanup_task(
 /// Make sure the queues are always cleared of
 /// users that are no longer in this room.'
 /// This helps reserve storage on the server.
+/// Unanswered integration connects are forgotten here too.
 fn setup_room_queue_cleanup_task(state: Arc<RwLock<ServerState>>) {
     tokio::spawn(async move {
         loop {
             sleep(Duration::from_millis(10000)).await;
             let mut write_state = state.write().await;
             cleanup_owner_queues(&mut write_state);
+            rooms::handler::cleanup_kick_cooldowns(&mut write_state);
+            let num_expired = integration::router::prune_expired_connects(
+                &mut write_state,
+                chrono::Utc::now().timestamp_millis(),
+            );
+            if num_expired > 0 {
+                logging::console::log_event(&format!(
+                    "Forgot {} integration connects that never got an auth result",
+                    num_expired
+                ));
+            }
+        }
+    });
+}
+
+/// Expired room blocks are already ignored when
+/// gathering blocks, this just keeps the table clean.
+fn setup_room_block_expiry_task(execution_handler: Arc<Mutex<ExecutionHandler>>) {
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_millis(60000)).await;
+            let mut handler = execution_handler.lock().await;
+            let num_removed = data_capturer::capture_expired_room_block_removal(&mut handler).await;
+            if num_removed > 0 {
+                logging::console::log_event(&format!(
+                    "Removed {} expired room blocks",
+                    num_removed
+                ));
+            }
+        }
+    });
+}
+
+/// Rolls back requests the voice servers never responded to.
+fn setup_voice_request_timeout_task(
+    state: Arc<RwLock<ServerState>>,
+    voice_publish_channel: Arc<Mutex<lapin::Channel>>,
+    integration_publish_channel: Arc<Mutex<lapin::Channel>>,
+    execution_handler: Arc<Mutex<ExecutionHandler>>,
+) {
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_millis(2000)).await;
+            let mut write_state = state.write().await;
+            let timed_out = rooms::handler::take_timed_out_voice_requests(&mut write_state);
+            for pending in timed_out {
+                rooms::handler::rollback_timed_out_voice_request(
+                    &mut write_state,
+                    &voice_publish_channel,
+                    &integration_publish_channel,
+                    &execution_handler,
+                    pending,
+                )
+                .await;
+            }
+        }
+    });
+}
+
+/// Lets rooms know when their boards go quiet(or come back),
+/// see board::liveness.
+fn setup_board_liveness_task(state: Arc<RwLock<ServerState>>) {
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_millis(5000)).await;
+            let mut write_state = state.write().await;
+            board::liveness::broadcast_status_changes(&mut write_state).await;
+        }
+    });
+}
+
+/// Removes voice servers that stopped sending heartbeats
+/// and moves their rooms to the voice servers still alive.
+fn setup_voice_server_cleanup_task(
+    state: Arc<RwLock<ServerState>>,
+    publish_channel: Arc<Mutex<lapin::Channel>>,
+    execution_handler: Arc<Mutex<ExecutionHandler>>,
+) {
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_millis(5000)).await;
+            let mut write_state = state.write().await;
+            let dead = voice_servers::handler::remove_dead_voice_servers(&mut write_state);
+            for voice_server_id in dead {
+                rooms::handler::migrate_rooms_from_voice_server(
+                    &mut write_state,
+                    &publish_channel,
+                    &execution_handler,
+                    &voice_server_id,
+                )
+                .await;
+            }
+        }
+    });
+}
+
+/// Compares what the voice servers reported last round
+/// with our state, fixes any drift and asks for new reports.
+fn setup_voice_reconciliation_task(
+    state: Arc<RwLock<ServerState>>,
+    publish_channel: Arc<Mutex<lapin::Channel>>,
+    execution_handler: Arc<Mutex<ExecutionHandler>>,
+) {
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_millis(30000)).await;
+            // the voice servers are shared with the other instances,
+            // we only reconcile the rooms we own.
+            let mut room_owners = None;
+            if cluster::handler::config().enabled {
+                let mut handler = execution_handler.lock().await;
+                let room_instances = data_fetcher::get_room_instances(&mut handler).await;
+                drop(handler);
+                if room_instances.0 {
+                    logging::console::log_failure("Issue gathering room owners for reconciliation");
+                    continue;
+                }
+                room_owners = Some(room_instances.1);
+            }
+            let mut write_state = state.write().await;
+            let reported = voice_servers::handler::take_reported_reconciliations(&mut write_state);
+            for (reconciliation, rooms_and_peers) in reported {
+                let drift = voice_servers::handler::find_drift(
+                    &write_state,
+                    &reconciliation,
+                    &rooms_and_peers,
+                    room_owners.as_ref(),
+                );
+                rooms::handler::reconcile_voice_server(
+                    &mut write_state,
+                    &publish_channel,
+                    &execution_handler,
+                    &reconciliation.voice_server_id,
+                    drift,
+                )
+                .await;
+            }
+            rooms::handler::request_voice_server_states(&mut write_state, &publish_channel).await;
+        }
+    });
+}
+
+/// Sends the passive data changes that got held back by the throttle.
+fn setup_passive_data_flush_task(state: Arc<RwLock<ServerState>>) {
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_millis(
+                board::passive::PASSIVE_FAN_OUT_THROTTLE_MS as u64,
+            ))
+            .await;
+            let mut write_state = state.write().await;
+            board::passive::flush_throttled_updates(&mut write_state).await;
+        }
+    });
+}
+
+/// Sends the speaking updates that got held back by the throttle.
+fn setup_speaking_flush_task(state: Arc<RwLock<ServerState>>) {
+    tokio::spawn(async move {
+        loop {
+            sleep(Duration::from_millis(
+                rooms::speaking::SPEAKING_FAN_OUT_THROTTLE_MS as u64,
+            ))
+            .await;
+            let mut write_state = state.write().await;
+            rooms::speaking::flush_throttled_updates(&mut write_state).await;
         }
     });
 }
@@ -1,8 +1,14 @@
 use std::collections::HashMap;
+use tokio::sync::mpsc::UnboundedSender;
 
-use crate::state::types::{ActiveRooms, ActiveUsers, PeerMap};
+use crate::state::types::{
+    ActiveRooms, ActiveUsers, IntegrationProtocolCounters, PeerMap, PendingIntegrationConnect,
+    PendingIoTAction, PendingReconciliation, PendingVoiceRequest, VoiceProtocolCounters,
+    VoiceServers,
+};
 
 use super::owner_queue::OwnerQueue;
+use crate::board::types::PendingBoardAction;
 
 pub struct ServerState {
     pub peer_map: PeerMap,
@@ -12,6 +18,35 @@ pub struct ServerState {
     /// maps external iot server ids
     /// to their local rooms
     pub external_servers: HashMap<String, i32>,
+    /// users connected to other instances(clustered mode)
+    /// mapped to their current room
+    pub remote_users: HashMap<i32, i32>,
+    /// messages waiting to be published to the
+    /// other instances(clustered mode only)
+    pub cluster_outbox: Option<UnboundedSender<String>>,
+    pub voice_servers: VoiceServers,
+    /// (room id, user id) -> unix millis the user
+    /// is allowed to rejoin the room they were kicked from
+    pub kick_cooldowns: HashMap<(i32, i32), i64>,
+    /// request id -> request waiting on a voice server response
+    pub pending_voice_requests: HashMap<String, PendingVoiceRequest>,
+    pub voice_protocol_counters: VoiceProtocolCounters,
+    pub integration_protocol_counters: IntegrationProtocolCounters,
+    /// request id -> reconciliation waiting on(or holding) a voice server's report
+    pub pending_reconciliations: HashMap<String, PendingReconciliation>,
+    /// (room id, unix millis) recordings that ended
+    /// without a db handle, see server::setup_room_cleanup_task
+    pub pending_recording_stops: Vec<(i32, i64)>,
+    /// actions triggered by board rules, sent
+    /// by server::setup_board_automation_task
+    pub pending_board_actions: Vec<PendingBoardAction>,
+    /// action id -> action waiting on an action_response
+    pub pending_iot_actions: HashMap<String, PendingIoTAction>,
+    /// request id -> connect waiting on its auth result
+    pub pending_integration_connects: HashMap<String, PendingIntegrationConnect>,
+    /// integration server id -> unix millis of its last heartbeat,
+    /// older integration servers don't send them
+    pub integration_heartbeats: HashMap<String, i64>,
 }
 
 //Holds all server memory state
@@ -23,6 +58,19 @@ impl ServerState {
             rooms: ActiveRooms::new(),
             owner_queues: HashMap::new(),
             external_servers: HashMap::new(),
+            remote_users: HashMap::new(),
+            cluster_outbox: None,
+            voice_servers: VoiceServers::new(),
+            kick_cooldowns: HashMap::new(),
+            pending_voice_requests: HashMap::new(),
+            voice_protocol_counters: VoiceProtocolCounters::default(),
+            integration_protocol_counters: IntegrationProtocolCounters::default(),
+            pending_reconciliations: HashMap::new(),
+            pending_recording_stops: Vec::new(),
+            pending_board_actions: Vec::new(),
+            pending_iot_actions: HashMap::new(),
+            pending_integration_connects: HashMap::new(),
+            integration_heartbeats: HashMap::new(),
         }
     }
 }
@@ -1,12 +1,553 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use super::{owner_queue::OwnerQueue, types::User};
+use super::{
+    owner_queue::OwnerQueue,
+    state::ServerState,
+    types::{
+        ActiveRecording, Board, PassiveFanOut, PendingIoTAction, PendingReconciliation,
+        PendingVoiceRequest, Room, SpeakingState, User, VoiceRequestRollback, VoiceRoomsAndPeers,
+    },
+};
+use crate::board;
+use crate::board::types::{BoardStatus, CreateBoardRule, CreateBoardSchedule, RuleComparison};
+use crate::cluster;
+use crate::communication::helpers::web_rtc_request_is_blocked_by_mod;
+use crate::integration::providers::IntegrationProvider;
+use crate::rooms;
+use crate::voice_servers::handler;
+use crate::voice_servers::types::VoiceServerDrift;
+use chrono::Utc;
+use futures::FutureExt;
+use tokio::sync::mpsc;
 
 pub fn test_owners_queue() {
     let mut mock_queue = OwnerQueue::new(32);
     adding_and_removing(&mut mock_queue);
 }
 
+pub fn test_voice_server_placement() {
+    let mut state = ServerState::new();
+    // nothing registered, the default voice server is used
+    assert_eq!(
+        handler::place_room(&state),
+        handler::DEFAULT_VOICE_SERVER_ID
+    );
+    handler::handle_heartbeat(
+        r#"{"voiceServerId":"vs-a","consumeQueue":"voice_server_consume.vs-a"}"#.to_owned(),
+        &mut state,
+    );
+    handler::handle_heartbeat(
+        r#"{"voiceServerId":"vs-b","consumeQueue":"voice_server_consume.vs-b"}"#.to_owned(),
+        &mut state,
+    );
+    assert_eq!(state.voice_servers.len(), 2);
+    // vs-a already has a room, so vs-b is the least loaded
+    state.rooms.insert(1, mock_room(1, "vs-a"));
+    assert_eq!(handler::place_room(&state), "vs-b");
+    assert_eq!(
+        handler::consume_queue_for_room(&state, &1),
+        "voice_server_consume.vs-a"
+    );
+    // missed heartbeats get the voice server removed
+    state.voice_servers.get_mut("vs-a").unwrap().last_heartbeat = 0;
+    let dead = handler::remove_dead_voice_servers(&mut state);
+    assert_eq!(dead, vec!["vs-a".to_owned()]);
+    assert_eq!(handler::place_room(&state), "vs-b");
+}
+
+pub fn test_kick_cooldowns() {
+    let mut state = ServerState::new();
+    let now = Utc::now().timestamp_millis();
+    state.kick_cooldowns.insert((1, 22), now + 60000);
+    state.kick_cooldowns.insert((1, 33), now - 1);
+    assert!(rooms::handler::user_is_on_kick_cooldown(&state, &1, &22));
+    // the cooldown is over
+    assert!(!rooms::handler::user_is_on_kick_cooldown(&state, &1, &33));
+    // the cooldown only applies to the room they were kicked from
+    assert!(!rooms::handler::user_is_on_kick_cooldown(&state, &2, &22));
+    rooms::handler::cleanup_kick_cooldowns(&mut state);
+    assert_eq!(state.kick_cooldowns.len(), 1);
+    // the configured cooldown is clamped
+    assert_eq!(rooms::handler::kick_cooldown_secs_to_millis(30), 30000);
+    for seconds in [0, -5, i64::MIN] {
+        assert_eq!(
+            rooms::handler::kick_cooldown_secs_to_millis(seconds),
+            rooms::handler::MIN_KICK_COOLDOWN_SECS * 1000
+        );
+    }
+    assert_eq!(
+        rooms::handler::kick_cooldown_secs_to_millis(i64::MAX),
+        rooms::handler::MAX_KICK_COOLDOWN_SECS * 1000
+    );
+}
+
+pub fn test_block_expiry() {
+    let now = Utc::now().timestamp_millis();
+    assert_eq!(rooms::handler::block_expires_at(None, now), Some(None));
+    assert_eq!(
+        rooms::handler::block_expires_at(Some(60), now),
+        Some(Some(now + 60000))
+    );
+    assert_eq!(rooms::handler::block_expires_at(Some(0), now), None);
+    assert_eq!(rooms::handler::block_expires_at(Some(-60), now), None);
+    // would overflow instead of blocking for a very long time
+    assert_eq!(rooms::handler::block_expires_at(Some(i64::MAX), now), None);
+    assert_eq!(
+        rooms::handler::block_expires_at(Some(i64::MAX / 1000), now),
+        None
+    );
+}
+
+pub fn test_timed_out_voice_requests() {
+    let mut state = ServerState::new();
+    let now = Utc::now().timestamp_millis();
+    state
+        .pending_voice_requests
+        .insert("fresh".to_owned(), mock_pending_voice_request(now));
+    state
+        .pending_voice_requests
+        .insert("stale".to_owned(), mock_pending_voice_request(now - 60000));
+    let timed_out = rooms::handler::take_timed_out_voice_requests(&mut state);
+    assert_eq!(timed_out.len(), 1);
+    assert_eq!(timed_out[0].op, "join-as-new-peer");
+    // only the fresh request is still pending
+    assert!(state.pending_voice_requests.contains_key("fresh"));
+    assert_eq!(state.pending_voice_requests.len(), 1);
+    // only voice servers that say they echo request ids get tracked
+    state.rooms.insert(1, mock_room(1, "vs-a"));
+    state.rooms.insert(2, mock_room(2, "vs-b"));
+    handler::handle_heartbeat(
+        r#"{"voiceServerId":"vs-a","consumeQueue":"voice_server_consume.vs-a"}"#.to_owned(),
+        &mut state,
+    );
+    handler::handle_heartbeat(
+        r#"{"voiceServerId":"vs-b","consumeQueue":"voice_server_consume.vs-b","echoesRequestId":true}"#
+            .to_owned(),
+        &mut state,
+    );
+    assert!(!handler::echoes_request_id(&state, &1));
+    assert!(handler::echoes_request_id(&state, &2));
+    // rooms on the default voice server are never tracked
+    state
+        .rooms
+        .insert(3, mock_room(3, handler::DEFAULT_VOICE_SERVER_ID));
+    assert!(!handler::echoes_request_id(&state, &3));
+}
+
+pub fn test_voice_server_drift() {
+    let mut state = ServerState::new();
+    for room_id in 1..4 {
+        let mut room = mock_room(room_id, "vs-a");
+        room.user_ids = HashSet::from([22, 33]);
+        state.rooms.insert(room_id, room);
+    }
+    let reconciliation = PendingReconciliation {
+        voice_server_id: "vs-a".to_owned(),
+        sent_at: Utc::now().timestamp_millis(),
+        expected: handler::expected_rooms_and_peers(&state, &"vs-a".to_owned()),
+        reported: None,
+    };
+    // 44 joined room 3 after we asked, it isn't missing yet
+    state.rooms.get_mut(&3).unwrap().user_ids.insert(44);
+    let reported: VoiceRoomsAndPeers = HashMap::from([
+        (1, HashSet::from([22, 33])),
+        (3, HashSet::from([22, 55])),
+        (9, HashSet::from([22])),
+    ]);
+    let drift = handler::find_drift(&state, &reconciliation, &reported, None);
+    assert_eq!(
+        drift,
+        VoiceServerDrift {
+            missing_rooms: vec![2],
+            missing_peers: vec![(3, 33)],
+            unknown_rooms: vec![9],
+            unknown_peers: vec![(3, 55)],
+        }
+    );
+    // clustered, rooms owned by other instances(or nobody)
+    // are left alone instead of being destroyed/closed
+    let instance_id = cluster::handler::config().instance_id.clone();
+    let room_owners = HashMap::from([
+        (1, instance_id.clone()),
+        (2, instance_id.clone()),
+        (3, "another-instance".to_owned()),
+    ]);
+    let drift = handler::find_drift(&state, &reconciliation, &reported, Some(&room_owners));
+    assert_eq!(
+        drift,
+        VoiceServerDrift {
+            missing_rooms: vec![2],
+            ..Default::default()
+        }
+    );
+}
+
+pub fn test_mod_mute_and_deafen() {
+    let mut state = ServerState::new();
+    let mut room = mock_room(3, "0");
+    room.user_ids = HashSet::from([22, 33]);
+    room.muted.insert(22);
+    room.deaf.insert(33);
+    state.rooms.insert(3, room);
+    for user_id in [22, 33] {
+        state.active_users.insert(
+            user_id,
+            User {
+                current_room_id: 3,
+                ..Default::default()
+            },
+        );
+    }
+    assert_eq!(
+        rooms::handler::mod_mute_and_deafen_state(&state, &22),
+        (true, false)
+    );
+    assert_eq!(
+        rooms::handler::mod_mute_and_deafen_state(&state, &33),
+        (false, true)
+    );
+    let request = serde_json::json!({"roomId": 3, "peerId": 22});
+    // muted users can't send tracks, but can still receive them
+    assert!(web_rtc_request_is_blocked_by_mod(
+        &state,
+        &request,
+        &22,
+        "@send-track"
+    ));
+    assert!(!web_rtc_request_is_blocked_by_mod(
+        &state,
+        &request,
+        &22,
+        "@get-recv-tracks"
+    ));
+    let request = serde_json::json!({"roomId": 3, "peerId": 33});
+    assert!(web_rtc_request_is_blocked_by_mod(
+        &state,
+        &request,
+        &33,
+        "@get-recv-tracks"
+    ));
+    assert!(!web_rtc_request_is_blocked_by_mod(
+        &state,
+        &request,
+        &33,
+        "@connect-transport"
+    ));
+}
+
+fn mock_pending_voice_request(sent_at: i64) -> PendingVoiceRequest {
+    return PendingVoiceRequest {
+        op: "join-as-new-peer".to_owned(),
+        user_id: 22,
+        room_id: 1,
+        sent_at: sent_at,
+        rollback: VoiceRequestRollback::RemoveUserFromRoom,
+    };
+}
+
+pub fn test_recording_consent() {
+    let mut state = ServerState::new();
+    state.rooms.insert(3, mock_room(3, "0"));
+    // nothing to consent to
+    assert!(!rooms::handler::needs_recording_consent(&state, &3, &22));
+    assert!(!rooms::handler::accept_recording_consent(
+        &mut state, &3, &22
+    ));
+    state.rooms.get_mut(&3).unwrap().recording = Some(ActiveRecording {
+        recording_id: 1,
+        started_by: 33,
+        started_at: 1000,
+        consents: HashSet::new(),
+    });
+    assert!(rooms::handler::needs_recording_consent(&state, &3, &22));
+    assert!(rooms::handler::accept_recording_consent(
+        &mut state, &3, &22
+    ));
+    assert!(!rooms::handler::needs_recording_consent(&state, &3, &22));
+    // only users that haven't consented yet are asked
+    let (tx_consented, mut rx_consented) = mpsc::unbounded_channel();
+    let (tx_listener, mut rx_listener) = mpsc::unbounded_channel();
+    state.peer_map.insert(22, tx_consented);
+    state.peer_map.insert(34, tx_listener);
+    rooms::handler::ask_for_recording_consent(&mut state, &3, vec![22, 34]);
+    assert!(rx_consented.recv().now_or_never().is_none());
+    assert!(rx_listener
+        .recv()
+        .now_or_never()
+        .flatten()
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .contains("recording_consent_required"));
+    let recording_state = rooms::handler::recording_state(state.rooms.get(&3).unwrap());
+    assert!(recording_state.recording);
+    assert_eq!(recording_state.started_by, Some(33));
+}
+
+pub fn test_board_grants() {
+    let mut board = mock_board(3, "server");
+    // full controller
+    board
+        .users_with_permission
+        .insert(33, board::handler::grant_from_scopes(None, None));
+    // operator for a single bot
+    board.users_with_permission.insert(
+        44,
+        board::handler::grant_from_scopes(Some(vec!["lamp".to_owned()]), None),
+    );
+    // viewer
+    board.users_with_permission.insert(
+        55,
+        board::handler::grant_from_scopes(None, Some(Vec::new())),
+    );
+    assert!(board::handler::can_run_action(&board, &22, "door", "open"));
+    assert!(board::handler::can_run_action(&board, &33, "door", "open"));
+    assert!(board::handler::can_run_action(&board, &44, "lamp", "on"));
+    assert!(!board::handler::can_run_action(&board, &44, "door", "open"));
+    assert!(!board::handler::can_run_action(&board, &55, "lamp", "on"));
+    assert!(!board::handler::can_run_action(&board, &66, "lamp", "on"));
+}
+
+pub fn test_board_automations() {
+    let mut state = ServerState::new();
+    let mut room = mock_room(3, "vs");
+    room.iot_server_connections
+        .insert("server".to_owned(), mock_board(3, "server"));
+    state.rooms.insert(3, room);
+    let now = Utc::now().timestamp_millis();
+    let board = state
+        .rooms
+        .get_mut(&3)
+        .unwrap()
+        .iot_server_connections
+        .get_mut("server")
+        .unwrap();
+    // a rule fires once each time its condition starts being met
+    assert!(board::automation::add_rule(
+        board,
+        CreateBoardRule {
+            external_id: "server".to_owned(),
+            pointer: "/thermostat/temperature".to_owned(),
+            comparison: RuleComparison::GreaterThan,
+            value: serde_json::json!(25),
+            bot_name: "fan".to_owned(),
+            action: "on".to_owned(),
+        }
+    ));
+    let hot = serde_json::json!({"thermostat": {"temperature": 30}}).to_string();
+    let cold = serde_json::json!({"thermostat": {"temperature": 20}}).to_string();
+    assert_eq!(board::automation::evaluate_rules(board, &hot).len(), 1);
+    assert_eq!(board::automation::evaluate_rules(board, &hot).len(), 0);
+    assert_eq!(board::automation::evaluate_rules(board, &cold).len(), 0);
+    // passive data arrives as a json string
+    let hot_string = serde_json::Value::String(hot).to_string();
+    assert_eq!(
+        board::automation::evaluate_rules(board, &hot_string).len(),
+        1
+    );
+    // schedules need exactly one of cron/run_at
+    let schedule = |cron: Option<&str>, run_at: Option<i64>| CreateBoardSchedule {
+        external_id: "server".to_owned(),
+        bot_name: "lamp".to_owned(),
+        action: "off".to_owned(),
+        cron: cron.map(|cron| cron.to_owned()),
+        run_at: run_at,
+    };
+    assert!(!board::automation::add_schedule(
+        board,
+        schedule(None, None),
+        now
+    ));
+    assert!(!board::automation::add_schedule(
+        board,
+        schedule(None, Some(now - 1)),
+        now
+    ));
+    assert!(!board::automation::add_schedule(
+        board,
+        schedule(Some("not cron"), None),
+        now
+    ));
+    assert!(board::automation::add_schedule(
+        board,
+        schedule(None, Some(now + 1000)),
+        now
+    ));
+    assert!(board::automation::add_schedule(
+        board,
+        schedule(Some("0 * * * * *"), None),
+        now
+    ));
+    assert_eq!(
+        board::automation::take_due_actions(&mut state, now).len(),
+        0
+    );
+    // the one shot runs and is removed, the cron schedule moves on
+    let due = board::automation::take_due_actions(&mut state, now + 60 * 1000);
+    assert_eq!(due.len(), 2);
+    assert!(due.iter().all(|action| action.external_id == "server"));
+    let board = state.rooms[&3]
+        .iot_server_connections
+        .get("server")
+        .unwrap();
+    assert_eq!(board.schedules.len(), 1);
+    assert!(board.schedules[0].next_run > now + 60 * 1000);
+}
+
+pub fn test_passive_history_retention() {
+    let day_millis = 24 * 60 * 60 * 1000;
+    assert_eq!(board::history::retention_days_to_millis(7), 7 * day_millis);
+    // out of range values are clamped instead of overflowing
+    assert_eq!(board::history::retention_days_to_millis(0), day_millis);
+    assert_eq!(board::history::retention_days_to_millis(-5), day_millis);
+    assert_eq!(
+        board::history::retention_days_to_millis(i64::MAX),
+        board::history::MAX_RETENTION_DAYS * day_millis
+    );
+}
+
+pub fn test_passive_data_fan_out() {
+    let mut board = mock_board(3, "server");
+    let first =
+        serde_json::json!({"lamp": "on", "thermostat": {"temperature": 20, "humidity": 40}});
+    let second =
+        serde_json::json!({"lamp": "on", "thermostat": {"temperature": 21, "humidity": 40}});
+    let third = serde_json::json!({"thermostat": {"temperature": 21, "humidity": 40}});
+    assert!(board::passive::update_snapshot(
+        &mut board,
+        first.to_string()
+    ));
+    // identical snapshots are suppressed
+    assert!(!board::passive::update_snapshot(
+        &mut board,
+        first.to_string()
+    ));
+    // the first fan out diffs against nothing, so it's the full snapshot
+    let (snapshot, diff) = board::passive::take_due_fan_out(&mut board, 1000).unwrap();
+    assert_eq!(snapshot, first.to_string());
+    assert_eq!(diff, first);
+    assert!(board::passive::take_due_fan_out(&mut board, 5000).is_none());
+    // changes within the throttle window wait
+    assert!(board::passive::update_snapshot(
+        &mut board,
+        second.to_string()
+    ));
+    assert!(board::passive::update_snapshot(
+        &mut board,
+        third.to_string()
+    ));
+    assert!(board::passive::take_due_fan_out(&mut board, 1100).is_none());
+    let (snapshot, diff) = board::passive::take_due_fan_out(
+        &mut board,
+        1000 + board::passive::PASSIVE_FAN_OUT_THROTTLE_MS,
+    )
+    .unwrap();
+    assert_eq!(snapshot, third.to_string());
+    assert_eq!(
+        diff,
+        serde_json::json!({"lamp": null, "thermostat": {"temperature": 21}})
+    );
+    assert_eq!(
+        board.passive_fan_out.fanned_out_snapshot,
+        Some(third.to_string())
+    );
+}
+
+pub fn test_iot_action_tracking() {
+    let mut state = ServerState::new();
+    let now = Utc::now().timestamp_millis();
+    let actions = [
+        ("first", "server", now - 2000),
+        ("second", "server", now - 1000),
+        ("other", "other_server", now),
+        ("stale", "server", now - 60000),
+    ];
+    for (action_id, external_id, sent_at) in actions {
+        state.pending_iot_actions.insert(
+            action_id.to_owned(),
+            PendingIoTAction {
+                room_id: 3,
+                external_id: external_id.to_owned(),
+                requester_id: Some(22),
+                bot_name: "lamp".to_owned(),
+                action: "on".to_owned(),
+                sent_at: sent_at,
+            },
+        );
+    }
+    let timed_out = board::actions::take_timed_out_actions(&mut state, now);
+    assert_eq!(timed_out.len(), 1);
+    assert_eq!(timed_out[0].0, "stale");
+    // responses can't be matched to another board's action
+    assert!(board::actions::take_responded_action(
+        &mut state,
+        &"server".to_owned(),
+        Some("other".to_owned())
+    )
+    .is_none());
+    // without an action id there's no telling which of the two it's for
+    assert!(
+        board::actions::take_responded_action(&mut state, &"server".to_owned(), None).is_none()
+    );
+    let (action_id, pending) = board::actions::take_responded_action(
+        &mut state,
+        &"server".to_owned(),
+        Some("second".to_owned()),
+    )
+    .unwrap();
+    assert_eq!(action_id, "second");
+    assert_eq!(pending.requester_id, Some(22));
+    // or the board's only pending action
+    let (action_id, _) =
+        board::actions::take_responded_action(&mut state, &"server".to_owned(), None).unwrap();
+    assert_eq!(action_id, "first");
+    assert!(
+        board::actions::take_responded_action(&mut state, &"server".to_owned(), None).is_none()
+    );
+    assert_eq!(state.pending_iot_actions.len(), 1);
+}
+
+pub fn mock_room(room_id: i32, voice_server_id: &str) -> Room {
+    return Room {
+        room_id: room_id,
+        muted: HashSet::new(),
+        name: "test".to_owned(),
+        desc: "test".to_owned(),
+        chat_throttle: 1000,
+        voice_server_id: voice_server_id.to_owned(),
+        deaf: HashSet::new(),
+        user_ids: HashSet::new(),
+        amount_of_users: 0,
+        public: true,
+        auto_speaker: false,
+        speaker_cap: None,
+        persistent: false,
+        active: true,
+        created_at: "".to_owned(),
+        iot_server_connections: HashMap::new(),
+        speaking: SpeakingState::default(),
+        recording: None,
+    };
+}
+
+pub fn mock_board(room_id: i32, external_id: &str) -> Board {
+    return Board {
+        room_id: room_id,
+        owner_user_id: 22,
+        users_with_permission: HashMap::new(),
+        external_server_id: external_id.to_owned(),
+        passive_data_snapshot: None,
+        passive_fan_out: PassiveFanOut::default(),
+        outside_name: "board".to_owned(),
+        provider: IntegrationProvider::HouseOfIoT,
+        last_seen: 0,
+        status: BoardStatus::Online,
+        integration_server_id: None,
+        schedules: Vec::new(),
+        rules: Vec::new(),
+    };
+}
+
 fn adding_and_removing(queue: &mut OwnerQueue) {
     let mut active_users: HashMap<i32, User> = HashMap::new();
     queue.insert_new_user(22);
@@ -51,3 +592,68 @@ fn adding_and_removing(queue: &mut OwnerQueue) {
     // while looking for the new valid user
     assert!(queue.user_queue.len() == 0);
 }
+
+pub fn test_board_liveness() {
+    let mut state = ServerState::new();
+    let now = Utc::now().timestamp_millis();
+    let mut room = mock_room(3, "vs");
+    for (external_id, last_seen, integration_server_id) in [
+        ("quiet", now - 120000, "integration"),
+        ("chatty", now, "integration"),
+        ("elsewhere", now, "other"),
+    ] {
+        room.iot_server_connections.insert(
+            external_id.to_owned(),
+            Board {
+                last_seen: last_seen,
+                integration_server_id: Some(integration_server_id.to_owned()),
+                ..mock_board(3, external_id)
+            },
+        );
+        state.external_servers.insert(external_id.to_owned(), 3);
+    }
+    state.rooms.insert(3, room);
+    // integration servers without heartbeats are assumed alive
+    let changes = board::liveness::take_status_changes(&mut state, now);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].0, 3);
+    assert_eq!(changes[0].1.external_id, "quiet");
+    assert_eq!(changes[0].1.status, BoardStatus::Unresponsive);
+    // unchanged statuses aren't reported again
+    assert!(board::liveness::take_status_changes(&mut state, now).is_empty());
+    // hearing from the board brings it back
+    board::liveness::mark_seen(&mut state, &"quiet".to_owned(), now);
+    let changes = board::liveness::take_status_changes(&mut state, now);
+    assert_eq!(changes.len(), 1);
+    assert_eq!(changes[0].1.status, BoardStatus::Online);
+    // only the boards of the integration server
+    // that stopped sending heartbeats are flagged
+    board::liveness::handle_heartbeat(
+        r#"{"integration_server_id":"integration"}"#.to_owned(),
+        &mut state,
+    );
+    assert!(board::liveness::take_status_changes(&mut state, now).is_empty());
+    let later = now + board::liveness::INTEGRATION_HEARTBEAT_TIMEOUT_MS + 5000;
+    let changes = board::liveness::take_status_changes(&mut state, later);
+    assert_eq!(changes.len(), 2);
+    assert!(changes
+        .iter()
+        .all(|(_, change)| change.external_id != "elsewhere"
+            && change.status == BoardStatus::IntegrationDown));
+    // malformed heartbeats are ignored
+    state.integration_heartbeats.clear();
+    board::liveness::handle_heartbeat("not json".to_owned(), &mut state);
+    assert!(state.integration_heartbeats.is_empty());
+    // the stale threshold has to be a positive number of seconds
+    assert_eq!(board::liveness::parse_stale_threshold_millis(None), 60000);
+    assert_eq!(
+        board::liveness::parse_stale_threshold_millis(Some("30".to_owned())),
+        30000
+    );
+    for value in ["0", "-5", "abc", "9223372036854775807"] {
+        assert_eq!(
+            board::liveness::parse_stale_threshold_millis(Some(value.to_owned())),
+            60000
+        );
+    }
+}
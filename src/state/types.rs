@@ -1,3 +1,5 @@
+use crate::board::types::{BoardRule, BoardSchedule, BoardStatus};
+use crate::integration::providers::IntegrationProvider;
 #[allow(unused_imports)]
 use futures_util::{stream::SplitSink, SinkExt, StreamExt};
 use std::collections::{HashMap, HashSet};
@@ -9,36 +11,199 @@ pub struct Board {
     pub room_id: i32,
     pub owner_user_id: i32,
     /// Those granted permissions by the owner
-    pub users_with_permission: HashSet<i32>,
+    pub users_with_permission: HashMap<i32, BoardGrant>,
     pub external_server_id: String,
     /// Used for new users when they join a specific server,
     /// because the new users need to get the most recent capture
     /// of passive data.
     pub passive_data_snapshot: Option<String>,
+    pub passive_fan_out: PassiveFanOut,
     pub outside_name: String,
+    /// What kind of backend the board is bridged to
+    pub provider: IntegrationProvider,
+    /// Last time anything came from the board(unix millis),
+    /// see board::liveness
+    pub last_seen: i64,
+    pub status: BoardStatus,
+    /// The integration server holding the connection, None
+    /// if it didn't say(or the board was restored on startup)
+    pub integration_server_id: Option<String>,
+    /// Automations set up by the owner, see board::automation
+    pub schedules: Vec<BoardSchedule>,
+    pub rules: Vec<BoardRule>,
 }
+
+/// What the room last heard about a board's passive data
+#[derive(Default)]
+pub struct PassiveFanOut {
+    /// What diffs are taken against, also what
+    /// new users get in existing_iot_data.
+    pub fanned_out_snapshot: Option<String>,
+    pub throttle: FanOutThrottle,
+}
+
+/// Changes fanned out at most once
+/// per window, see common::throttle.
+#[derive(Default)]
+pub struct FanOutThrottle {
+    pub last_fan_out: i64, //unix millis
+    /// changed since the last fan out
+    pub dirty: bool,
+}
+
+/// What a controller can do with a board,
+/// None means no restriction.
+#[derive(Default, Clone, Debug, PartialEq)]
+pub struct BoardGrant {
+    pub bots: Option<HashSet<String>>,
+    pub actions: Option<HashSet<String>>,
+}
+
 #[derive(Default)]
 pub struct User {
     pub muted: bool,
     pub deaf: bool,
     pub ip: String,
     pub current_room_id: i32,
+    /// Gets passive data diffs instead of full snapshots
+    pub passive_data_diffs: bool,
 }
 
 pub struct Room {
     pub room_id: i32,
+    /// Users muted by a mod, kept when they
+    /// leave so rejoining doesn't lift it.
     pub muted: HashSet<i32>,
     pub name: String,
     pub desc: String,
     pub chat_throttle: i32,
     pub voice_server_id: String,
+    /// Users deafened by a mod, same as muted
     pub deaf: HashSet<i32>,
     pub user_ids: HashSet<i32>,
     pub amount_of_users: i32,
     pub public: bool,
     pub auto_speaker: bool,
+    /// Maximum amount of speakers auto speaker
+    /// will promote to, None means no cap.
+    pub speaker_cap: Option<i32>,
+    /// Persistent rooms aren't destroyed when empty,
+    /// they're kept around until someone joins again.
+    pub persistent: bool,
+    /// Whether the voice server currently has this room,
+    /// empty persistent rooms are inactive.
+    pub active: bool,
     pub created_at: String, //datetime
     pub iot_server_connections: HashMap<String, Board>,
+    pub speaking: SpeakingState,
+    /// None when the room isn't being recorded
+    pub recording: Option<ActiveRecording>,
+}
+
+/// A recording in progress, users have to
+/// consent to it before they can join.
+pub struct ActiveRecording {
+    /// The room_recording row
+    pub recording_id: i32,
+    pub started_by: i32,
+    pub started_at: i64, //unix millis
+    pub consents: HashSet<i32>,
+}
+
+/// Who is talking, as last reported by the voice server
+#[derive(Default)]
+pub struct SpeakingState {
+    pub active_speaker: Option<i32>,
+    /// user id -> volume(dBov, 0 is the loudest)
+    pub audio_levels: HashMap<i32, f64>,
+    pub throttle: FanOutThrottle,
+}
+
+/// A voice server registered through its heartbeats
+pub struct VoiceServer {
+    pub voice_server_id: String,
+    /// The queue this voice server consumes requests from
+    pub consume_queue: String,
+    pub last_heartbeat: i64, //unix millis
+    /// Only requests to voice servers that send the
+    /// request_id back with responses are tracked.
+    pub echoes_request_id: bool,
+}
+
+/// What gets undone when the voice server
+/// never responds to a request.
+pub enum VoiceRequestRollback {
+    Nothing,
+    /// The user was optimistically added to the room state
+    RemoveUserFromRoom,
+    /// The user was optimistically made a speaker in the database
+    RevertToListener {
+        asked_to_speak: bool,
+        is_mod: bool,
+    },
+}
+
+/// A request sent to a voice server that
+/// we are still waiting on a response for.
+pub struct PendingVoiceRequest {
+    pub op: String,
+    pub user_id: i32,
+    pub room_id: i32,
+    pub sent_at: i64, //unix millis
+    pub rollback: VoiceRequestRollback,
+}
+
+/// An action sent to an IoT server that we
+/// are still waiting on an action_response for.
+#[derive(Debug, PartialEq)]
+pub struct PendingIoTAction {
+    pub room_id: i32,
+    pub external_id: String,
+    /// None for automations
+    pub requester_id: Option<i32>,
+    pub bot_name: String,
+    pub action: String,
+    pub sent_at: i64, //unix millis
+}
+
+/// A connect request waiting on its auth result
+pub struct PendingIntegrationConnect {
+    pub user_id: i32,
+    /// the room the board is connected to
+    pub room_id: i32,
+    /// what was asked for, older integration
+    /// servers don't echo the provider back
+    pub provider: IntegrationProvider,
+    /// encrypted with the storage key, only set
+    /// if the owner asked for them to be remembered
+    pub credentials: Option<String>,
+    pub requested_at: i64, //unix millis
+}
+
+/// room id -> users in the room
+pub type VoiceRoomsAndPeers = HashMap<i32, HashSet<i32>>;
+
+/// A reconciliation exchange with a voice server, expected
+/// is what we believed it had when we asked.
+pub struct PendingReconciliation {
+    pub voice_server_id: String,
+    pub sent_at: i64, //unix millis
+    pub expected: VoiceRoomsAndPeers,
+    pub reported: Option<VoiceRoomsAndPeers>,
+}
+
+/// Voice server messages we couldn't handle
+#[derive(Default)]
+pub struct VoiceProtocolCounters {
+    pub decode_errors: u64,
+    pub dead_letters: u64,
+}
+
+/// Integration server messages we couldn't handle
+#[derive(Default)]
+pub struct IntegrationProtocolCounters {
+    pub decode_errors: u64,
+    pub unknown_categories: u64,
 }
 
 /// IoTServerConnectionId -> Permissions for the connection(represented as the board)
@@ -55,3 +220,6 @@ pub type ActiveUsers = HashMap<i32, User>;
 
 //room collection
 pub type ActiveRooms = HashMap<i32, Room>;
+
+//voice server id -> voice server
+pub type VoiceServers = HashMap<String, VoiceServer>;
@@ -4,4 +4,19 @@ async fn run_all_tests() {
     crate::data_store::test::test().await;
     crate::communication::test::test().await;
     crate::state::tests::test_owners_queue();
+    crate::state::tests::test_voice_server_placement();
+    crate::state::tests::test_kick_cooldowns();
+    crate::state::tests::test_block_expiry();
+    crate::state::tests::test_timed_out_voice_requests();
+    crate::state::tests::test_voice_server_drift();
+    crate::state::tests::test_mod_mute_and_deafen();
+    crate::state::tests::test_recording_consent();
+    crate::state::tests::test_board_grants();
+    crate::state::tests::test_board_automations();
+    crate::state::tests::test_passive_history_retention();
+    crate::state::tests::test_passive_data_fan_out();
+    crate::state::tests::test_iot_action_tracking();
+    crate::state::tests::test_board_liveness();
+    crate::vs_response::tests::test().await;
+    crate::integration::tests::test().await;
 }
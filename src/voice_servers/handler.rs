@@ -0,0 +1,250 @@
+use crate::cluster;
+use crate::logging;
+use crate::state::state::ServerState;
+use crate::state::types::{PendingReconciliation, VoiceRoomsAndPeers, VoiceServer};
+use crate::voice_servers::types::{VoiceServerDrift, VoiceServerHeartbeat};
+use chrono::Utc;
+use std::collections::{HashMap, HashSet};
+use std::env;
+
+/// Used when no voice server has registered itself,
+/// this is the original single voice server setup.
+pub const DEFAULT_VOICE_SERVER_ID: &str = "0";
+pub const DEFAULT_VOICE_SERVER_QUEUE: &str = "voice_server_consume";
+/// Voice servers that haven't sent a heartbeat
+/// within this window are considered dead.
+pub const HEARTBEAT_TIMEOUT_MS: i64 = 15000;
+/// Reconciliations without a report after
+/// this window are dropped.
+pub const RECONCILIATION_TIMEOUT_MS: i64 = 60000;
+
+pub fn handle_heartbeat(msg: String, server_state: &mut ServerState) {
+    let heartbeat: VoiceServerHeartbeat = match serde_json::from_str(&msg) {
+        Ok(heartbeat) => heartbeat,
+        Err(_) => {
+            logging::console::log_failure("Invalid voice server heartbeat");
+            return;
+        }
+    };
+    let now = Utc::now().timestamp_millis();
+    if let Some(voice_server) = server_state.voice_servers.get_mut(&heartbeat.voiceServerId) {
+        voice_server.last_heartbeat = now;
+        voice_server.consume_queue = heartbeat.consumeQueue;
+        voice_server.echoes_request_id = heartbeat.echoesRequestId;
+        return;
+    }
+    logging::console::log_event(&format!(
+        "Voice server({}) registered",
+        heartbeat.voiceServerId
+    ));
+    server_state.voice_servers.insert(
+        heartbeat.voiceServerId.clone(),
+        VoiceServer {
+            voice_server_id: heartbeat.voiceServerId,
+            consume_queue: heartbeat.consumeQueue,
+            last_heartbeat: now,
+            echoes_request_id: heartbeat.echoesRequestId,
+        },
+    );
+}
+
+/// Picks the least loaded voice server for a new(or moved) room.
+/// - MERLIN_VOICE_PLACEMENT=users balances by the amount of users
+/// - anything else balances by the amount of active rooms
+pub fn place_room(server_state: &ServerState) -> String {
+    if server_state.voice_servers.is_empty() {
+        return DEFAULT_VOICE_SERVER_ID.to_owned();
+    }
+    let by_users = env::var("MERLIN_VOICE_PLACEMENT").map_or(false, |value| value == "users");
+    let mut load: HashMap<&String, i32> = server_state
+        .voice_servers
+        .keys()
+        .map(|voice_server_id| (voice_server_id, 0))
+        .collect();
+    for room in server_state.rooms.values() {
+        if !room.active {
+            continue;
+        }
+        if let Some(current_load) = load.get_mut(&room.voice_server_id) {
+            *current_load += if by_users { room.amount_of_users } else { 1 };
+        }
+    }
+    // ties go to the lowest id, so placement is predictable
+    let (voice_server_id, _) = load
+        .into_iter()
+        .min_by(|a, b| a.1.cmp(&b.1).then(a.0.cmp(b.0)))
+        .unwrap();
+    return voice_server_id.to_owned();
+}
+
+pub fn consume_queue_for_room(server_state: &ServerState, room_id: &i32) -> String {
+    if let Some(room) = server_state.rooms.get(room_id) {
+        return consume_queue_for_voice_server(server_state, &room.voice_server_id);
+    }
+    return DEFAULT_VOICE_SERVER_QUEUE.to_owned();
+}
+
+pub fn consume_queue_for_voice_server(
+    server_state: &ServerState,
+    voice_server_id: &String,
+) -> String {
+    if let Some(voice_server) = server_state.voice_servers.get(voice_server_id) {
+        return voice_server.consume_queue.clone();
+    }
+    return DEFAULT_VOICE_SERVER_QUEUE.to_owned();
+}
+
+/// Whether the voice server the room is on declared
+/// that it sends request ids back with its responses.
+pub fn echoes_request_id(server_state: &ServerState, room_id: &i32) -> bool {
+    return server_state
+        .rooms
+        .get(room_id)
+        .and_then(|room| server_state.voice_servers.get(&room.voice_server_id))
+        .map_or(false, |voice_server| voice_server.echoes_request_id);
+}
+
+/// Removes voice servers that missed their heartbeats,
+/// returns the ids of the removed voice servers.
+pub fn remove_dead_voice_servers(server_state: &mut ServerState) -> Vec<String> {
+    let now = Utc::now().timestamp_millis();
+    let dead: Vec<String> = server_state
+        .voice_servers
+        .values()
+        .filter(|voice_server| now - voice_server.last_heartbeat > HEARTBEAT_TIMEOUT_MS)
+        .map(|voice_server| voice_server.voice_server_id.clone())
+        .collect();
+    for voice_server_id in &dead {
+        server_state.voice_servers.remove(voice_server_id);
+        logging::console::log_failure(&format!(
+            "Voice server({}) missed its heartbeats and was removed",
+            voice_server_id
+        ));
+    }
+    return dead;
+}
+
+/// Every voice server that is registered or has rooms on it.
+pub fn voice_server_ids_in_use(server_state: &ServerState) -> Vec<String> {
+    let mut voice_server_ids: HashSet<String> =
+        server_state.voice_servers.keys().cloned().collect();
+    for room in server_state.rooms.values() {
+        if room.active {
+            voice_server_ids.insert(room.voice_server_id.clone());
+        }
+    }
+    let mut voice_server_ids: Vec<String> = voice_server_ids.into_iter().collect();
+    voice_server_ids.sort();
+    return voice_server_ids;
+}
+
+/// What we believe the voice server has,
+/// only active rooms live on a voice server.
+pub fn expected_rooms_and_peers(
+    server_state: &ServerState,
+    voice_server_id: &String,
+) -> VoiceRoomsAndPeers {
+    return server_state
+        .rooms
+        .values()
+        .filter(|room| room.active && &room.voice_server_id == voice_server_id)
+        .map(|room| (room.room_id.clone(), room.user_ids.clone()))
+        .collect();
+}
+
+/// Compares what a voice server reported with our state.
+/// Rooms and peers only count as missing if they were expected
+/// when we asked and are still there now, anything that changed
+/// in between is left to the next round.
+/// In clustered mode room_owners(room id -> instance id) limits
+/// the drift to rooms we own, the voice servers also hold
+/// rooms for the other instances.
+pub fn find_drift(
+    server_state: &ServerState,
+    reconciliation: &PendingReconciliation,
+    reported: &VoiceRoomsAndPeers,
+    room_owners: Option<&HashMap<i32, String>>,
+) -> VoiceServerDrift {
+    let owned = |room_id: &i32| {
+        room_owners.map_or(true, |room_owners| {
+            room_owners.get(room_id) == Some(&cluster::handler::config().instance_id)
+        })
+    };
+    let current = expected_rooms_and_peers(server_state, &reconciliation.voice_server_id);
+    let mut drift = VoiceServerDrift::default();
+    for (room_id, user_ids) in &reconciliation.expected {
+        if !owned(room_id) {
+            continue;
+        }
+        let current_user_ids = match current.get(room_id) {
+            Some(current_user_ids) => current_user_ids,
+            None => continue,
+        };
+        let reported_user_ids = match reported.get(room_id) {
+            Some(reported_user_ids) => reported_user_ids,
+            None => {
+                drift.missing_rooms.push(room_id.clone());
+                continue;
+            }
+        };
+        for user_id in user_ids {
+            if current_user_ids.contains(user_id) && !reported_user_ids.contains(user_id) {
+                drift.missing_peers.push((room_id.clone(), user_id.clone()));
+            }
+        }
+    }
+    for (room_id, reported_user_ids) in reported {
+        if !owned(room_id) {
+            continue;
+        }
+        let current_user_ids = match current.get(room_id) {
+            Some(current_user_ids) => current_user_ids,
+            None => {
+                drift.unknown_rooms.push(room_id.clone());
+                continue;
+            }
+        };
+        for user_id in reported_user_ids {
+            if !current_user_ids.contains(user_id) {
+                drift.unknown_peers.push((room_id.clone(), user_id.clone()));
+            }
+        }
+    }
+    drift.missing_rooms.sort();
+    drift.missing_peers.sort();
+    drift.unknown_rooms.sort();
+    drift.unknown_peers.sort();
+    return drift;
+}
+
+/// Removes and returns the reconciliations that got a report,
+/// the ones that never will are dropped.
+pub fn take_reported_reconciliations(
+    server_state: &mut ServerState,
+) -> Vec<(PendingReconciliation, VoiceRoomsAndPeers)> {
+    let now = Utc::now().timestamp_millis();
+    let finished: Vec<String> = server_state
+        .pending_reconciliations
+        .iter()
+        .filter(|(_, reconciliation)| {
+            reconciliation.reported.is_some()
+                || now - reconciliation.sent_at > RECONCILIATION_TIMEOUT_MS
+        })
+        .map(|(request_id, _)| request_id.clone())
+        .collect();
+    let mut reported = Vec::new();
+    for request_id in finished {
+        let mut reconciliation = server_state
+            .pending_reconciliations
+            .remove(&request_id)
+            .unwrap();
+        match reconciliation.reported.take() {
+            Some(rooms_and_peers) => reported.push((reconciliation, rooms_and_peers)),
+            None => logging::console::log_failure(&format!(
+                "Voice server({}) never reported its rooms and peers",
+                reconciliation.voice_server_id
+            )),
+        }
+    }
+    return reported;
+}
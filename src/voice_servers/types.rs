@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+/// Published by every voice server on an interval,
+/// the first heartbeat registers the voice server.
+#[allow(non_snake_case)]
+#[derive(Deserialize, Serialize)]
+pub struct VoiceServerHeartbeat {
+    pub voiceServerId: String,
+    pub consumeQueue: String,
+    /// Older voice servers don't send request_id back
+    #[serde(default)]
+    pub echoesRequestId: bool,
+}
+
+/// Differences between what a voice server
+/// reported and what we have in state.
+#[derive(Default, Debug, PartialEq)]
+pub struct VoiceServerDrift {
+    pub missing_rooms: Vec<i32>,
+    /// (room id, user id)
+    pub missing_peers: Vec<(i32, i32)>,
+    pub unknown_rooms: Vec<i32>,
+    /// (room id, user id)
+    pub unknown_peers: Vec<(i32, i32)>,
+}
+
+/// Sent to everyone in a room when its voice server
+/// died and the room was moved to a new one.
+#[derive(Deserialize, Serialize)]
+pub struct RoomVoiceServerChanged {
+    pub room_id: i32,
+    pub voice_server_id: String,
+}
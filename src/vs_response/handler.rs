@@ -1,7 +1,12 @@
 use crate::communication::types::BasicResponse;
 use crate::logging;
+use crate::rooms;
 use crate::state::state::ServerState;
-use crate::vs_response::types::VoiceServerResponse;
+use crate::state::types::VoiceRoomsAndPeers;
+use crate::vs_response::types::{
+    ActiveSpeakerMessage, AudioLevelsMessage, RoomMessage, RoomScopedUserMessage,
+    RoomsAndPeersMessage, UnknownOpMessage, UserMessage,
+};
 use crate::ws_fan::fan;
 
 //used for basic events where
@@ -10,7 +15,8 @@ use crate::ws_fan::fan;
 //but the main details is needed to be sent
 //to the user themselves.
 pub async fn notify_user_and_room(
-    response: VoiceServerResponse,
+    response: RoomScopedUserMessage,
+    op_code: &str,
     state: &mut ServerState,
     op_code_for_other_users: String,
 ) {
@@ -18,65 +24,163 @@ pub async fn notify_user_and_room(
         "VoiceServer triggered op code:{}",
         op_code_for_other_users
     ));
-    let room_id: i32 = grab_room_id(response.d["roomId"].to_string());
-    let user_id: i32 = response.uid.parse().unwrap();
     let basic_response_for_user = BasicResponse {
-        response_op_code: response.op,
-        response_containing_data: response.d.to_string(),
+        response_op_code: op_code.to_owned(),
+        response_containing_data: response.d.raw.to_string(),
     };
     let basic_response_for_room = BasicResponse {
         response_op_code: op_code_for_other_users,
-        response_containing_data: user_id.to_string(),
+        response_containing_data: response.uid.to_string(),
     };
     fan::broadcast_message_to_single_user(
         serde_json::to_string(&basic_response_for_user).unwrap(),
         state,
-        &user_id,
+        &response.uid,
     )
     .await;
     fan::broadcast_message_to_room_excluding_user(
         serde_json::to_string(&basic_response_for_room).unwrap(),
         state,
-        room_id,
-        user_id,
+        response.d.room_id,
+        response.uid,
     )
     .await;
 }
 
-pub async fn notify_user_only(response: VoiceServerResponse, state: &mut ServerState) {
+pub async fn notify_user_only(response: UserMessage, op_code: &str, state: &mut ServerState) {
     let basic_response_for_user = BasicResponse {
-        response_op_code: response.op,
+        response_op_code: op_code.to_owned(),
         response_containing_data: response.d.to_string(),
     };
-    let user_id: i32 = response.uid.parse().unwrap();
     fan::broadcast_message_to_single_user(
         serde_json::to_string(&basic_response_for_user).unwrap(),
         state,
-        &user_id,
+        &response.uid,
     )
     .await;
 }
 
-pub async fn notify_entire_room(response: serde_json::Value, state: &mut ServerState) {
+pub async fn notify_entire_room(response: RoomMessage, op_code: &str, state: &mut ServerState) {
     let basic_response_for_user = BasicResponse {
-        response_op_code: response["op"].to_string(),
-        response_containing_data: response["d"].to_string(),
+        response_op_code: op_code.to_owned(),
+        response_containing_data: response.d.to_string(),
     };
-    let room_id: i32 = response["rid"].to_string().parse().unwrap();
 
     fan::broadcast_message_to_room(
         serde_json::to_string(&basic_response_for_user).unwrap(),
         state,
-        room_id,
+        response.rid,
     )
     .await;
 }
 
-/// Simply remove the wrapping double quotes
-/// from the roomId if it exists
-fn grab_room_id(mut data: String) -> i32 {
-    if data.contains(r#"""#) {
-        data = data[1..data.len() - 1].to_string();
+/// The report is acted on by the reconciliation task,
+/// see rooms::handler::reconcile_voice_server.
+pub fn store_rooms_and_peers(
+    request_id: Option<String>,
+    message: RoomsAndPeersMessage,
+    state: &mut ServerState,
+) {
+    let reconciliation = match request_id
+        .and_then(|request_id| state.pending_reconciliations.get_mut(&request_id))
+    {
+        Some(reconciliation) => reconciliation,
+        None => {
+            logging::console::log_failure("Got rooms and peers for an unknown reconciliation");
+            return;
+        }
+    };
+    let reported: VoiceRoomsAndPeers = message
+        .d
+        .rooms
+        .into_iter()
+        .map(|room| (room.roomId, room.peerIds.into_iter().collect()))
+        .collect();
+    reconciliation.reported = Some(reported);
+}
+
+pub async fn update_active_speaker(message: ActiveSpeakerMessage, state: &mut ServerState) {
+    if let Some(room) = state.rooms.get_mut(&message.rid) {
+        rooms::speaking::set_active_speaker(room, message.d.peerId);
+        rooms::speaking::fan_out_if_due(state, &message.rid).await;
+    }
+}
+
+pub async fn update_audio_levels(message: AudioLevelsMessage, state: &mut ServerState) {
+    if let Some(room) = state.rooms.get_mut(&message.rid) {
+        let levels: Vec<(i32, f64)> = message
+            .d
+            .levels
+            .into_iter()
+            .map(|level| (level.peerId, level.volume))
+            .collect();
+        rooms::speaking::set_audio_levels(room, levels);
+        rooms::speaking::fan_out_if_due(state, &message.rid).await;
+    }
+}
+
+/// Malformed messages are counted and dead lettered,
+/// one bad message shouldn't take down voice.
+pub fn handle_decode_error(msg: &str, error: serde_json::Error, state: &mut ServerState) {
+    state.voice_protocol_counters.decode_errors += 1;
+    state.voice_protocol_counters.dead_letters += 1;
+    logging::console::log_failure(&format!(
+        "Dead lettering voice server message we couldn't decode({} so far):{}, error:{}",
+        state.voice_protocol_counters.decode_errors, msg, error
+    ));
+}
+
+/// Known ops that don't decode are still passed along by
+/// their uid/rid like unknown ops, only messages without
+/// either are dead lettered.
+pub async fn forward_undecodable_op(
+    msg: &str,
+    error: serde_json::Error,
+    state: &mut ServerState,
+) -> bool {
+    if serde_json::from_str::<UnknownOpMessage>(msg).is_err() {
+        handle_decode_error(msg, error, state);
+        return false;
+    }
+    state.voice_protocol_counters.decode_errors += 1;
+    logging::console::log_failure(&format!(
+        "Forwarding voice server message we couldn't decode({} so far):{}, error:{}",
+        state.voice_protocol_counters.decode_errors, msg, error
+    ));
+    return forward_unknown_op(msg, state).await;
+}
+
+/// Ops we don't know about are passed along like they
+/// always were, the same way known user/room ops are.
+/// Returns false if there is nobody to forward it to.
+pub async fn forward_unknown_op(msg: &str, state: &mut ServerState) -> bool {
+    let message: UnknownOpMessage = match serde_json::from_str(msg) {
+        Ok(message) => message,
+        Err(error) => {
+            handle_decode_error(msg, error, state);
+            return false;
+        }
+    };
+    if let Some(uid) = message.uid {
+        let user_message = UserMessage {
+            uid: uid,
+            d: message.d,
+        };
+        notify_user_only(user_message, &message.op, state).await;
+        return true;
+    }
+    if let Some(rid) = message.rid {
+        let room_message = RoomMessage {
+            rid: rid,
+            d: message.d,
+        };
+        notify_entire_room(room_message, &message.op, state).await;
+        return true;
     }
-    data.parse().unwrap()
+    state.voice_protocol_counters.dead_letters += 1;
+    logging::console::log_failure(&format!(
+        "Dead lettering voice server message with no uid or rid({} so far):{}",
+        state.voice_protocol_counters.dead_letters, msg
+    ));
+    return false;
 }
@@ -1,45 +1,74 @@
 use crate::state::state::ServerState;
 use crate::vs_response::handler;
-use crate::vs_response::types::VoiceServerResponse;
-pub async fn route_msg(msg: String, state: &mut ServerState) {
-    let temp_value: serde_json::Value = serde_json::from_str(&msg).unwrap();
-    if temp_value["uid"] != serde_json::Value::Null {
-        let op = temp_value["op"].as_str().unwrap();
-        match op {
-            "you_left_room" => {
-                let response: VoiceServerResponse = serde_json::from_str(&msg).unwrap();
-                handler::notify_user_and_room(response, state, "user_left_room".to_owned()).await;
-            }
-            //we don't need to let them know which one
-            //because they will get the permissions and know
-            //if this new user is a listener/speaker.
-            //
-            //Everytime a user joins, the frontend
-            //requests permissions.
-            "you-joined-as-speaker" | "you-joined-as-peer" => {
-                let response: VoiceServerResponse = serde_json::from_str(&msg).unwrap();
-                handler::notify_user_and_room(response, state, "new_user_joined".to_owned()).await;
-            }
+use crate::vs_response::types::{VoiceServerResponse, VoiceServerResponseOp};
 
-            "you-are-now-a-speaker" => {
-                let response: VoiceServerResponse = serde_json::from_str(&msg).unwrap();
-                handler::notify_user_and_room(response, state, "new_speaker".to_owned()).await;
+/// Returns the message back if it should be dead
+/// lettered(it was malformed with nobody to forward it to).
+pub async fn route_msg(msg: String, state: &mut ServerState) -> Option<String> {
+    let response: VoiceServerResponse = match serde_json::from_str(&msg) {
+        Ok(response) => response,
+        Err(error) => {
+            if !handler::forward_undecodable_op(&msg, error, state).await {
+                return Some(msg);
             }
-
-            //private updates for users only, like
-            //getting recv tracks and connecting send
-            //transports etc.
-            _ => {
-                let response: VoiceServerResponse = serde_json::from_str(&msg).unwrap();
-                handler::notify_user_only(response, state).await;
-            }
-        };
+            return None;
+        }
+    };
+    //the request this is a response to is no longer pending
+    if let Some(request_id) = &response.request_id {
+        state.pending_voice_requests.remove(request_id);
     }
-    //when the response is suppose to go to the entire room
-    //with no filters as to who see what. Some responses
-    //have filters meaning only one user sees like
-    //credentials etc.
-    else {
-        handler::notify_entire_room(temp_value, state).await;
+    let op_code = response.op.op_code();
+    match response.op {
+        VoiceServerResponseOp::YouLeftRoom(message) => {
+            handler::notify_user_and_room(message, op_code, state, "user_left_room".to_owned())
+                .await;
+        }
+        //we don't need to let them know which one
+        //because they will get the permissions and know
+        //if this new user is a listener/speaker.
+        //
+        //Everytime a user joins, the frontend
+        //requests permissions.
+        VoiceServerResponseOp::YouJoinedAsSpeaker(message)
+        | VoiceServerResponseOp::YouJoinedAsPeer(message) => {
+            handler::notify_user_and_room(message, op_code, state, "new_user_joined".to_owned())
+                .await;
+        }
+        VoiceServerResponseOp::YouAreNowASpeaker(message) => {
+            handler::notify_user_and_room(message, op_code, state, "new_speaker".to_owned()).await;
+        }
+        //private updates for users only, like
+        //getting recv tracks and connecting send
+        //transports etc.
+        VoiceServerResponseOp::ConnectTransportSendDone(message)
+        | VoiceServerResponseOp::ConnectTransportRecvDone(message)
+        | VoiceServerResponseOp::SendTrackSendDone(message)
+        | VoiceServerResponseOp::SendTrackRecvDone(message)
+        | VoiceServerResponseOp::GetRecvTracksDone(message)
+        | VoiceServerResponseOp::Error(message) => {
+            handler::notify_user_only(message, op_code, state).await;
+        }
+        //when the response is suppose to go to the entire room
+        //with no filters as to who see what.
+        VoiceServerResponseOp::RoomCreated(message)
+        | VoiceServerResponseOp::RoomDestroyed(message) => {
+            handler::notify_entire_room(message, op_code, state).await;
+        }
+        VoiceServerResponseOp::RoomsAndPeers(message) => {
+            handler::store_rooms_and_peers(response.request_id, message, state);
+        }
+        VoiceServerResponseOp::ActiveSpeaker(message) => {
+            handler::update_active_speaker(message, state).await;
+        }
+        VoiceServerResponseOp::AudioLevels(message) => {
+            handler::update_audio_levels(message, state).await;
+        }
+        VoiceServerResponseOp::Unknown => {
+            if !handler::forward_unknown_op(&msg, state).await {
+                return Some(msg);
+            }
+        }
     }
+    return None;
 }
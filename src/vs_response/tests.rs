@@ -0,0 +1,131 @@
+use crate::rooms;
+use crate::state::state::ServerState;
+use crate::state::tests::mock_room;
+use crate::vs_response::router;
+use crate::vs_response::types::{VoiceServerResponse, VoiceServerResponseOp};
+use tokio::sync::mpsc;
+
+pub async fn test() {
+    test_decoding().await;
+    test_speaking_updates().await;
+}
+
+async fn test_decoding() {
+    let mut state = ServerState::new();
+    // ids can come as strings or numbers
+    let response: VoiceServerResponse = serde_json::from_str(
+        r#"{"op":"you-joined-as-peer","uid":"22","d":{"roomId":3},"request_id":"abc"}"#,
+    )
+    .unwrap();
+    assert_eq!(response.request_id, Some("abc".to_owned()));
+    match response.op {
+        VoiceServerResponseOp::YouJoinedAsPeer(message) => {
+            assert_eq!(message.uid, 22);
+            assert_eq!(message.d.room_id, 3);
+        }
+        _ => panic!("wrong op decoded"),
+    }
+    // malformed messages are counted and handed back
+    // for dead lettering instead of panicking
+    let malformed = r#"{"op":"you_left_room","uid":"abc","d":{"roomId":"3"}}"#.to_owned();
+    let dead_letter = router::route_msg(malformed.clone(), &mut state).await;
+    assert_eq!(dead_letter, Some(malformed));
+    let dead_letter = router::route_msg("not json".to_owned(), &mut state).await;
+    assert_eq!(dead_letter, Some("not json".to_owned()));
+    assert_eq!(state.voice_protocol_counters.decode_errors, 2);
+    assert_eq!(state.voice_protocol_counters.dead_letters, 2);
+    // unknown ops are still forwarded to the user or room
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    state.peer_map.insert(22, tx.clone());
+    let mut room = mock_room(3, "0");
+    room.user_ids.insert(22);
+    state.rooms.insert(3, room);
+    let dead_letter = router::route_msg(
+        r#"{"op":"something-new","uid":"22","d":{"a":1}}"#.to_owned(),
+        &mut state,
+    )
+    .await;
+    assert!(dead_letter.is_none());
+    let message = rx.recv().await.unwrap();
+    assert!(message.to_str().unwrap().contains("something-new"));
+    let dead_letter = router::route_msg(
+        r#"{"op":"something-else","rid":3,"d":{}}"#.to_owned(),
+        &mut state,
+    )
+    .await;
+    assert!(dead_letter.is_none());
+    let message = rx.recv().await.unwrap();
+    assert!(message.to_str().unwrap().contains("something-else"));
+    // so are known ops that don't decode, by whichever id they have
+    let dead_letter = router::route_msg(
+        r#"{"op":"error","rid":3,"d":{"message":"no uid"}}"#.to_owned(),
+        &mut state,
+    )
+    .await;
+    assert!(dead_letter.is_none());
+    let message = rx.recv().await.unwrap();
+    assert!(message.to_str().unwrap().contains("no uid"));
+    let dead_letter = router::route_msg(
+        r#"{"op":"room-created","uid":"22","d":{}}"#.to_owned(),
+        &mut state,
+    )
+    .await;
+    assert!(dead_letter.is_none());
+    let message = rx.recv().await.unwrap();
+    assert!(message.to_str().unwrap().contains("room-created"));
+    assert_eq!(state.voice_protocol_counters.decode_errors, 4);
+    assert_eq!(state.voice_protocol_counters.dead_letters, 2);
+    // unless there is nobody to forward them to
+    let unroutable = r#"{"op":"something-new","d":{}}"#.to_owned();
+    let dead_letter = router::route_msg(unroutable.clone(), &mut state).await;
+    assert_eq!(dead_letter, Some(unroutable));
+    let unroutable = r#"{"op":"room-destroyed","d":{}}"#.to_owned();
+    let dead_letter = router::route_msg(unroutable.clone(), &mut state).await;
+    assert_eq!(dead_letter, Some(unroutable));
+    assert_eq!(state.voice_protocol_counters.dead_letters, 4);
+}
+
+async fn test_speaking_updates() {
+    let mut state = ServerState::new();
+    let mut room = mock_room(3, "0");
+    room.user_ids.insert(22);
+    room.user_ids.insert(33);
+    state.rooms.insert(3, room);
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    state.peer_map.insert(22, tx.clone());
+    state.peer_map.insert(33, tx);
+    // 44 isn't in the room so their level is ignored
+    router::route_msg(
+        r#"{"op":"audio-levels","rid":3,"d":{"levels":[{"peerId":"22","volume":-30},{"peerId":44,"volume":-20}]}}"#
+            .to_owned(),
+        &mut state,
+    )
+    .await;
+    let room = state.rooms.get(&3).unwrap();
+    assert_eq!(rooms::speaking::currently_speaking(room), vec![22]);
+    // the first update goes out right away
+    assert!(!room.speaking.throttle.dirty);
+    router::route_msg(
+        r#"{"op":"active-speaker","rid":3,"d":{"peerId":33}}"#.to_owned(),
+        &mut state,
+    )
+    .await;
+    let room = state.rooms.get(&3).unwrap();
+    assert_eq!(room.speaking.active_speaker, Some(33));
+    // the second is held back by the throttle
+    assert!(room.speaking.throttle.dirty);
+    state
+        .rooms
+        .get_mut(&3)
+        .unwrap()
+        .speaking
+        .throttle
+        .last_fan_out = 0;
+    rooms::speaking::flush_throttled_updates(&mut state).await;
+    assert!(!state.rooms.get(&3).unwrap().speaking.throttle.dirty);
+    // both updates reached both users
+    for _ in 0..4 {
+        let message = rx.recv().await.unwrap();
+        assert!(message.to_str().unwrap().contains("room_speaking_update"));
+    }
+}
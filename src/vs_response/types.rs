@@ -1,8 +1,325 @@
-use serde::{Deserialize, Serialize};
+use crate::communication::types::{
+    GenericRoomIdAndPeerId, VoiceServerClosePeer, VoiceServerCreateRoom, VoiceServerDestroyRoom,
+    VoiceServerListRoomsAndPeers, VoiceServerRecording,
+};
+use serde::de::Error;
+use serde::{Deserialize, Deserializer, Serialize};
 
+/// Everything we send to the voice server,
+/// the op is the tag and d is the content.
 #[derive(Deserialize, Serialize)]
+#[serde(tag = "op", content = "d")]
+pub enum VoiceServerRequestOp {
+    #[serde(rename = "create-room")]
+    CreateRoom(VoiceServerCreateRoom),
+    #[serde(rename = "destroy-room")]
+    DestroyRoom(VoiceServerDestroyRoom),
+    #[serde(rename = "join-as-speaker")]
+    JoinAsSpeaker(GenericRoomIdAndPeerId),
+    #[serde(rename = "join-as-new-peer")]
+    JoinAsNewPeer(GenericRoomIdAndPeerId),
+    #[serde(rename = "add-speaker")]
+    AddSpeaker(GenericRoomIdAndPeerId),
+    #[serde(rename = "remove-speaker")]
+    RemoveSpeaker(GenericRoomIdAndPeerId),
+    #[serde(rename = "close-peer")]
+    ClosePeer(VoiceServerClosePeer),
+    #[serde(rename = "list-rooms-and-peers")]
+    ListRoomsAndPeers(VoiceServerListRoomsAndPeers),
+    // mod imposed mutes and deafens
+    #[serde(rename = "pause-producer")]
+    PauseProducer(GenericRoomIdAndPeerId),
+    #[serde(rename = "resume-producer")]
+    ResumeProducer(GenericRoomIdAndPeerId),
+    #[serde(rename = "pause-consumers")]
+    PauseConsumers(GenericRoomIdAndPeerId),
+    #[serde(rename = "resume-consumers")]
+    ResumeConsumers(GenericRoomIdAndPeerId),
+    #[serde(rename = "start-recording")]
+    StartRecording(VoiceServerRecording),
+    #[serde(rename = "stop-recording")]
+    StopRecording(VoiceServerRecording),
+    // the media soup objects are passed through untouched
+    #[serde(rename = "@connect-transport")]
+    ConnectTransport(serde_json::Value),
+    #[serde(rename = "@send-track")]
+    SendTrack(serde_json::Value),
+    #[serde(rename = "@get-recv-tracks")]
+    GetRecvTracks(serde_json::Value),
+}
+
+impl VoiceServerRequestOp {
+    pub fn op_code(&self) -> &'static str {
+        match self {
+            Self::CreateRoom(_) => "create-room",
+            Self::DestroyRoom(_) => "destroy-room",
+            Self::JoinAsSpeaker(_) => "join-as-speaker",
+            Self::JoinAsNewPeer(_) => "join-as-new-peer",
+            Self::AddSpeaker(_) => "add-speaker",
+            Self::RemoveSpeaker(_) => "remove-speaker",
+            Self::ClosePeer(_) => "close-peer",
+            Self::ListRoomsAndPeers(_) => "list-rooms-and-peers",
+            Self::PauseProducer(_) => "pause-producer",
+            Self::ResumeProducer(_) => "resume-producer",
+            Self::PauseConsumers(_) => "pause-consumers",
+            Self::ResumeConsumers(_) => "resume-consumers",
+            Self::StartRecording(_) => "start-recording",
+            Self::StopRecording(_) => "stop-recording",
+            Self::ConnectTransport(_) => "@connect-transport",
+            Self::SendTrack(_) => "@send-track",
+            Self::GetRecvTracks(_) => "@get-recv-tracks",
+        }
+    }
+
+    pub fn join(as_speaker: bool, data: GenericRoomIdAndPeerId) -> Self {
+        if as_speaker {
+            Self::JoinAsSpeaker(data)
+        } else {
+            Self::JoinAsNewPeer(data)
+        }
+    }
+
+    pub fn web_rtc(op_code: &str, data: serde_json::Value) -> Option<Self> {
+        match op_code {
+            "@connect-transport" => Some(Self::ConnectTransport(data)),
+            "@send-track" => Some(Self::SendTrack(data)),
+            "@get-recv-tracks" => Some(Self::GetRecvTracks(data)),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct VoiceServerRequest {
+    #[serde(flatten)]
+    pub op: VoiceServerRequestOp,
+    pub uid: String,
+    /// echoed back by the voice server in its response
+    pub request_id: String,
+}
+
+/// Everything the voice server sends us.
+#[derive(Deserialize)]
 pub struct VoiceServerResponse {
+    #[serde(default)]
+    pub request_id: Option<String>,
+    #[serde(flatten)]
+    pub op: VoiceServerResponseOp,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op")]
+pub enum VoiceServerResponseOp {
+    // the user gets the details, the rest
+    // of the room gets the user id
+    #[serde(rename = "you_left_room")]
+    YouLeftRoom(RoomScopedUserMessage),
+    #[serde(rename = "you-joined-as-speaker")]
+    YouJoinedAsSpeaker(RoomScopedUserMessage),
+    #[serde(rename = "you-joined-as-peer")]
+    YouJoinedAsPeer(RoomScopedUserMessage),
+    #[serde(rename = "you-are-now-a-speaker")]
+    YouAreNowASpeaker(RoomScopedUserMessage),
+    // private updates for the user only
+    #[serde(rename = "@connect-transport-send-done")]
+    ConnectTransportSendDone(UserMessage),
+    #[serde(rename = "@connect-transport-recv-done")]
+    ConnectTransportRecvDone(UserMessage),
+    #[serde(rename = "@send-track-send-done")]
+    SendTrackSendDone(UserMessage),
+    #[serde(rename = "@send-track-recv-done")]
+    SendTrackRecvDone(UserMessage),
+    #[serde(rename = "@get-recv-tracks-done")]
+    GetRecvTracksDone(UserMessage),
+    #[serde(rename = "error")]
+    Error(UserMessage),
+    // for everyone in the room
+    #[serde(rename = "room-created")]
+    RoomCreated(RoomMessage),
+    #[serde(rename = "room-destroyed")]
+    RoomDestroyed(RoomMessage),
+    // used for reconciliation, see rooms::handler
+    #[serde(rename = "rooms-and-peers")]
+    RoomsAndPeers(RoomsAndPeersMessage),
+    // throttled before reaching the room, see rooms::speaking
+    #[serde(rename = "active-speaker")]
+    ActiveSpeaker(ActiveSpeakerMessage),
+    #[serde(rename = "audio-levels")]
+    AudioLevels(AudioLevelsMessage),
+    /// ops we don't know about are forwarded as is,
+    /// see vs_response::handler::forward_unknown_op
+    #[serde(other)]
+    Unknown,
+}
+
+impl VoiceServerResponseOp {
+    pub fn op_code(&self) -> &'static str {
+        match self {
+            Self::YouLeftRoom(_) => "you_left_room",
+            Self::YouJoinedAsSpeaker(_) => "you-joined-as-speaker",
+            Self::YouJoinedAsPeer(_) => "you-joined-as-peer",
+            Self::YouAreNowASpeaker(_) => "you-are-now-a-speaker",
+            Self::ConnectTransportSendDone(_) => "@connect-transport-send-done",
+            Self::ConnectTransportRecvDone(_) => "@connect-transport-recv-done",
+            Self::SendTrackSendDone(_) => "@send-track-send-done",
+            Self::SendTrackRecvDone(_) => "@send-track-recv-done",
+            Self::GetRecvTracksDone(_) => "@get-recv-tracks-done",
+            Self::Error(_) => "error",
+            Self::RoomCreated(_) => "room-created",
+            Self::RoomDestroyed(_) => "room-destroyed",
+            Self::RoomsAndPeers(_) => "rooms-and-peers",
+            Self::ActiveSpeaker(_) => "active-speaker",
+            Self::AudioLevels(_) => "audio-levels",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct UserMessage {
+    #[serde(deserialize_with = "deserialize_id")]
+    pub uid: i32,
+    pub d: serde_json::Value,
+}
+
+/// The parts of an unknown op we need to forward it,
+/// a uid goes to that user, a rid to the entire room.
+#[derive(Deserialize)]
+pub struct UnknownOpMessage {
     pub op: String,
+    #[serde(default, deserialize_with = "deserialize_optional_id")]
+    pub uid: Option<i32>,
+    #[serde(default, deserialize_with = "deserialize_optional_id")]
+    pub rid: Option<i32>,
+    #[serde(default)]
     pub d: serde_json::Value,
-    pub uid: String,
+}
+
+#[derive(Deserialize)]
+pub struct RoomScopedUserMessage {
+    #[serde(deserialize_with = "deserialize_id")]
+    pub uid: i32,
+    pub d: RoomScopedData,
+}
+
+#[derive(Deserialize)]
+pub struct RoomMessage {
+    #[serde(deserialize_with = "deserialize_id")]
+    pub rid: i32,
+    pub d: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+pub struct RoomsAndPeersMessage {
+    pub d: VoiceServerRoomsAndPeers,
+}
+
+#[derive(Deserialize)]
+pub struct VoiceServerRoomsAndPeers {
+    pub rooms: Vec<VoiceServerRoom>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Deserialize)]
+pub struct VoiceServerRoom {
+    #[serde(deserialize_with = "deserialize_id")]
+    pub roomId: i32,
+    #[serde(deserialize_with = "deserialize_ids")]
+    pub peerIds: Vec<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct ActiveSpeakerMessage {
+    #[serde(deserialize_with = "deserialize_id")]
+    pub rid: i32,
+    pub d: ActiveSpeaker,
+}
+
+/// No peer id means nobody is speaking
+#[allow(non_snake_case)]
+#[derive(Deserialize)]
+pub struct ActiveSpeaker {
+    #[serde(default, deserialize_with = "deserialize_optional_id")]
+    pub peerId: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct AudioLevelsMessage {
+    #[serde(deserialize_with = "deserialize_id")]
+    pub rid: i32,
+    pub d: AudioLevels,
+}
+
+/// Empty levels means the room went silent
+#[derive(Deserialize)]
+pub struct AudioLevels {
+    pub levels: Vec<AudioLevel>,
+}
+
+#[allow(non_snake_case)]
+#[derive(Deserialize)]
+pub struct AudioLevel {
+    #[serde(deserialize_with = "deserialize_id")]
+    pub peerId: i32,
+    pub volume: f64,
+}
+
+/// The data of a room scoped message, kept as is
+/// so it can be forwarded to the user untouched.
+pub struct RoomScopedData {
+    pub room_id: i32,
+    pub raw: serde_json::Value,
+}
+
+impl<'de> Deserialize<'de> for RoomScopedData {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = serde_json::Value::deserialize(deserializer)?;
+        match id_from_value(&raw["roomId"]) {
+            Some(room_id) => Ok(Self {
+                room_id: room_id,
+                raw: raw,
+            }),
+            None => Err(D::Error::custom("missing or invalid roomId")),
+        }
+    }
+}
+
+/// The voice server sends ids as
+/// either strings or numbers.
+fn deserialize_id<'de, D: Deserializer<'de>>(deserializer: D) -> Result<i32, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    id_from_value(&value).ok_or_else(|| D::Error::custom(format!("invalid id {}", value)))
+}
+
+fn deserialize_optional_id<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<i32>, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    if value.is_null() {
+        return Ok(None);
+    }
+    match id_from_value(&value) {
+        Some(id) => Ok(Some(id)),
+        None => Err(D::Error::custom(format!("invalid id {}", value))),
+    }
+}
+
+fn deserialize_ids<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<i32>, D::Error> {
+    let values: Vec<serde_json::Value> = Vec::deserialize(deserializer)?;
+    let mut ids = Vec::new();
+    for value in values {
+        match id_from_value(&value) {
+            Some(id) => ids.push(id),
+            None => return Err(D::Error::custom(format!("invalid id {}", value))),
+        }
+    }
+    return Ok(ids);
+}
+
+fn id_from_value(value: &serde_json::Value) -> Option<i32> {
+    match value {
+        serde_json::Value::Number(number) => number.as_i64().and_then(|id| i32::try_from(id).ok()),
+        serde_json::Value::String(id) => id.parse().ok(),
+        _ => None,
+    }
 }